@@ -1,41 +1,142 @@
 use std::fmt::{Display, Formatter};
 use std::fmt;
 
+use actix_web::http::{header, StatusCode};
 use actix_web::ResponseError;
-use diesel::result::Error as DieselError;
+use diesel::result::{DatabaseErrorInformation, DatabaseErrorKind, Error as DieselError};
+use juniper::{FieldError, IntoFieldError, Object, ScalarValue, Value};
+use paperclip::actix::{api_v2_errors, Apiv2Schema};
 use paperclip::actix::web::HttpResponse;
 use serde::{Deserialize, Serialize};
+use validator::{Validate, ValidationErrors};
 
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// The errors of the REST controllers, every variant is rendered as an RFC 7807 `application/problem+json` body.
+#[api_v2_errors(
+    code = 400, description = "bad-request, pagination-error, query-too-deep, query-too-complex: the request is not valid, the `errors` array lists every invalid field.",
+    code = 401, description = "invalid-credentials, invalid-token: the credentials or the bearer token are not valid.",
+    code = 403, description = "forbidden: the principal is not allowed to perform the action.",
+    code = 404, description = "not-found: the object doesn't exist.",
+    code = 409, description = "unique-violation, duplication-error, deleted-duplication-error: the object conflicts with a stored one, or with a deleted one that can be restored.",
+    code = 412, description = "version-mismatch: the `If-Match` version is not the stored one, the object was changed since it was read.",
+    code = 422, description = "foreign-key-violation, check-violation, not-null-violation: the object breaks a database constraint.",
+    code = 500, description = "internal-server-error, db-error: the request failed on the server.",
+    code = 503, description = "service-unavailable, timeout: the database is not reachable or the request took too long.",
+)]
 #[derive(Debug)]
 pub enum Errors {
     BadReq(Vec<ErrorCode>),
     BadRequest(ErrorCode),
     InternalServerError(ErrorCode),
     NotFound(ErrorCode),
+    Unauthorized(ErrorCode),
+    Forbidden(ErrorCode),
+    Conflict(ErrorCode),
+    PreconditionFailed(ErrorCode),
+    UnprocessableEntity(ErrorCode),
+    ServiceUnavailable(ErrorCode),
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Apiv2Schema)]
 #[serde(rename_all = "camelCase")]
 pub struct ErrorCode {
     pub error_code: String,
     pub message: String,
+    // The invalid input field, it is only set for validation errors.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub field: Option<String>,
+}
+
+/// The RFC 7807 body of the error responses. `errorCode` holds the same value as `StateCode::get_code`,
+/// `instance` and `requestId` are filled by the request id middleware.
+#[derive(Debug, Serialize, Deserialize, Clone, Apiv2Schema)]
+#[serde(rename_all = "camelCase")]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    pub error_code: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub errors: Vec<ErrorCode>,
 }
 
 impl Display for Errors {
-    fn fmt(&self, _f: &mut Formatter<'_>) -> std::fmt::Result {
-        unimplemented!()
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let error_code = self.error_code();
+        write!(f, "{} {}: {}", self.status_code().as_u16(), error_code.error_code, error_code.message)
+    }
+}
+
+impl Errors {
+    pub fn error_code(&self) -> ErrorCode {
+        match self {
+            // The invalid fields are listed apart, in the `errors` of the problem.
+            Errors::BadReq(_) => StateCode::BadRequest.into(),
+            Errors::BadRequest(error)
+            | Errors::InternalServerError(error)
+            | Errors::NotFound(error)
+            | Errors::Unauthorized(error)
+            | Errors::Forbidden(error)
+            | Errors::Conflict(error)
+            | Errors::PreconditionFailed(error)
+            | Errors::UnprocessableEntity(error)
+            | Errors::ServiceUnavailable(error) => error.clone(),
+        }
+    }
+}
+
+impl Errors {
+    pub fn problem_details(&self, instance: Option<String>, request_id: Option<String>) -> ProblemDetails {
+        let status = self.status_code();
+        let error_code = self.error_code();
+        ProblemDetails {
+            problem_type: format!("/problems/{}", error_code.error_code),
+            title: status.canonical_reason().unwrap_or("Error").to_string(),
+            status: status.as_u16(),
+            detail: error_code.message.clone(),
+            error_code: error_code.error_code.clone(),
+            instance,
+            request_id,
+            errors: match self {
+                Errors::BadReq(errors) => errors.clone(),
+                _ => Vec::new(),
+            },
+        }
+    }
+
+    // Render the problem with the path and the id of the request that failed.
+    pub fn problem_response(&self, instance: Option<String>, request_id: Option<String>) -> HttpResponse {
+        HttpResponse::build(self.status_code())
+            .set_header(header::CONTENT_TYPE, PROBLEM_JSON)
+            .json(self.problem_details(instance, request_id))
     }
 }
 
 impl ResponseError for Errors {
-    fn error_response(&self) -> HttpResponse {
+    fn status_code(&self) -> StatusCode {
         match self {
-            Errors::BadReq(errors) => HttpResponse::BadRequest().json(errors),
-            Errors::BadRequest(error) => HttpResponse::BadRequest().json(error),
-            Errors::NotFound(errors) => HttpResponse::NotFound().json(errors),
-            Errors::InternalServerError(errors) => HttpResponse::InternalServerError().json(errors),
+            Errors::BadReq(_) | Errors::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Errors::NotFound(_) => StatusCode::NOT_FOUND,
+            Errors::InternalServerError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Errors::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Errors::Forbidden(_) => StatusCode::FORBIDDEN,
+            Errors::Conflict(_) => StatusCode::CONFLICT,
+            Errors::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            Errors::UnprocessableEntity(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Errors::ServiceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
         }
     }
+
+    fn error_response(&self) -> HttpResponse {
+        self.problem_response(None, None)
+    }
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -45,6 +146,20 @@ pub enum StateCode {
     DBError,
     PaginationError,
     DuplicationError,
+    InvalidCredentials,
+    InvalidToken,
+    Forbidden,
+    BadRequest,
+    UniqueViolation,
+    ForeignKeyViolation,
+    CheckViolation,
+    NotNullViolation,
+    ServiceUnavailable,
+    QueryTooDeep,
+    QueryTooComplex,
+    Timeout,
+    VersionMismatch,
+    DeletedDuplicationError,
 }
 
 impl StateCode {
@@ -55,6 +170,20 @@ impl StateCode {
             Self::DBError => "db-error",
             Self::PaginationError => "pagination-error",
             Self::DuplicationError => "duplication-error",
+            Self::InvalidCredentials => "invalid-credentials",
+            Self::InvalidToken => "invalid-token",
+            Self::Forbidden => "forbidden",
+            Self::BadRequest => "bad-request",
+            Self::UniqueViolation => "unique-violation",
+            Self::ForeignKeyViolation => "foreign-key-violation",
+            Self::CheckViolation => "check-violation",
+            Self::NotNullViolation => "not-null-violation",
+            Self::ServiceUnavailable => "service-unavailable",
+            Self::QueryTooDeep => "query-too-deep",
+            Self::QueryTooComplex => "query-too-complex",
+            Self::Timeout => "timeout",
+            Self::VersionMismatch => "version-mismatch",
+            Self::DeletedDuplicationError => "deleted-duplication-error",
         }
     }
     pub fn get_message(&self) -> &'static str {
@@ -64,6 +193,20 @@ impl StateCode {
             Self::DBError => "There is an error in dealing with Database.",
             Self::PaginationError => "Paginated Data is not valid.",
             Self::DuplicationError => "The object is duplicated.",
+            Self::InvalidCredentials => "The email or the password is not correct.",
+            Self::InvalidToken => "The token is invalid, expired or revoked.",
+            Self::Forbidden => "You are not allowed to perform this action.",
+            Self::BadRequest => "The request is not valid.",
+            Self::UniqueViolation => "The value is already used by another object.",
+            Self::ForeignKeyViolation => "The referenced object does not exist.",
+            Self::CheckViolation => "The value is not allowed.",
+            Self::NotNullViolation => "A required value is missing.",
+            Self::ServiceUnavailable => "The database is not reachable, try again later.",
+            Self::QueryTooDeep => "The query is nested too deeply.",
+            Self::QueryTooComplex => "The query selects too many fields.",
+            Self::Timeout => "The request took too long.",
+            Self::VersionMismatch => "The object was changed since it was read, read it again.",
+            Self::DeletedDuplicationError => "The object conflicts with a deleted one, restore it instead.",
         }
     }
 }
@@ -73,10 +216,41 @@ impl From<StateCode> for ErrorCode {
         Self {
             error_code: item.get_code().to_string(),
             message: item.get_message().to_string(),
+            field: None,
         }
     }
 }
 
+impl ErrorCode {
+    // Keep the code of the state, and name the field or the table the error is about in the message.
+    pub fn with_detail(item: StateCode, detail: &str) -> Self {
+        Self {
+            error_code: item.get_code().to_string(),
+            message: format!("{} ({})", item.get_message(), detail),
+            field: None,
+        }
+    }
+
+    // One error per violated rule, the code is the one given in the `#[validate]` attribute of the field.
+    pub fn from_validation_errors(errors: &ValidationErrors, field_prefix: &str) -> Vec<Self> {
+        let mut field_errors: Vec<_> = errors.field_errors().into_iter().collect();
+        field_errors.sort_by_key(|(field, _)| *field);
+        field_errors
+            .into_iter()
+            .flat_map(|(field, errors)| {
+                errors.iter().map(move |error| Self {
+                    error_code: error.code.to_string(),
+                    message: match &error.message {
+                        Some(message) => message.to_string(),
+                        None => format!("The value of {} is not valid.", field),
+                    },
+                    field: Some(format!("{}{}", field_prefix, field)),
+                })
+            })
+            .collect()
+    }
+}
+
 #[derive(Debug)]
 pub enum Error {
     DBError(DieselError),
@@ -84,8 +258,35 @@ pub enum Error {
     InternalServerError(String),
     NotFound(String),
     HttpRequest(String),
+    Unauthorized(String),
+    Forbidden(String),
+    // The field whose value is already used.
+    UniqueViolation(String),
+    // The parent table where the referenced row is missing.
+    ForeignKeyViolation(String),
+    // The name of the violated check constraint.
+    CheckViolation(String),
+    // The column that is missing a value.
+    NotNullViolation(String),
+    ConnectionError(String),
+    // Every invalid field of the input.
+    Validation(Vec<ErrorCode>),
     DuplicationError,
     DeletedDuplicationError,
+    // The version the client read and the stored one.
+    VersionMismatch(i32, i32),
+    // The maximum depth of the GraphQL query.
+    QueryTooDeep(usize),
+    // The maximum complexity of the GraphQL query.
+    QueryTooComplex(u64),
+    // The timeout of the request, in milliseconds.
+    Timeout(u64),
+    // Every invalid or missing setting of the configuration.
+    InvalidConfig(Vec<String>),
+    // The migrations that aren't applied to the database.
+    PendingMigrations(Vec<String>),
+    // A migration that failed or can't be reverted.
+    Migration(String),
 }
 
 impl fmt::Display for Error {
@@ -96,15 +297,190 @@ impl fmt::Display for Error {
             Error::InternalServerError(error) => write!(f, "{}", error),
             Error::NotFound(error) => write!(f, "{}", error),
             Error::HttpRequest(error) => write!(f, "{}", error),
+            Error::Unauthorized(error) => write!(f, "{}", error),
+            Error::Forbidden(error) => write!(f, "{}", error),
+            Error::UniqueViolation(field) => write!(f, "The value of {} is already used", field),
+            Error::ForeignKeyViolation(parent) => write!(f, "The referenced {} does not exist", parent),
+            Error::CheckViolation(constraint) => write!(f, "The check constraint {} is violated", constraint),
+            Error::NotNullViolation(column) => write!(f, "The value of {} is missing", column),
+            Error::ConnectionError(error) => write!(f, "{}", error),
+            Error::Validation(errors) => write!(f, "{} invalid fields", errors.len()),
             Error::DuplicationError => write!(f, "The object is duplicated"),
             Error::DeletedDuplicationError => write!(f, "The deleted object is duplicated."),
+            Error::VersionMismatch(expected, found) => {
+                write!(f, "The version {} doesn't match the stored version {}", expected, found)
+            }
+            Error::QueryTooDeep(max_depth) => write!(f, "The query is nested deeper than {} levels", max_depth),
+            Error::QueryTooComplex(max_complexity) => write!(f, "The query costs more than {}", max_complexity),
+            Error::Timeout(timeout_ms) => write!(f, "The request did not complete within {} ms", timeout_ms),
+            Error::InvalidConfig(problems) => write!(f, "Invalid configuration: {}", problems.join("; ")),
+            Error::PendingMigrations(names) => write!(f, "The migrations {} are pending", names.join(", ")),
+            Error::Migration(error) => write!(f, "{}", error),
         }
     }
 }
 
+// The graphql-ws connection refuses the init of a subscription with a standard error.
+impl std::error::Error for Error {}
+
+// Classify the diesel error by its kind, and by the message for the violations diesel doesn't have a kind for.
+// Serialization failures and the other unknown errors are kept as DBError, so they can still be retried.
 impl From<DieselError> for Error {
     fn from(err: DieselError) -> Self {
-        Error::DBError(err)
+        match err {
+            DieselError::NotFound => Error::NotFound("Cannot find the object".to_string()),
+            DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, ref info) => {
+                Error::UniqueViolation(unique_field(info.as_ref()))
+            }
+            DieselError::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, ref info) => {
+                Error::ForeignKeyViolation(missing_parent(info.as_ref()))
+            }
+            DieselError::DatabaseError(DatabaseErrorKind::UnableToSendCommand, ref info) => {
+                Error::ConnectionError(info.message().to_string())
+            }
+            DieselError::DatabaseError(DatabaseErrorKind::__Unknown, ref info)
+            if info.message().contains("violates check constraint") => {
+                Error::CheckViolation(info.constraint_name().unwrap_or("unknown").to_string())
+            }
+            DieselError::DatabaseError(DatabaseErrorKind::__Unknown, ref info)
+            if info.message().contains("violates not-null constraint") => {
+                Error::NotNullViolation(not_null_column(info.as_ref()))
+            }
+            DieselError::DatabaseError(DatabaseErrorKind::__Unknown, ref info)
+            if is_connection_failure(info.message()) => {
+                Error::ConnectionError(info.message().to_string())
+            }
+            _ => Error::DBError(err),
+        }
+    }
+}
+
+// Postgres details unique violations as `Key (email)=(a@b.com) already exists.`
+fn unique_field(info: &dyn DatabaseErrorInformation) -> String {
+    info.details()
+        .and_then(|details| between(details, "Key (", ")="))
+        .or_else(|| info.constraint_name())
+        .unwrap_or("unknown")
+        .to_string()
+}
+
+// Postgres details foreign key violations as `Key (team_id)=(...) is not present in table "team".`
+fn missing_parent(info: &dyn DatabaseErrorInformation) -> String {
+    info.details()
+        .and_then(|details| between(details, "in table \"", "\""))
+        .or_else(|| info.constraint_name())
+        .unwrap_or("unknown")
+        .to_string()
+}
+
+// Postgres reports not-null violations as `null value in column "name" violates not-null constraint`.
+fn not_null_column(info: &dyn DatabaseErrorInformation) -> String {
+    info.column_name()
+        .or_else(|| between(info.message(), "column \"", "\""))
+        .unwrap_or("unknown")
+        .to_string()
+}
+
+fn is_connection_failure(message: &str) -> bool {
+    let message = message.to_lowercase();
+    message.contains("server closed the connection")
+        || message.contains("connection refused")
+        || message.contains("terminating connection")
+        || message.contains("no connection to the server")
+}
+
+fn between<'a>(text: &'a str, start: &str, end: &str) -> Option<&'a str> {
+    let from = text.find(start)? + start.len();
+    let to = text[from..].find(end)? + from;
+    Some(&text[from..to])
+}
+
+impl Error {
+    // The field, table or constraint the error is about, when it is known.
+    pub fn field(&self) -> Option<&str> {
+        match self {
+            Error::UniqueViolation(field)
+            | Error::ForeignKeyViolation(field)
+            | Error::CheckViolation(field)
+            | Error::NotNullViolation(field) => Some(field),
+            _ => None,
+        }
+    }
+}
+
+// Choose the status and the code of every domain error, so the controllers can use `?` on the engine results.
+impl From<Error> for Errors {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::NotFound(_) => Errors::NotFound(StateCode::NotFound.into()),
+            Error::BadRequest(detail) | Error::HttpRequest(detail) => {
+                Errors::BadRequest(ErrorCode::with_detail(StateCode::BadRequest, &detail))
+            }
+            Error::Unauthorized(_) => Errors::Unauthorized(StateCode::InvalidToken.into()),
+            Error::Forbidden(_) => Errors::Forbidden(StateCode::Forbidden.into()),
+            Error::UniqueViolation(field) => {
+                Errors::Conflict(ErrorCode::with_detail(StateCode::UniqueViolation, &field))
+            }
+            Error::DuplicationError => Errors::Conflict(StateCode::DuplicationError.into()),
+            Error::DeletedDuplicationError => Errors::Conflict(StateCode::DeletedDuplicationError.into()),
+            Error::VersionMismatch(_, found) => Errors::PreconditionFailed(
+                ErrorCode::with_detail(StateCode::VersionMismatch, &format!("version {}", found)),
+            ),
+            Error::ForeignKeyViolation(parent) => {
+                Errors::UnprocessableEntity(ErrorCode::with_detail(StateCode::ForeignKeyViolation, &parent))
+            }
+            Error::CheckViolation(constraint) => {
+                Errors::UnprocessableEntity(ErrorCode::with_detail(StateCode::CheckViolation, &constraint))
+            }
+            Error::NotNullViolation(column) => {
+                Errors::UnprocessableEntity(ErrorCode::with_detail(StateCode::NotNullViolation, &column))
+            }
+            Error::ConnectionError(_) => Errors::ServiceUnavailable(StateCode::ServiceUnavailable.into()),
+            Error::QueryTooDeep(max_depth) => {
+                Errors::BadRequest(ErrorCode::with_detail(StateCode::QueryTooDeep, &format!("max depth {}", max_depth)))
+            }
+            Error::QueryTooComplex(max_complexity) => Errors::BadRequest(
+                ErrorCode::with_detail(StateCode::QueryTooComplex, &format!("max complexity {}", max_complexity)),
+            ),
+            Error::Timeout(timeout_ms) => {
+                Errors::ServiceUnavailable(ErrorCode::with_detail(StateCode::Timeout, &format!("{} ms", timeout_ms)))
+            }
+            Error::Validation(errors) => Errors::BadReq(errors),
+            Error::DBError(_) => Errors::InternalServerError(StateCode::DBError.into()),
+            Error::InternalServerError(_)
+            | Error::InvalidConfig(_)
+            | Error::PendingMigrations(_)
+            | Error::Migration(_) => {
+                Errors::InternalServerError(StateCode::InternalServerError.into())
+            }
+        }
+    }
+}
+
+impl From<ValidationErrors> for Error {
+    fn from(errors: ValidationErrors) -> Self {
+        Error::Validation(ErrorCode::from_validation_errors(&errors, ""))
+    }
+}
+
+impl From<ValidationErrors> for Errors {
+    fn from(errors: ValidationErrors) -> Self {
+        Errors::BadReq(ErrorCode::from_validation_errors(&errors, ""))
+    }
+}
+
+// Validate every item of a bulk input, the fields of the errors are prefixed with the index of the item like `[2].name`.
+pub fn validate_all<T: Validate>(values: &[T]) -> Result<(), Vec<ErrorCode>> {
+    let errors: Vec<ErrorCode> = values
+        .iter()
+        .enumerate()
+        .filter_map(|(index, value)| value.validate().err().map(|errors| (index, errors)))
+        .flat_map(|(index, errors)| ErrorCode::from_validation_errors(&errors, &format!("[{}].", index)))
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
     }
 }
 
@@ -113,3 +489,150 @@ impl From<String> for Error {
         Error::HttpRequest(req)
     }
 }
+
+// GraphQL errors carry the same code and message as the REST body in their extensions, like
+// `{"message": "...", "path": ["createMember"], "extensions": {"errorCode": "foreign-key-violation", "field": "team"}}`.
+// Juniper adds the path of the failed field itself.
+impl<S: ScalarValue> IntoFieldError<S> for Error {
+    fn into_field_error(self) -> FieldError<S> {
+        let field = self.field().map(|field| field.to_string());
+        let errors = Errors::from(self);
+        let error_code = errors.error_code();
+
+        let mut extensions = Object::with_capacity(4);
+        extensions.add_field("errorCode", Value::scalar(error_code.error_code.clone()));
+        extensions.add_field("message", Value::scalar(error_code.message.clone()));
+        if let Some(field) = field.or_else(|| error_code.field.clone()) {
+            extensions.add_field("field", Value::scalar(field));
+        }
+        // Validation failures list every invalid input field, like the `errors` array of the REST problem.
+        if let Errors::BadReq(error_codes) = &errors {
+            let error_values = error_codes.iter().map(|error_code| {
+                let mut error_value = Object::with_capacity(3);
+                error_value.add_field("errorCode", Value::scalar(error_code.error_code.clone()));
+                error_value.add_field("message", Value::scalar(error_code.message.clone()));
+                if let Some(field) = &error_code.field {
+                    error_value.add_field("field", Value::scalar(field.clone()));
+                }
+                Value::Object(error_value)
+            });
+            extensions.add_field("errors", Value::list(error_values.collect()));
+        }
+        FieldError::new(error_code.message.clone(), Value::Object(extensions))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FakeInfo {
+        message: &'static str,
+        details: Option<&'static str>,
+        constraint_name: Option<&'static str>,
+    }
+
+    impl DatabaseErrorInformation for FakeInfo {
+        fn message(&self) -> &str {
+            self.message
+        }
+        fn details(&self) -> Option<&str> {
+            self.details
+        }
+        fn hint(&self) -> Option<&str> {
+            None
+        }
+        fn table_name(&self) -> Option<&str> {
+            None
+        }
+        fn column_name(&self) -> Option<&str> {
+            None
+        }
+        fn constraint_name(&self) -> Option<&str> {
+            self.constraint_name
+        }
+    }
+
+    fn database_error(kind: DatabaseErrorKind, message: &'static str, details: Option<&'static str>) -> DieselError {
+        DieselError::DatabaseError(kind, Box::new(FakeInfo {
+            message,
+            details,
+            constraint_name: Some("some_constraint"),
+        }))
+    }
+
+    #[test]
+    fn diesel_error_to_domain_error() {
+        let unique = database_error(
+            DatabaseErrorKind::UniqueViolation,
+            "duplicate key value violates unique constraint",
+            Some("Key (email)=(a@b.com) already exists."),
+        );
+        assert!(matches!(Error::from(unique), Error::UniqueViolation(field) if field == "email"));
+
+        let foreign_key = database_error(
+            DatabaseErrorKind::ForeignKeyViolation,
+            "insert or update on table \"member\" violates foreign key constraint",
+            Some("Key (team_id)=(1) is not present in table \"team\"."),
+        );
+        assert!(matches!(Error::from(foreign_key), Error::ForeignKeyViolation(parent) if parent == "team"));
+
+        let not_null = database_error(
+            DatabaseErrorKind::__Unknown,
+            "null value in column \"name\" violates not-null constraint",
+            None,
+        );
+        assert!(matches!(Error::from(not_null), Error::NotNullViolation(column) if column == "name"));
+
+        let check = database_error(DatabaseErrorKind::__Unknown, "new row violates check constraint", None);
+        assert!(matches!(Error::from(check), Error::CheckViolation(constraint) if constraint == "some_constraint"));
+
+        assert!(matches!(Error::from(DieselError::NotFound), Error::NotFound(_)));
+        let serialization = database_error(DatabaseErrorKind::SerializationFailure, "Restart read required", None);
+        assert!(matches!(Error::from(serialization), Error::DBError(_)));
+    }
+
+    #[test]
+    fn errors_to_problem_details() {
+        let problem = Errors::BadReq(vec![StateCode::PaginationError.into()])
+            .problem_details(Some("/member/list_paginated".to_string()), Some("request-1".to_string()));
+
+        assert_eq!(problem.status, 400);
+        assert_eq!(problem.title, "Bad Request");
+        assert_eq!(problem.problem_type, "/problems/bad-request");
+        assert_eq!(problem.errors[0].error_code, "pagination-error");
+        assert_eq!(problem.request_id.as_deref(), Some("request-1"));
+
+        let problem = Errors::from(Error::VersionMismatch(2, 3)).problem_details(None, None);
+        assert_eq!(problem.status, 412);
+        assert_eq!(problem.error_code, "version-mismatch");
+    }
+
+    #[derive(Validate)]
+    struct NewThing {
+        #[validate(length(min = 1, code = "name-length-error"))]
+        name: String,
+    }
+
+    #[test]
+    fn validate_all_prefixes_the_index() {
+        let things = vec![NewThing { name: "thing".to_string() }, NewThing { name: "".to_string() }];
+        let errors = validate_all(&things).unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].error_code, "name-length-error");
+        assert_eq!(errors[0].field.as_deref(), Some("[1].name"));
+    }
+
+    #[test]
+    fn error_into_field_error() {
+        let field_error: FieldError = Error::UniqueViolation("email".to_string()).into_field_error();
+        let extensions = field_error.extensions().as_object_value().unwrap();
+
+        assert_eq!(
+            extensions.get_field_value("errorCode"),
+            Some(&Value::scalar(StateCode::UniqueViolation.get_code().to_string()))
+        );
+        assert_eq!(extensions.get_field_value("field"), Some(&Value::scalar("email".to_string())));
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use juniper::{Definition, InputValue, ScalarValue, Selection, Variables};
+use juniper::parser::parse_document_source;
+
+use error::error::Error;
+use yugabyte::config::GraphQLConfig;
+use yugabyte::model::page::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+
+use crate::gql::schema::Schema;
+
+// The connections, their items are counted `first` or `last` times, or once per item of a default page.
+const CONNECTION_FIELDS: [&str; 4] = ["listAuthUsers", "listUsers", "listTeams", "listMembers"];
+
+// The other fields returning a list, their items are counted `list_size` times.
+const LIST_FIELDS: [&str; 8] = [
+    "members",
+    "memberships",
+    "filterMembersByTheName",
+    "retrieveAllMemberNamesByTeamId",
+    "createBulkAuthUser",
+    "createBulkUsers",
+    "createBulkTeams",
+    "createBulkMembers",
+];
+
+/// The limits of the GraphQL requests, from the `graphql` section of the config.
+///
+/// The depth and the complexity are checked on the parsed query before any resolver runs: every field costs 1,
+/// and the fields selected under a list are counted once per item of the list.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QueryLimits {
+    // The root fields are at depth 1.
+    pub max_depth: usize,
+    pub max_complexity: u64,
+    // The expected length of the lists that have no size argument.
+    pub list_size: u64,
+    pub timeout: Duration,
+}
+
+impl From<&GraphQLConfig> for QueryLimits {
+    fn from(graphql_config: &GraphQLConfig) -> Self {
+        QueryLimits {
+            max_depth: graphql_config.max_depth,
+            max_complexity: graphql_config.max_complexity,
+            list_size: graphql_config.list_size,
+            timeout: Duration::from_millis(graphql_config.timeout_ms),
+        }
+    }
+}
+
+impl QueryLimits {
+    pub fn check_request(
+        &self,
+        query: &str,
+        operation_name: Option<&str>,
+        variables: &Variables,
+        schema: &Schema,
+    ) -> Result<(), Error> {
+        match parse_document_source(query, &schema.schema) {
+            Ok(document) => self.check(&document, operation_name, variables),
+            // Juniper reports the syntax errors itself when the request runs.
+            Err(_) => Ok(()),
+        }
+    }
+
+    // Check the operation that will run, or every operation of the document when it isn't named.
+    pub fn check<'d, S: ScalarValue>(
+        &self,
+        document: &'d [Definition<'d, S>],
+        operation_name: Option<&str>,
+        variables: &Variables<S>,
+    ) -> Result<(), Error> {
+        let mut fragments = HashMap::new();
+        let mut operations = Vec::new();
+        for definition in document {
+            match definition {
+                Definition::Operation(operation) => operations.push(&operation.item),
+                Definition::Fragment(fragment) => {
+                    fragments.insert(fragment.item.name.item, &fragment.item.selection_set[..]);
+                }
+            }
+        }
+
+        let analysis = Analysis { limits: self, fragments, variables };
+        for operation in operations {
+            let name = operation.name.as_ref().map(|name| name.item);
+            if operation_name.is_none() || name == operation_name {
+                analysis.cost(&operation.selection_set, 1, &mut Vec::new())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Analysis<'a, 'd, S> {
+    limits: &'a QueryLimits,
+    fragments: HashMap<&'d str, &'d [Selection<'d, S>]>,
+    variables: &'a Variables<S>,
+}
+
+impl<'a, 'd, S: ScalarValue> Analysis<'a, 'd, S> {
+    // The cost of the selections at the given depth, it fails as soon as a limit is exceeded.
+    // `spreads` holds the fragments being expanded, so a fragment that spreads itself is only counted once.
+    fn cost(&self, selections: &'d [Selection<'d, S>], depth: usize, spreads: &mut Vec<&'d str>) -> Result<u64, Error> {
+        let mut cost: u64 = 0;
+        for selection in selections {
+            let selection_cost = match selection {
+                Selection::Field(field) => {
+                    let field = &field.item;
+                    // The introspection fields don't touch the database, and the IDEs nest them deeply.
+                    if field.name.item.starts_with("__") {
+                        continue;
+                    }
+                    if depth > self.limits.max_depth {
+                        return Err(Error::QueryTooDeep(self.limits.max_depth));
+                    }
+                    let children_cost = match &field.selection_set {
+                        Some(selection_set) => self.cost(selection_set, depth + 1, spreads)?,
+                        None => 0,
+                    };
+                    let list_size = match field.arguments.as_ref() {
+                        Some(arguments) => arguments.item.get("first").or_else(|| arguments.item.get("last")),
+                        None => None,
+                    };
+                    let list_size = match list_size {
+                        Some(size) => self.page_size(&size.item),
+                        None if CONNECTION_FIELDS.contains(&field.name.item) => DEFAULT_PAGE_SIZE as u64,
+                        None if LIST_FIELDS.contains(&field.name.item) => self.limits.list_size,
+                        None => 1,
+                    };
+                    list_size.saturating_mul(children_cost).saturating_add(1)
+                }
+                Selection::FragmentSpread(spread) => {
+                    let name = spread.item.name.item;
+                    match self.fragments.get(name) {
+                        Some(selections) if !spreads.contains(&name) => {
+                            spreads.push(name);
+                            let fragment_cost = self.cost(selections, depth, spreads)?;
+                            spreads.pop();
+                            fragment_cost
+                        }
+                        _ => 0,
+                    }
+                }
+                Selection::InlineFragment(fragment) => self.cost(&fragment.item.selection_set, depth, spreads)?,
+            };
+            cost = cost.saturating_add(selection_cost);
+            if cost > self.limits.max_complexity {
+                return Err(Error::QueryTooComplex(self.limits.max_complexity));
+            }
+        }
+        Ok(cost)
+    }
+
+    // The size of the page asked with a literal or a variable, the resolvers reject the sizes out of the range.
+    fn page_size(&self, value: &InputValue<S>) -> u64 {
+        let value = match value {
+            InputValue::Variable(name) => self.variables.get(name),
+            value => Some(value),
+        };
+        value
+            .and_then(|value| value.as_scalar())
+            .and_then(|scalar| scalar.as_int())
+            .map_or(DEFAULT_PAGE_SIZE, |size| (size as i64).clamp(0, MAX_PAGE_SIZE)) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use juniper::DefaultScalarValue;
+
+    use crate::gql::schema::schema;
+
+    use super::*;
+
+    fn limits() -> QueryLimits {
+        QueryLimits {
+            max_depth: 5,
+            max_complexity: 100,
+            list_size: 10,
+            timeout: Duration::from_secs(1),
+        }
+    }
+
+    fn check(query: &str, variables: Variables<DefaultScalarValue>) -> Result<(), Error> {
+        let schema = schema();
+        let document = parse_document_source(query, &schema.schema).unwrap();
+        limits().check(&document, None, &variables)
+    }
+
+    #[test]
+    fn check_rejects_deep_and_complex_queries() {
+        let team = r#"findTeam(teamId: "8c4f0e4a-1d2b-4c3e-9f5a-6b7c8d9e0f1a")"#;
+
+        // 1 + (1 + 10 * 1) for the team and its members.
+        assert!(check(&format!("{{ {} {{ name members {{ name }} }} }}", team), Variables::new()).is_ok());
+        assert!(matches!(
+            check(&format!("{{ {} {{ members {{ user {{ memberships {{ team {{ name }} }} }} }} }} }}", team), Variables::new()),
+            Err(Error::QueryTooDeep(5))
+        ));
+
+        // 1 + 50 * (1 + (1 + (1 + 10 * 1))) for 50 teams and their members.
+        let teams = "query($first: Int) { listTeams(first: $first) { edges { node { members { name } } } } }";
+        assert!(matches!(
+            check(teams, vec![("first".to_string(), InputValue::scalar(50))].into_iter().collect()),
+            Err(Error::QueryTooComplex(100))
+        ));
+        assert!(check(teams, vec![("first".to_string(), InputValue::scalar(5))].into_iter().collect()).is_ok());
+
+        // The introspection is neither deep nor complex.
+        let introspection = "{ __schema { types { fields { type { ofType { ofType { ofType { name } } } } } } } }";
+        assert!(check(introspection, Variables::new()).is_ok());
+    }
+}
@@ -1,49 +1,190 @@
 use std::sync::Arc;
+use std::time::Duration;
 
-use actix_web::{Error, HttpResponse, web};
-use dotenv::dotenv;
-use juniper::http::GraphQLRequest;
+use actix_web::{Error, HttpRequest, HttpResponse, web};
+use actix_web::http::header;
+use actix_web::rt::time::timeout;
+use juniper::{DefaultScalarValue, Definition, InputValue, IntoFieldError, OperationType, Variables};
+use juniper::http::{GraphQLRequest, GraphQLResponse};
+use juniper::http::graphiql::graphiql_source;
+use juniper::http::playground::playground_source;
+use juniper::parser::parse_document_source;
+use juniper_actix::subscriptions::subscriptions_handler;
+use juniper_graphql_ws::ConnectionConfig;
+use serde::Deserialize;
 use tracing_subscriber::EnvFilter;
 
+use error::error::{ErrorCode, Errors, StateCode};
+use yugabyte::auth::principal::{Principal, principal_from_authorization};
+use yugabyte::config;
 use yugabyte::context::GraphQLContext;
-use yugabyte::db_connection::PgPool;
+use yugabyte::db_connection::{CoreDBPool, DBPoolConvertable};
+use yugabyte::event_bus::EventBus;
 
-use crate::gql::schema::auth_user_schema::{auth_user_schema, AuthUserSchema};
-use crate::gql::schema::member_schema::{member_schema, MemberSchema};
+use crate::gql::limits::QueryLimits;
+use crate::gql::schema::{schema, Schema};
 
+mod limits;
 mod schema;
 
+// The paths of the GraphQL IDEs and of the SDL, they are public by default because they don't touch the data.
+pub const PUBLIC_PATHS: [&str; 3] = ["/graphiql", "/playground", "/graphql/schema.graphql"];
+
 pub fn routes(config: &mut web::ServiceConfig) {
-    let auth_schema = Arc::new(auth_user_schema());
-    let member_schema = Arc::new(member_schema());
     config
-        .data(auth_schema)
-        .data(member_schema)
-        .route("/graphql", web::post().to(auth_user_graphql))
-        .route("/graphql", web::post().to(member_graphql));
+        .data(Arc::new(schema()))
+        .data(QueryLimits::from(&config::config().graphql))
+        .route("/graphql", web::post().to(graphql))
+        .route("/graphql", web::get().to(graphql_get))
+        .route("/subscriptions", web::get().to(subscriptions))
+        .route("/graphql/schema.graphql", web::get().to(schema_sdl))
+        .route("/graphiql", web::get().to(graphiql))
+        .route("/playground", web::get().to(playground));
+}
+
+// The schema in the GraphQL schema definition language, it is printed by `graphql-service --print-schema`.
+pub fn schema_language() -> String {
+    schema().as_schema_language()
+}
+
+/// The body of `POST /graphql`, juniper keeps the fields of its own request private and the limits need them.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphQLBody {
+    query: String,
+    operation_name: Option<String>,
+    variables: Option<InputValue>,
+}
+
+impl GraphQLBody {
+    fn variables(&self) -> Variables {
+        self.variables
+            .as_ref()
+            .and_then(|variables| variables.to_object_value())
+            .map(|variables| variables.into_iter().map(|(name, value)| (name.to_string(), value.clone())).collect())
+            .unwrap_or_default()
+    }
+
+    fn into_request(self) -> GraphQLRequest {
+        GraphQLRequest::new(self.query, self.operation_name, self.variables)
+    }
+
+    // Whether the operation juniper would run is a query. A document it can't parse or run is left to it to report.
+    fn runs_a_query(&self, schema: &Schema) -> bool {
+        let document = match parse_document_source(&self.query, &schema.schema) {
+            Ok(document) => document,
+            Err(_) => return true,
+        };
+        let operation = document
+            .iter()
+            .filter_map(|definition| match definition {
+                Definition::Operation(operation) => Some(&operation.item),
+                Definition::Fragment(_) => None,
+            })
+            .find(|operation| {
+                self.operation_name.is_none() || operation.name.as_ref().map(|name| name.item) == self.operation_name.as_deref()
+            });
+        operation.is_none_or(|operation| operation.operation_type == OperationType::Query)
+    }
+}
+
+/// The query string of `GET /graphql`, `variables` is a JSON encoded object.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphQLQueryParams {
+    query: String,
+    operation_name: Option<String>,
+    variables: Option<String>,
+}
+
+impl GraphQLQueryParams {
+    fn into_body(self) -> Result<GraphQLBody, Errors> {
+        let variables = match self.variables {
+            Some(variables) => Some(serde_json::from_str::<InputValue>(&variables).map_err(|e| {
+                Errors::BadRequest(ErrorCode::with_detail(StateCode::BadRequest, &format!("variables: {}", e)))
+            })?),
+            None => None,
+        };
+        Ok(GraphQLBody { query: self.query, operation_name: self.operation_name, variables })
+    }
 }
 
 // The core handler that provides all GraphQL functionality.
-async fn auth_user_graphql(
+async fn graphql(
+    // The incoming HTTP request, the authentication middleware stores the principal in its extensions
+    req: HttpRequest,
     // The DB connection pool
-    pool: web::Data<PgPool>,
+    pool: web::Data<CoreDBPool>,
     // The GraphQL schema
-    schema: web::Data<Arc<AuthUserSchema>>,
+    schema: web::Data<Arc<Schema>>,
+    // The bus the subscriptions listen to
+    events: web::Data<Arc<EventBus>>,
+    // The depth, complexity and time limits of the request
+    limits: web::Data<QueryLimits>,
     // The incoming HTTP request
-    data: web::Json<GraphQLRequest>,
+    data: web::Json<GraphQLBody>,
+) -> Result<HttpResponse, Error> {
+    execute(req, pool, schema, events, limits, data.into_inner()).await
+}
+
+// The same as the POST handler, with the request read from the query string. A GET must not change anything,
+// so the mutations and the subscriptions are answered with 405.
+async fn graphql_get(
+    req: HttpRequest,
+    pool: web::Data<CoreDBPool>,
+    schema: web::Data<Arc<Schema>>,
+    events: web::Data<Arc<EventBus>>,
+    limits: web::Data<QueryLimits>,
+    params: web::Query<GraphQLQueryParams>,
+) -> Result<HttpResponse, Error> {
+    let data = params.into_inner().into_body()?;
+    if !data.runs_a_query(&schema) {
+        let err = error::error::Error::BadRequest("Only queries can be sent with GET, use POST".to_string());
+        return Ok(HttpResponse::MethodNotAllowed()
+            .header(header::ALLOW, "POST")
+            .content_type("application/json")
+            .body(serde_json::to_string(&GraphQLResponse::<DefaultScalarValue>::error(err.into_field_error()))?));
+    }
+    execute(req, pool, schema, events, limits, data).await
+}
+
+async fn execute(
+    req: HttpRequest,
+    pool: web::Data<CoreDBPool>,
+    schema: web::Data<Arc<Schema>>,
+    events: web::Data<Arc<EventBus>>,
+    limits: web::Data<QueryLimits>,
+    data: GraphQLBody,
 ) -> Result<HttpResponse, Error> {
     // Instantiate a context
-    let context = GraphQLContext {
-        pool: pool.get_ref().to_owned(),
-    };
+    let context = GraphQLContext::new(
+        pool.to_pgpool().clone(),
+        req.extensions().get::<Principal>().cloned(),
+        events.get_ref().clone(),
+    ).with_timeout(limits.timeout);
 
-    // Handle the incoming request and return a string result (or error)
+    // Handle the incoming request and return a string result (or error), the queries over the limits are
+    // rejected before any resolver runs
+    let limits = *limits.get_ref();
     let res = web::block(move || {
-        let res = data.execute(&schema, &context);
-        Ok::<_, serde_json::error::Error>(serde_json::to_string(&res)?)
-    })
-        .await
-        .map_err(Error::from)?;
+        let checked = limits.check_request(&data.query, data.operation_name.as_deref(), &data.variables(), &schema);
+        let request = data.into_request();
+        let res = match checked {
+            Ok(()) => request.execute_sync(&schema, &context),
+            Err(err) => GraphQLResponse::error(err.into_field_error()),
+        };
+        serde_json::to_string(&res)
+    });
+
+    // The blocking thread can't be interrupted, but the resolvers of a request that timed out get no more connections
+    // and their running statements are cancelled, see GraphQLContext::connection
+    let res = match timeout(limits.timeout, res).await {
+        Ok(res) => res.map_err(Error::from)?,
+        Err(_) => {
+            let err = error::error::Error::Timeout(limits.timeout.as_millis() as u64);
+            serde_json::to_string(&GraphQLResponse::<DefaultScalarValue>::error(err.into_field_error()))?
+        }
+    };
 
     // Return the string as a JSON payload
     Ok(HttpResponse::Ok()
@@ -51,38 +192,78 @@ async fn auth_user_graphql(
         .body(res))
 }
 
-async fn member_graphql(
-    // The DB connection pool
-    pool: web::Data<PgPool>,
-    // The GraphQL schema
-    schema: web::Data<Arc<MemberSchema>>,
-    // The incoming HTTP request
-    data: web::Json<GraphQLRequest>,
+// Serve the subscriptions over the graphql-ws protocol.
+async fn subscriptions(
+    req: HttpRequest,
+    stream: web::Payload,
+    pool: web::Data<CoreDBPool>,
+    schema: web::Data<Arc<Schema>>,
+    events: web::Data<Arc<EventBus>>,
 ) -> Result<HttpResponse, Error> {
-    // Instantiate a context
-    let context = GraphQLContext {
-        pool: pool.get_ref().to_owned(),
+    let pool = pool.to_pgpool().clone();
+    let principal = req.extensions().get::<Principal>().cloned();
+    let events = events.get_ref().clone();
+    let init = move |params: Variables| async move {
+        // The browsers can't set headers on a WebSocket, so the token can also come with the connection_init payload.
+        let principal = match (principal, params.get("Authorization").and_then(|value| value.as_string_value())) {
+            (Some(principal), _) => Some(principal),
+            (None, Some(authorization)) => Some(principal_from_authorization(authorization)?),
+            (None, None) => None,
+        };
+        let context = GraphQLContext::new(pool, principal, events);
+        Ok::<_, error::error::Error>(ConnectionConfig::new(context).with_keep_alive_interval(Duration::from_secs(15)))
     };
+    subscriptions_handler(req, stream, schema.get_ref().clone(), init).await
+}
 
-    // Handle the incoming request and return a string result (or error)
-    let res = web::block(move || {
-        let res = data.execute(&schema, &context);
-        Ok::<_, serde_json::error::Error>(serde_json::to_string(&res)?)
-    })
-        .await
-        .map_err(Error::from)?;
+async fn schema_sdl(schema: web::Data<Arc<Schema>>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/plain; charset=utf-8")
+        .body(schema.as_schema_language())
+}
 
-    // Return the string as a JSON payload
-    Ok(HttpResponse::Ok()
-        .content_type("application/json")
-        .body(res))
+async fn graphiql() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(graphiql_source("/graphql", None))
 }
 
-// Initiate the tracing subscriber for RUST_LOG
-pub fn start_tracing() {
-    dotenv().ok();
+async fn playground() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(playground_source("/graphql", None))
+}
 
+// Initiate the tracing subscriber for RUST_LOG
+pub fn start_tracing(filter: &str) {
     tracing_subscriber::fmt()
-        .with_env_filter(EnvFilter::from_default_env())
+        .with_env_filter(EnvFilter::new(filter))
         .init();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn body(query: &str, operation_name: Option<&str>) -> GraphQLBody {
+        GraphQLBody {
+            query: query.to_string(),
+            operation_name: operation_name.map(str::to_string),
+            variables: None,
+        }
+    }
+
+    #[test]
+    fn runs_a_query_tells_the_operation_apart() {
+        let schema = schema();
+        assert!(body("{ __typename }", None).runs_a_query(&schema));
+        assert!(!body("mutation { removeAllUsers }", None).runs_a_query(&schema));
+        assert!(!body("subscription { __typename }", None).runs_a_query(&schema));
+
+        let document = "query Read { __typename } mutation Write { removeAllUsers }";
+        assert!(body(document, Some("Read")).runs_a_query(&schema));
+        assert!(!body(document, Some("Write")).runs_a_query(&schema));
+        // Juniper reports the documents it can't parse.
+        assert!(body("{", None).runs_a_query(&schema));
+    }
+}
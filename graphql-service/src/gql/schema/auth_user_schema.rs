@@ -1,85 +1,113 @@
-use diesel::pg::PgConnection;
 use uuid::Uuid;
 
-use error::error::Error;
+use error::error::{Error, validate_all};
+use yugabyte::auth::policy::{Action, authorize};
 use yugabyte::context::GraphQLContext;
 use yugabyte::engine::auth_user::{
-    delete_all_auth_users, find_auth_user_by_id, insert_bulk_auth_users, list_all_auth_users,
+    delete_all_auth_users, find_auth_user_by_id, insert_bulk_auth_users, list_all_auth_users, update_auth_user,
 };
-use yugabyte::model::auth_user::AuthUser;
-use yugabyte::model::dto::PaginationDTO;
-use yugabyte::model::user::NewUser;
-use juniper::{RootNode, EmptySubscription, GraphQLType, Registry, DefaultScalarValue};
-use juniper::meta::MetaType;
-
-pub struct Query;
-
-// The root Query struct depends on GraphQLContext to provide the connection pool
-// needed to execute the actual Postgres queries.
-#[juniper::graphql_object(Context = GraphQLContext)]
-impl Query {
-    // I think the reason of error is that I need to change the Return type to FieldResult,
-    // and my engine function returns Result<Vec<AuthUser>, Error>
-    pub fn list_auth_users(pagination_dto: PaginationDTO, context: &GraphQLContext) -> Result<Vec<AuthUser>, Error> {
-        let pg_connection: &PgConnection = &context.pool.get().unwrap();
-
-        list_all_auth_users(&pagination_dto, pg_connection)
-    }
-
-    pub fn find_auth_user(auth_user_id: Uuid, context: &GraphQLContext) -> Result<AuthUser, Error> {
-        let pg_connection: &PgConnection = &context.pool.get().unwrap();
+use yugabyte::model::auth_user::{AuthUser, AuthUserPatch, NewAuthUser};
+use yugabyte::engine::session;
+use yugabyte::model::dto::{LoginDTO, TokenPair};
+use yugabyte::model::page::PageRequest;
+use validator::Validate;
+
+use crate::gql::schema::connection::AuthUserConnection;
+
+// Error implements IntoFieldError, so the resolvers return the engine results as they are.
+pub(crate) fn list_auth_users(
+    first: Option<i32>,
+    after: Option<String>,
+    last: Option<i32>,
+    before: Option<String>,
+    context: &GraphQLContext,
+) -> Result<AuthUserConnection, Error> {
+    let principal = context.principal()?;
+    let page_request = PageRequest::from_connection_args(first, after, last, before)?;
+    let pg_connection = &context.connection()?;
+    authorize(principal, Action::ManageAuthUsers, None, pg_connection)?;
+
+    list_all_auth_users(&page_request, pg_connection).map(AuthUserConnection::from)
+}
 
-        find_auth_user_by_id(&auth_user_id, pg_connection)
+pub(crate) fn find_auth_user(auth_user_id: Uuid, context: &GraphQLContext) -> Result<AuthUser, Error> {
+    let principal = context.principal()?;
+    let pg_connection = &context.connection()?;
+    // The auth_user can find itself, the global administrators can find anyone.
+    if principal.auth_user_id != auth_user_id {
+        authorize(principal, Action::ManageAuthUsers, None, pg_connection)?;
     }
+
+    find_auth_user_by_id(&auth_user_id, pg_connection)
 }
 
+pub(crate) fn create_auth_user(
+    context: &GraphQLContext,
+    new_user: NewAuthUser,
+) -> Result<AuthUser, Error> {
+    // The signup is public like POST /auth_user/insert, so no principal is required.
+    new_user.validate()?;
+    let pg_connection = &context.connection()?;
 
-pub struct Mutation;
+    new_user.add_auth_user(pg_connection)
+}
 
-#[juniper::graphql_object(Context = GraphQLContext)]
-impl Mutation {
-    pub fn create_auth_user(
-        context: &GraphQLContext,
-        new_user: NewUser,
-    ) -> Result<AuthUser, Error> {
-        let pg_connection: &PgConnection = &context.pool.get().unwrap();
+pub(crate) fn create_bulk_auth_user(
+    context: &GraphQLContext,
+    new_users: Vec<NewAuthUser>,
+) -> Result<Vec<AuthUser>, Error> {
+    let principal = context.principal()?;
+    let pg_connection = &context.connection()?;
+    authorize(principal, Action::ManageAuthUsers, None, pg_connection)?;
+    validate_all(&new_users).map_err(Error::Validation)?;
 
-        new_user.add_auth_user(pg_connection)
-    }
+    let mut auth_users = Vec::new();
 
-    pub fn create_bulk_auth_user(
-        context: &GraphQLContext,
-        new_users: Vec<NewUser>,
-    ) -> Result<Vec<AuthUser>, Error> {
-        let pg_connection: &PgConnection = &context.pool.get().unwrap();
-
-        let mut auth_users = Vec::new();
-
-        // Step 2: Iterate over the New Teams and create the list of teams to be added in a bulk not to load the execution time of the database.
-        for new_user in new_users {
-            let auth_user = AuthUser {
-                id: Uuid::new_v4(),
-                email: new_user.email,
-                password: new_user.password,
-            };
-            auth_users.push(auth_user);
-        }
-
-        insert_bulk_auth_users(&auth_users, pg_connection)
+    // Hash the password of every new auth_user, then add them in a single insert.
+    for new_user in new_users {
+        auth_users.push(new_user.to_auth_user()?);
     }
 
-    pub fn remove_all_auth_user(
-        context: &GraphQLContext,
-    ) -> Result<Vec<AuthUser>, Error> {
-        let pg_connection: &PgConnection = &context.pool.get().unwrap();
+    insert_bulk_auth_users(&auth_users, pg_connection)
+}
 
-        delete_all_auth_users(pg_connection)
-    }
+pub(crate) fn login(
+    context: &GraphQLContext,
+    login_dto: LoginDTO,
+) -> Result<TokenPair, Error> {
+    login_dto.validate()?;
+    let pg_connection = &context.connection()?;
+
+    session::login(&login_dto, pg_connection)
 }
 
+pub(crate) fn remove_all_auth_user(
+    context: &GraphQLContext,
+) -> Result<i32, Error> {
+    let principal = context.principal()?;
+    let pg_connection = &context.connection()?;
+    authorize(principal, Action::RemoveAll, None, pg_connection)?;
+
+    // Like removeAllUsers and removeAllTeams, the number of deleted auth_users is returned as an Int.
+    delete_all_auth_users(pg_connection).map(|deleted| deleted as i32)
+}
 
-pub type AuthUserSchema = RootNode<'static, Query, Mutation, EmptySubscription<GraphQLContext>>;
+pub(crate) fn patch_auth_user(
+    context: &GraphQLContext,
+    auth_user_id: Uuid,
+    auth_user_patch: AuthUserPatch,
+) -> Result<AuthUser, Error> {
+    let principal = context.principal()?;
+    auth_user_patch.validate()?;
+    let pg_connection = &context.connection()?;
+    // The auth_user can update itself, the global administrators can update anyone,
+    // and only they can grant or revoke the administration.
+    let stored_auth_user = find_auth_user_by_id(&auth_user_id, pg_connection)?;
+    let changes_admin = matches!(auth_user_patch.is_admin, Some(is_admin) if is_admin != stored_auth_user.is_admin);
+    if principal.auth_user_id != auth_user_id || changes_admin {
+        authorize(principal, Action::ManageAuthUsers, None, pg_connection)?;
+    }
 
-pub fn auth_user_schema() -> AuthUserSchema {
-    AuthUserSchema::new(Query, Mutation, EmptySubscription::new())
-}
\ No newline at end of file
+    // A new email is also given to the user, a new password revokes the refresh tokens.
+    update_auth_user(&auth_user_id, &auth_user_patch, pg_connection)
+}
@@ -0,0 +1,132 @@
+use juniper::GraphQLObject;
+
+use yugabyte::context::GraphQLContext;
+use yugabyte::model::auth_user::AuthUser;
+use yugabyte::model::member::Member;
+use yugabyte::model::page::{Keyed, Page};
+use yugabyte::model::team::Team;
+use yugabyte::model::user::User;
+
+// The Relay connections of the list queries, see https://relay.dev/graphql/connections.htm
+#[derive(GraphQLObject)]
+pub struct PageInfo {
+    pub has_next_page: bool,
+    pub has_previous_page: bool,
+    pub start_cursor: Option<String>,
+    pub end_cursor: Option<String>,
+}
+
+impl<T: Keyed> From<&Page<T>> for PageInfo {
+    fn from(page: &Page<T>) -> Self {
+        PageInfo {
+            has_next_page: page.has_next_page,
+            has_previous_page: page.has_previous_page,
+            start_cursor: page.items.first().map(|item| item.cursor().encode()),
+            end_cursor: page.items.last().map(|item| item.cursor().encode()),
+        }
+    }
+}
+
+#[derive(GraphQLObject)]
+#[graphql(context = GraphQLContext)]
+pub struct AuthUserEdge {
+    pub cursor: String,
+    pub node: AuthUser,
+}
+
+#[derive(GraphQLObject)]
+#[graphql(context = GraphQLContext)]
+pub struct AuthUserConnection {
+    pub edges: Vec<AuthUserEdge>,
+    pub page_info: PageInfo,
+}
+
+impl From<Page<AuthUser>> for AuthUserConnection {
+    fn from(page: Page<AuthUser>) -> Self {
+        AuthUserConnection {
+            page_info: PageInfo::from(&page),
+            edges: page.items
+                .into_iter()
+                .map(|node| AuthUserEdge { cursor: node.cursor().encode(), node })
+                .collect(),
+        }
+    }
+}
+
+#[derive(GraphQLObject)]
+#[graphql(context = GraphQLContext)]
+pub struct MemberEdge {
+    pub cursor: String,
+    pub node: Member,
+}
+
+#[derive(GraphQLObject)]
+#[graphql(context = GraphQLContext)]
+pub struct MemberConnection {
+    pub edges: Vec<MemberEdge>,
+    pub page_info: PageInfo,
+}
+
+impl From<Page<Member>> for MemberConnection {
+    fn from(page: Page<Member>) -> Self {
+        MemberConnection {
+            page_info: PageInfo::from(&page),
+            edges: page.items
+                .into_iter()
+                .map(|node| MemberEdge { cursor: node.cursor().encode(), node })
+                .collect(),
+        }
+    }
+}
+
+#[derive(GraphQLObject)]
+#[graphql(context = GraphQLContext)]
+pub struct TeamEdge {
+    pub cursor: String,
+    pub node: Team,
+}
+
+#[derive(GraphQLObject)]
+#[graphql(context = GraphQLContext)]
+pub struct TeamConnection {
+    pub edges: Vec<TeamEdge>,
+    pub page_info: PageInfo,
+}
+
+impl From<Page<Team>> for TeamConnection {
+    fn from(page: Page<Team>) -> Self {
+        TeamConnection {
+            page_info: PageInfo::from(&page),
+            edges: page.items
+                .into_iter()
+                .map(|node| TeamEdge { cursor: node.cursor().encode(), node })
+                .collect(),
+        }
+    }
+}
+
+#[derive(GraphQLObject)]
+#[graphql(context = GraphQLContext)]
+pub struct UserEdge {
+    pub cursor: String,
+    pub node: User,
+}
+
+#[derive(GraphQLObject)]
+#[graphql(context = GraphQLContext)]
+pub struct UserConnection {
+    pub edges: Vec<UserEdge>,
+    pub page_info: PageInfo,
+}
+
+impl From<Page<User>> for UserConnection {
+    fn from(page: Page<User>) -> Self {
+        UserConnection {
+            page_info: PageInfo::from(&page),
+            edges: page.items
+                .into_iter()
+                .map(|node| UserEdge { cursor: node.cursor().encode(), node })
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use std::pin::Pin;
+
+use futures_util::future::ready;
+use futures_util::stream::{Stream, StreamExt};
+use uuid::Uuid;
+
+use error::error::Error;
+use yugabyte::auth::policy::{Action, authorize};
+use yugabyte::auth::principal::Principal;
+use yugabyte::context::GraphQLContext;
+use yugabyte::db_connection::{CoreDBPool, PgPool};
+use yugabyte::model::event::{DomainEvent, TeamChange};
+use yugabyte::model::member::Member;
+
+pub type EventStream<T> = Pin<Box<dyn Stream<Item=T> + Send>>;
+
+// Follow the events of the team that the filter keeps. The principal must be able to see the team when the
+// subscription starts, and again after every member removed from the team or change of the team: the stream
+// ends as soon as the principal lost its access.
+async fn team_events<T, F>(context: &GraphQLContext, team_id: Uuid, filter: F) -> Result<EventStream<T>, Error>
+    where
+        T: Send + 'static,
+        F: Fn(DomainEvent) -> Option<T> + Send + 'static,
+{
+    let principal = context.principal()?.clone();
+    let pool = context.pool.clone();
+    authorize_view_team(pool.clone(), principal.clone(), team_id).await?;
+
+    let events = context
+        .events
+        .subscribe()
+        .filter(move |event| ready(event.team_id() == team_id))
+        .take_while(move |event| {
+            let recheck = !principal.is_admin
+                && matches!(event, DomainEvent::MemberRemoved(_) | DomainEvent::TeamChanged(_));
+            let (pool, principal) = (pool.clone(), principal.clone());
+            async move { !recheck || authorize_view_team(pool, principal, team_id).await.is_ok() }
+        })
+        .filter_map(move |event| ready(filter(event)));
+    Ok(Box::pin(events))
+}
+
+// The resolvers of the subscriptions run on the actix workers, so the database is read on the blocking thread pool.
+async fn authorize_view_team(pool: PgPool, principal: Principal, team_id: Uuid) -> Result<(), Error> {
+    CoreDBPool(pool)
+        .run(move |pg_connection| authorize(&principal, Action::ViewTeam, Some(&team_id), pg_connection))
+        .await
+}
+
+pub(crate) async fn member_added(context: &GraphQLContext, team_id: Uuid) -> Result<EventStream<Member>, Error> {
+    team_events(context, team_id, |event| match event {
+        DomainEvent::MemberAdded(member) => Some(member),
+        _ => None,
+    }).await
+}
+
+pub(crate) async fn member_updated(context: &GraphQLContext, team_id: Uuid) -> Result<EventStream<Member>, Error> {
+    team_events(context, team_id, |event| match event {
+        DomainEvent::MemberUpdated(member) => Some(member),
+        _ => None,
+    }).await
+}
+
+pub(crate) async fn member_removed(context: &GraphQLContext, team_id: Uuid) -> Result<EventStream<Member>, Error> {
+    team_events(context, team_id, |event| match event {
+        DomainEvent::MemberRemoved(member) => Some(member),
+        _ => None,
+    }).await
+}
+
+pub(crate) async fn team_changed(context: &GraphQLContext, team_id: Uuid) -> Result<EventStream<TeamChange>, Error> {
+    team_events(context, team_id, |event| match event {
+        DomainEvent::TeamChanged(team_change) => Some(team_change),
+        _ => None,
+    }).await
+}
@@ -1,103 +1,193 @@
-use diesel::PgConnection;
 use uuid::Uuid;
 
-use error::error::Error;
+use error::error::{Error, validate_all};
+use yugabyte::auth::policy::{Action, authorize};
 use yugabyte::context::GraphQLContext;
+use yugabyte::db_connection::with_transaction;
 use yugabyte::engine::member::{
-    filter_members_by_name, find_member_by_id, get_all_member_names_by_team_id,
-    insert_bulk_members, list_all_members, update_member,
+    delete_member_by_id, filter_members_by_name, find_member_by_id, get_all_member_names_by_team_id,
+    insert_bulk_members, list_all_members, patch_member, update_member,
 };
-use yugabyte::model::dto::PaginationDTO;
-use yugabyte::model::member::{Member, Name, NewMember};
+use yugabyte::model::page::PageRequest;
+use yugabyte::model::member::{Member, MemberPatch, MemberPatchInput, Name, NewMember};
 use yugabyte::util::utils::current_timestamp;
-use juniper::{RootNode, EmptySubscription};
-
-
-pub struct Query;
-
-#[juniper::graphql_object(Context = GraphQLContext)]
-impl Query {
-    pub fn list_members(pagination_dto: PaginationDTO, context: &GraphQLContext) -> Result<Vec<Member>, Error> {
-        let pg_connection: &PgConnection = &context.pool.get().unwrap();
-
-        list_all_members(&pagination_dto, pg_connection)
+use validator::Validate;
+
+use crate::gql::schema::connection::MemberConnection;
+
+pub(crate) fn list_members(
+    first: Option<i32>,
+    after: Option<String>,
+    last: Option<i32>,
+    before: Option<String>,
+    include_deleted: Option<bool>,
+    context: &GraphQLContext,
+) -> Result<MemberConnection, Error> {
+    let principal = context.principal()?;
+    let mut page_request = PageRequest::from_connection_args(first, after, last, before)?;
+    page_request.include_deleted = include_deleted.unwrap_or(false);
+    let pg_connection = &context.connection()?;
+    // Only the global administrators can list the deleted members.
+    if page_request.include_deleted {
+        authorize(principal, Action::ManageDeleted, None, pg_connection)?;
     }
 
-    pub fn find_member_by_id(auth_user_id: Uuid, context: &GraphQLContext) -> Result<Member, Error> {
-        let pg_connection: &PgConnection = &context.pool.get().unwrap();
-
-        find_member_by_id(&auth_user_id, pg_connection)
-    }
+    // The others only list the members of their teams.
+    let visible_team_ids = context.visible_team_ids()?;
+    let page = list_all_members(&page_request, visible_team_ids.as_deref(), pg_connection)?;
+    context.loaders.defer_members(&page.items);
+    Ok(MemberConnection::from(page))
+}
 
-    pub fn filter_members_by_the_name(member_name: String, context: &GraphQLContext) -> Result<Vec<Member>, Error> {
-        let pg_connection: &PgConnection = &context.pool.get().unwrap();
+pub(crate) fn find_member(member_id: Uuid, context: &GraphQLContext) -> Result<Member, Error> {
+    let principal = context.principal()?;
+    let pg_connection = &context.connection()?;
 
-        filter_members_by_name(&member_name, pg_connection)
-    }
+    let found_member = find_member_by_id(&member_id, pg_connection)?;
+    authorize(principal, Action::ViewTeam, Some(&found_member.team_id), pg_connection)?;
+    Ok(found_member)
+}
 
-    pub fn retrieve_all_member_names_by_team_id(
-        team_id: Uuid, context: &GraphQLContext,
-    ) -> Result<Vec<Name>, Error> {
-        let pg_connection: &PgConnection = &context.pool.get().unwrap();
+pub(crate) fn filter_members_by_the_name(member_name: String, context: &GraphQLContext) -> Result<Vec<Member>, Error> {
+    context.principal()?;
+    let pg_connection = &context.connection()?;
 
-        get_all_member_names_by_team_id(&team_id, pg_connection)
-    }
+    let visible_team_ids = context.visible_team_ids()?;
+    let members = filter_members_by_name(&member_name, visible_team_ids.as_deref(), pg_connection)?;
+    context.loaders.defer_members(&members);
+    Ok(members)
 }
 
+pub(crate) fn retrieve_all_member_names_by_team_id(
+    team_id: Uuid, context: &GraphQLContext,
+) -> Result<Vec<Name>, Error> {
+    let principal = context.principal()?;
+    let pg_connection = &context.connection()?;
+    authorize(principal, Action::ViewTeam, Some(&team_id), pg_connection)?;
 
-pub struct Mutation;
+    get_all_member_names_by_team_id(&team_id, pg_connection)
+}
+
+pub(crate) fn create_member(
+    context: &GraphQLContext,
+    new_member: NewMember,
+) -> Result<Member, Error> {
+    let principal = context.principal()?;
+    new_member.validate()?;
+    let pg_connection = &context.connection()?;
+    authorize(principal, Action::ManageMembers, Some(&new_member.team_id), pg_connection)?;
 
-#[juniper::object(Context = GraphQLContext)]
-impl Mutation {
-    pub fn create_member(
-        context: &GraphQLContext,
-        new_member: NewMember,
-    ) -> Result<Member, Error> {
-        let pg_connection: &PgConnection = &context.pool.get().unwrap();
+    new_member.insert_member(pg_connection)
+}
 
-        new_member.insert_member(pg_connection)
+pub(crate) fn create_bulk_members(
+    context: &GraphQLContext,
+    new_members: Vec<NewMember>,
+) -> Result<Vec<Member>, Error> {
+    let principal = context.principal()?;
+    let pg_connection = &context.connection()?;
+
+    validate_all(&new_members).map_err(Error::Validation)?;
+
+    // Step 1: Collect the teams the members are added to.
+    let mut team_ids: Vec<Uuid> = new_members.iter().map(|new_member| new_member.team_id).collect();
+    team_ids.sort();
+    team_ids.dedup();
+
+    let mut members = Vec::new();
+
+    // Step 2: Iterate over the new members and create the list of members to be added in a bulk not to load the execution time of the database.
+    for new_member in new_members {
+        let member = Member {
+            id: Uuid::new_v4(),
+            team_id: new_member.team_id,
+            user_id: new_member.user_id,
+            name: new_member.name,
+            identity_num: new_member.identity_num,
+            role: new_member.role,
+            assigned_at: current_timestamp(),
+            expired_at: new_member.expired_at,
+            modification_date: None,
+            version: 1,
+            deleted_at: None,
+        };
+        members.push(member);
     }
 
-    pub fn create_bulk_members(
-        context: &GraphQLContext,
-        new_members: Vec<NewMember>,
-    ) -> Result<Vec<Member>, Error> {
-        let pg_connection: &PgConnection = &context.pool.get().unwrap();
-
-        let mut members = Vec::new();
-
-        // Step 2: Iterate over the New Teams and create the list of teams to be added in a bulk not to load the execution time of the database.
-        for new_member in new_members {
-            let member = Member {
-                id: Uuid::new_v4(),
-                team_id: new_member.team_id,
-                user_id: new_member.user_id,
-                name: new_member.name,
-                identity_num: new_member.identity_num,
-                role: new_member.role,
-                assigned_at: current_timestamp(),
-                expired_at: new_member.expired_at,
-                modification_date: None,
-            };
-            members.push(member);
+    // Step 3: Check that the principal manages every team and insert the bulk of members in one transaction.
+    with_transaction(pg_connection, |connection| {
+        for team_id in team_ids.iter() {
+            authorize(principal, Action::ManageMembers, Some(team_id), connection)?;
         }
+        insert_bulk_members(&members, connection)
+    })
+}
 
-        insert_bulk_members(&members, pg_connection)
-    }
-
-    pub fn update_one_member(
-        context: &GraphQLContext,
-        member: Member,
-    ) -> Result<Member, Error> {
-        let pg_connection: &PgConnection = &context.pool.get().unwrap();
+pub(crate) fn update_one_member(
+    context: &GraphQLContext,
+    member_id: Uuid,
+    new_member: NewMember,
+    version: Option<i32>,
+) -> Result<Member, Error> {
+    let principal = context.principal()?;
+    new_member.validate()?;
+    let pg_connection = &context.connection()?;
+    with_transaction(pg_connection, |connection| {
+        // The update keeps the team and the user of the member, so the stored team is the one to check.
+        let stored_member = find_member_by_id(&member_id, connection)?;
+        authorize(principal, Action::ManageMembers, Some(&stored_member.team_id), connection)?;
+
+        let member = Member {
+            name: new_member.name.clone(),
+            identity_num: new_member.identity_num.clone(),
+            role: new_member.role,
+            expired_at: new_member.expired_at,
+            // Without a version the member is overwritten whatever its stored version is.
+            version: version.unwrap_or(stored_member.version),
+            ..stored_member
+        };
+        update_member(&member, connection)
+    })
+}
 
-        update_member(&member, pg_connection)
-    }
+pub(crate) fn patch_one_member(
+    context: &GraphQLContext,
+    member_id: Uuid,
+    member_patch: MemberPatchInput,
+    version: Option<i32>,
+) -> Result<Member, Error> {
+    let principal = context.principal()?;
+    let member_patch = MemberPatch::try_from(member_patch)?;
+    member_patch.validate()?;
+    let pg_connection = &context.connection()?;
+    with_transaction(pg_connection, |connection| {
+        let stored_member = find_member_by_id(&member_id, connection)?;
+        authorize(principal, Action::ManageMembers, Some(&stored_member.team_id), connection)?;
+
+        patch_member(&member_id, &member_patch, version, connection)
+    })
 }
 
+pub(crate) fn remove_member(
+    context: &GraphQLContext,
+    member_id: Uuid,
+    version: Option<i32>,
+) -> Result<bool, Error> {
+    let principal = context.principal()?;
+    let pg_connection = &context.connection()?;
+    let stored_member = find_member_by_id(&member_id, pg_connection)?;
+    authorize(principal, Action::ManageMembers, Some(&stored_member.team_id), pg_connection)?;
+
+    delete_member_by_id(&member_id, version, pg_connection)
+}
 
-pub type MemberSchema = RootNode<'static, Query, Mutation, EmptySubscription<GraphQLContext>>;
+pub(crate) fn restore_member(
+    context: &GraphQLContext,
+    member_id: Uuid,
+) -> Result<Member, Error> {
+    let principal = context.principal()?;
+    let pg_connection = &context.connection()?;
+    authorize(principal, Action::ManageDeleted, None, pg_connection)?;
 
-pub fn member_schema() -> MemberSchema {
-    MemberSchema::new(Query, Mutation, EmptySubscription::new())
-}
\ No newline at end of file
+    yugabyte::engine::member::restore_member(&member_id, pg_connection)
+}
@@ -1,2 +1,248 @@
+use juniper::RootNode;
+use uuid::Uuid;
+
+use error::error::Error;
+use yugabyte::context::GraphQLContext;
+use yugabyte::model::auth_user::{AuthUser, AuthUserPatch, NewAuthUser};
+use yugabyte::model::dto::{LoginDTO, TokenPair};
+use yugabyte::model::event::TeamChange;
+use yugabyte::model::member::{Member, MemberPatchInput, Name, NewMember};
+use yugabyte::model::team::{NewTeam, Team, TeamPatch};
+use yugabyte::model::user::{NewUser, User, UserPatch};
+
+use crate::gql::schema::connection::{AuthUserConnection, MemberConnection, TeamConnection, UserConnection};
+use crate::gql::schema::event_schema::EventStream;
+
 pub(crate) mod auth_user_schema;
+pub(crate) mod connection;
+pub(crate) mod event_schema;
 pub(crate) mod member_schema;
+pub(crate) mod team_schema;
+pub(crate) mod user_schema;
+
+// The root Query and Mutation expose the resolvers of every domain module under one schema,
+// the relationship fields between the objects are resolved by the objects themselves.
+pub struct Query;
+
+#[juniper::graphql_object(Context = GraphQLContext)]
+impl Query {
+    pub fn list_auth_users(
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+        context: &GraphQLContext,
+    ) -> Result<AuthUserConnection, Error> {
+        auth_user_schema::list_auth_users(first, after, last, before, context)
+    }
+
+    pub fn find_auth_user(auth_user_id: Uuid, context: &GraphQLContext) -> Result<AuthUser, Error> {
+        auth_user_schema::find_auth_user(auth_user_id, context)
+    }
+
+    pub fn list_users(
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+        include_deleted: Option<bool>,
+        context: &GraphQLContext,
+    ) -> Result<UserConnection, Error> {
+        user_schema::list_users(first, after, last, before, include_deleted, context)
+    }
+
+    pub fn find_user(user_id: Uuid, context: &GraphQLContext) -> Result<User, Error> {
+        user_schema::find_user(user_id, context)
+    }
+
+    pub fn list_teams(
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+        include_deleted: Option<bool>,
+        context: &GraphQLContext,
+    ) -> Result<TeamConnection, Error> {
+        team_schema::list_teams(first, after, last, before, include_deleted, context)
+    }
+
+    pub fn find_team(team_id: Uuid, context: &GraphQLContext) -> Result<Team, Error> {
+        team_schema::find_team(team_id, context)
+    }
+
+    pub fn list_members(
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+        include_deleted: Option<bool>,
+        context: &GraphQLContext,
+    ) -> Result<MemberConnection, Error> {
+        member_schema::list_members(first, after, last, before, include_deleted, context)
+    }
+
+    pub fn find_member_by_id(member_id: Uuid, context: &GraphQLContext) -> Result<Member, Error> {
+        member_schema::find_member(member_id, context)
+    }
+
+    pub fn filter_members_by_the_name(member_name: String, context: &GraphQLContext) -> Result<Vec<Member>, Error> {
+        member_schema::filter_members_by_the_name(member_name, context)
+    }
+
+    pub fn retrieve_all_member_names_by_team_id(team_id: Uuid, context: &GraphQLContext) -> Result<Vec<Name>, Error> {
+        member_schema::retrieve_all_member_names_by_team_id(team_id, context)
+    }
+}
+
+
+pub struct Mutation;
+
+#[juniper::graphql_object(Context = GraphQLContext)]
+impl Mutation {
+    pub fn login(context: &GraphQLContext, login_dto: LoginDTO) -> Result<TokenPair, Error> {
+        auth_user_schema::login(context, login_dto)
+    }
+
+    pub fn create_auth_user(context: &GraphQLContext, new_user: NewAuthUser) -> Result<AuthUser, Error> {
+        auth_user_schema::create_auth_user(context, new_user)
+    }
+
+    pub fn create_bulk_auth_user(context: &GraphQLContext, new_users: Vec<NewAuthUser>) -> Result<Vec<AuthUser>, Error> {
+        auth_user_schema::create_bulk_auth_user(context, new_users)
+    }
+
+    pub fn remove_all_auth_user(context: &GraphQLContext) -> Result<i32, Error> {
+        auth_user_schema::remove_all_auth_user(context)
+    }
+
+    pub fn patch_auth_user(context: &GraphQLContext, auth_user_id: Uuid, patch: AuthUserPatch) -> Result<AuthUser, Error> {
+        auth_user_schema::patch_auth_user(context, auth_user_id, patch)
+    }
+
+    pub fn create_user(context: &GraphQLContext, new_user: NewUser) -> Result<User, Error> {
+        user_schema::create_user(context, new_user)
+    }
+
+    pub fn create_bulk_users(context: &GraphQLContext, new_users: Vec<NewUser>) -> Result<Vec<User>, Error> {
+        user_schema::create_bulk_users(context, new_users)
+    }
+
+    // The update and remove mutations fail with `version-mismatch` when a version is given and the stored one is another.
+    pub fn update_user(
+        context: &GraphQLContext,
+        user_id: Uuid,
+        email: String,
+        name: String,
+        version: Option<i32>,
+    ) -> Result<User, Error> {
+        user_schema::update_user(context, user_id, email, name, version)
+    }
+
+    pub fn patch_user(context: &GraphQLContext, user_id: Uuid, patch: UserPatch, version: Option<i32>) -> Result<User, Error> {
+        user_schema::patch_user(context, user_id, patch, version)
+    }
+
+    pub fn remove_user(context: &GraphQLContext, user_id: Uuid, version: Option<i32>) -> Result<bool, Error> {
+        user_schema::remove_user(context, user_id, version)
+    }
+
+    pub fn remove_all_users(context: &GraphQLContext) -> Result<i32, Error> {
+        user_schema::remove_all_users(context)
+    }
+
+    pub fn restore_user(context: &GraphQLContext, user_id: Uuid) -> Result<User, Error> {
+        user_schema::restore_user(context, user_id)
+    }
+
+    pub fn create_team(context: &GraphQLContext, new_team: NewTeam) -> Result<Team, Error> {
+        team_schema::create_team(context, new_team)
+    }
+
+    pub fn create_bulk_teams(context: &GraphQLContext, new_teams: Vec<NewTeam>) -> Result<Vec<Team>, Error> {
+        team_schema::create_bulk_teams(context, new_teams)
+    }
+
+    pub fn update_team(context: &GraphQLContext, team_id: Uuid, new_team: NewTeam, version: Option<i32>) -> Result<Team, Error> {
+        team_schema::update_team(context, team_id, new_team, version)
+    }
+
+    pub fn patch_team(context: &GraphQLContext, team_id: Uuid, patch: TeamPatch, version: Option<i32>) -> Result<Team, Error> {
+        team_schema::patch_team(context, team_id, patch, version)
+    }
+
+    pub fn remove_team(context: &GraphQLContext, team_id: Uuid, version: Option<i32>) -> Result<bool, Error> {
+        team_schema::remove_team(context, team_id, version)
+    }
+
+    pub fn remove_all_teams(context: &GraphQLContext) -> Result<i32, Error> {
+        team_schema::remove_all_teams(context)
+    }
+
+    pub fn restore_team(context: &GraphQLContext, team_id: Uuid) -> Result<Team, Error> {
+        team_schema::restore_team(context, team_id)
+    }
+
+    pub fn create_member(context: &GraphQLContext, new_member: NewMember) -> Result<Member, Error> {
+        member_schema::create_member(context, new_member)
+    }
+
+    pub fn create_bulk_members(context: &GraphQLContext, new_members: Vec<NewMember>) -> Result<Vec<Member>, Error> {
+        member_schema::create_bulk_members(context, new_members)
+    }
+
+    pub fn update_one_member(
+        context: &GraphQLContext,
+        member_id: Uuid,
+        new_member: NewMember,
+        version: Option<i32>,
+    ) -> Result<Member, Error> {
+        member_schema::update_one_member(context, member_id, new_member, version)
+    }
+
+    pub fn patch_member(
+        context: &GraphQLContext,
+        member_id: Uuid,
+        patch: MemberPatchInput,
+        version: Option<i32>,
+    ) -> Result<Member, Error> {
+        member_schema::patch_one_member(context, member_id, patch, version)
+    }
+
+    pub fn remove_member(context: &GraphQLContext, member_id: Uuid, version: Option<i32>) -> Result<bool, Error> {
+        member_schema::remove_member(context, member_id, version)
+    }
+
+    pub fn restore_member(context: &GraphQLContext, member_id: Uuid) -> Result<Member, Error> {
+        member_schema::restore_member(context, member_id)
+    }
+}
+
+
+pub struct Subscription;
+
+// The changes of a team, they are published by the engine and relayed from the outbox once committed.
+#[juniper::graphql_subscription(Context = GraphQLContext)]
+impl Subscription {
+    pub async fn member_added(context: &GraphQLContext, team_id: Uuid) -> Result<EventStream<Member>, Error> {
+        event_schema::member_added(context, team_id).await
+    }
+
+    pub async fn member_updated(context: &GraphQLContext, team_id: Uuid) -> Result<EventStream<Member>, Error> {
+        event_schema::member_updated(context, team_id).await
+    }
+
+    pub async fn member_removed(context: &GraphQLContext, team_id: Uuid) -> Result<EventStream<Member>, Error> {
+        event_schema::member_removed(context, team_id).await
+    }
+
+    pub async fn team_changed(context: &GraphQLContext, team_id: Uuid) -> Result<EventStream<TeamChange>, Error> {
+        event_schema::team_changed(context, team_id).await
+    }
+}
+
+
+pub type Schema = RootNode<'static, Query, Mutation, Subscription>;
+
+pub fn schema() -> Schema {
+    Schema::new(Query, Mutation, Subscription)
+}
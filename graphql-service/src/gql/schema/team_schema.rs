@@ -0,0 +1,138 @@
+use uuid::Uuid;
+
+use error::error::{Error, validate_all};
+use yugabyte::auth::policy::{Action, authorize};
+use yugabyte::context::GraphQLContext;
+use yugabyte::engine::team::{
+    delete_all_teams, delete_team_by_id, find_team_by_id, insert_bulk_team, list_all_teams,
+};
+use yugabyte::model::page::PageRequest;
+use yugabyte::model::team::{NewTeam, Team, TeamPatch};
+use validator::Validate;
+
+use crate::gql::schema::connection::TeamConnection;
+
+pub(crate) fn list_teams(
+    first: Option<i32>,
+    after: Option<String>,
+    last: Option<i32>,
+    before: Option<String>,
+    include_deleted: Option<bool>,
+    context: &GraphQLContext,
+) -> Result<TeamConnection, Error> {
+    let principal = context.principal()?;
+    let mut page_request = PageRequest::from_connection_args(first, after, last, before)?;
+    page_request.include_deleted = include_deleted.unwrap_or(false);
+    let pg_connection = &context.connection()?;
+    // Only the global administrators can list the deleted teams.
+    if page_request.include_deleted {
+        authorize(principal, Action::ManageDeleted, None, pg_connection)?;
+    }
+
+    let page = list_all_teams(&page_request, pg_connection)?;
+    context.loaders.defer_teams(&page.items);
+    Ok(TeamConnection::from(page))
+}
+
+pub(crate) fn find_team(team_id: Uuid, context: &GraphQLContext) -> Result<Team, Error> {
+    let principal = context.principal()?;
+    let pg_connection = &context.connection()?;
+    // Only the members of the team can see it.
+    authorize(principal, Action::ViewTeam, Some(&team_id), pg_connection)?;
+
+    find_team_by_id(&team_id, pg_connection)
+}
+
+pub(crate) fn create_team(
+    context: &GraphQLContext,
+    new_team: NewTeam,
+) -> Result<Team, Error> {
+    let principal = context.principal()?;
+    new_team.validate()?;
+    let pg_connection = &context.connection()?;
+    authorize(principal, Action::CreateTeam, None, pg_connection)?;
+
+    new_team.insert_team(pg_connection)
+}
+
+pub(crate) fn create_bulk_teams(
+    context: &GraphQLContext,
+    new_teams: Vec<NewTeam>,
+) -> Result<Vec<Team>, Error> {
+    let principal = context.principal()?;
+    validate_all(&new_teams).map_err(Error::Validation)?;
+    let pg_connection = &context.connection()?;
+    authorize(principal, Action::CreateTeam, None, pg_connection)?;
+
+    let teams: Vec<Team> = new_teams
+        .into_iter()
+        .map(|new_team| Team {
+            id: Uuid::new_v4(),
+            name: new_team.name,
+            description: new_team.description,
+            version: 1,
+            deleted_at: None,
+        })
+        .collect();
+
+    insert_bulk_team(&teams, pg_connection)
+}
+
+pub(crate) fn update_team(
+    context: &GraphQLContext,
+    team_id: Uuid,
+    new_team: NewTeam,
+    version: Option<i32>,
+) -> Result<Team, Error> {
+    new_team.validate()?;
+    patch_team(context, team_id, TeamPatch::from(new_team), version)
+}
+
+pub(crate) fn patch_team(
+    context: &GraphQLContext,
+    team_id: Uuid,
+    team_patch: TeamPatch,
+    version: Option<i32>,
+) -> Result<Team, Error> {
+    let principal = context.principal()?;
+    team_patch.validate()?;
+    let pg_connection = &context.connection()?;
+    authorize(principal, Action::UpdateTeam, Some(&team_id), pg_connection)?;
+
+    yugabyte::engine::team::update_team(&team_id, &team_patch, version, pg_connection)
+}
+
+pub(crate) fn remove_team(
+    context: &GraphQLContext,
+    team_id: Uuid,
+    version: Option<i32>,
+) -> Result<bool, Error> {
+    let principal = context.principal()?;
+    let pg_connection = &context.connection()?;
+    // Only the owners of the team can delete it.
+    authorize(principal, Action::DeleteTeam, Some(&team_id), pg_connection)?;
+
+    delete_team_by_id(&team_id, version, pg_connection)
+}
+
+pub(crate) fn remove_all_teams(
+    context: &GraphQLContext,
+) -> Result<i32, Error> {
+    let principal = context.principal()?;
+    let pg_connection = &context.connection()?;
+    authorize(principal, Action::RemoveAll, None, pg_connection)?;
+
+    // GraphQL has no unsigned integers, so the number of deleted teams is returned as an Int.
+    delete_all_teams(pg_connection).map(|deleted| deleted as i32)
+}
+
+pub(crate) fn restore_team(
+    context: &GraphQLContext,
+    team_id: Uuid,
+) -> Result<Team, Error> {
+    let principal = context.principal()?;
+    let pg_connection = &context.connection()?;
+    authorize(principal, Action::ManageDeleted, None, pg_connection)?;
+
+    yugabyte::engine::team::restore_team(&team_id, pg_connection)
+}
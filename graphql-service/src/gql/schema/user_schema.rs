@@ -0,0 +1,136 @@
+use uuid::Uuid;
+
+use error::error::{Error, validate_all};
+use yugabyte::auth::policy::{Action, authorize};
+use yugabyte::context::GraphQLContext;
+use yugabyte::engine::user::{
+    delete_all_users, delete_user_by_id, find_user_by_id, insert_bulk_users, list_all_users,
+};
+use yugabyte::model::page::PageRequest;
+use yugabyte::model::user::{NewUser, User, UserPatch};
+use validator::Validate;
+
+use crate::gql::schema::connection::UserConnection;
+
+pub(crate) fn list_users(
+    first: Option<i32>,
+    after: Option<String>,
+    last: Option<i32>,
+    before: Option<String>,
+    include_deleted: Option<bool>,
+    context: &GraphQLContext,
+) -> Result<UserConnection, Error> {
+    let principal = context.principal()?;
+    let mut page_request = PageRequest::from_connection_args(first, after, last, before)?;
+    page_request.include_deleted = include_deleted.unwrap_or(false);
+    let pg_connection = &context.connection()?;
+    // Only the global administrators can list the deleted users.
+    if page_request.include_deleted {
+        authorize(principal, Action::ManageDeleted, None, pg_connection)?;
+    }
+
+    let page = list_all_users(&page_request, pg_connection)?;
+    context.loaders.defer_users(&page.items);
+    Ok(UserConnection::from(page))
+}
+
+pub(crate) fn find_user(user_id: Uuid, context: &GraphQLContext) -> Result<User, Error> {
+    context.principal()?;
+    let pg_connection = &context.connection()?;
+
+    find_user_by_id(&user_id, pg_connection)
+}
+
+pub(crate) fn create_user(
+    context: &GraphQLContext,
+    new_user: NewUser,
+) -> Result<User, Error> {
+    let principal = context.principal()?;
+    new_user.validate()?;
+    let pg_connection = &context.connection()?;
+
+    new_user.add_user(principal, pg_connection)
+}
+
+pub(crate) fn create_bulk_users(
+    context: &GraphQLContext,
+    new_users: Vec<NewUser>,
+) -> Result<Vec<User>, Error> {
+    let principal = context.principal()?;
+    validate_all(&new_users).map_err(Error::Validation)?;
+    let pg_connection = &context.connection()?;
+
+    let users = new_users
+        .iter()
+        .map(|new_user| new_user.to_user(principal, pg_connection))
+        .collect::<Result<Vec<User>, Error>>()?;
+
+    insert_bulk_users(&users, pg_connection)
+}
+
+pub(crate) fn update_user(
+    context: &GraphQLContext,
+    user_id: Uuid,
+    email: String,
+    name: String,
+    version: Option<i32>,
+) -> Result<User, Error> {
+    let user_patch = UserPatch {
+        email: Some(email),
+        name: Some(name),
+    };
+    patch_user(context, user_id, user_patch, version)
+}
+
+pub(crate) fn patch_user(
+    context: &GraphQLContext,
+    user_id: Uuid,
+    user_patch: UserPatch,
+    version: Option<i32>,
+) -> Result<User, Error> {
+    let principal = context.principal()?;
+    user_patch.validate()?;
+    let pg_connection = &context.connection()?;
+    // The user linked to the principal can update itself, the global administrators can update anyone.
+    let stored_user = find_user_by_id(&user_id, pg_connection)?;
+    if stored_user.auth_user_id != Some(principal.auth_user_id) {
+        authorize(principal, Action::ManageAuthUsers, None, pg_connection)?;
+    }
+
+    // A new email is also given to the auth_user.
+    yugabyte::engine::user::update_user(&user_id, &user_patch, version, pg_connection)
+}
+
+pub(crate) fn remove_user(
+    context: &GraphQLContext,
+    user_id: Uuid,
+    version: Option<i32>,
+) -> Result<bool, Error> {
+    let principal = context.principal()?;
+    let pg_connection = &context.connection()?;
+    authorize(principal, Action::ManageAuthUsers, None, pg_connection)?;
+
+    delete_user_by_id(&user_id, version, pg_connection)
+}
+
+pub(crate) fn remove_all_users(
+    context: &GraphQLContext,
+) -> Result<i32, Error> {
+    let principal = context.principal()?;
+    let pg_connection = &context.connection()?;
+    authorize(principal, Action::RemoveAll, None, pg_connection)?;
+
+    // GraphQL has no unsigned integers, so the number of deleted users is returned as an Int.
+    delete_all_users(pg_connection).map(|deleted| deleted as i32)
+}
+
+pub(crate) fn restore_user(
+    context: &GraphQLContext,
+    user_id: Uuid,
+) -> Result<User, Error> {
+    let principal = context.principal()?;
+    let pg_connection = &context.connection()?;
+    authorize(principal, Action::ManageDeleted, None, pg_connection)?;
+
+    yugabyte::engine::user::restore_user(&user_id, pg_connection)
+}
@@ -1,30 +1,64 @@
 use std::env;
+use std::sync::Arc;
 
 use actix_web::{App, HttpServer};
 use actix_web::middleware::Logger;
 use actix_web::web::{Data, JsonConfig};
 
-use yugabyte::db_connection::CoreDBPool;
+use yugabyte::config::{check_config, CliArgs, Config, startup_error};
+use yugabyte::db_connection::{CoreDBPool, DBPoolConvertable};
+use yugabyte::event_bus::{EventBus, spawn_outbox_relay};
+use yugabyte::middleware::authentication::Authentication;
+use yugabyte::middleware::request_id::RequestIdentifier;
+use yugabyte::migration::{migrate_on_startup, run_command};
 
-use crate::gql::{routes, start_tracing};
+use crate::gql::{PUBLIC_PATHS, routes, schema_language, start_tracing};
 
 mod gql;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    start_tracing();
-    let core_db_pool_data = Data::new(CoreDBPool::default());
-    dotenv::dotenv().expect("Failed to read .env file");
+    // Print the SDL for the code generators and the schema diff checks, without starting the server.
+    if env::args().any(|arg| arg == "--print-schema") {
+        println!("{}", schema_language());
+        return Ok(());
+    }
+
+    let cli_args = CliArgs::from_env().map_err(startup_error)?;
+    if cli_args.check_config {
+        let config = Config::from_sources(cli_args.config_path.as_deref()).map_err(startup_error)?;
+        return check_config(&config).map_err(startup_error);
+    }
+
+    // Every invalid setting is reported at once.
+    let config = Config::load(cli_args.config_path.as_deref()).map_err(startup_error)?.install();
+    start_tracing(&config.log.filter);
+    let core_db_pool = CoreDBPool::new(&config.database).map_err(startup_error)?;
+    // The admin commands work on the database and exit, without starting the server.
+    if let Some(command) = cli_args.migrate {
+        return run_command(command, core_db_pool.to_pgpool()).map_err(startup_error);
+    }
+    migrate_on_startup(config.database.migrations, core_db_pool.to_pgpool()).map_err(startup_error)?;
+    let core_db_pool_data = Data::new(core_db_pool);
+    let json_limit = config.server.json_limit;
+
+    // Relay the committed changes of every service from the outbox to the subscriptions of this instance.
+    let events = Arc::new(EventBus::default());
+    spawn_outbox_relay(core_db_pool_data.to_pgpool().clone(), events.clone(), config.outbox);
 
     HttpServer::new(move || {
         App::new()
+            // The GraphQL endpoint accepts anonymous calls for the login and createAuthUser mutations, the other resolvers
+            // require the principal.
+            .wrap(Authentication::from_config(&config.auth, &PUBLIC_PATHS).optional("/graphql").optional("/subscriptions"))
+            .wrap(RequestIdentifier)
             .wrap(Logger::default())
-            .data(JsonConfig::default().limit(4096))
+            .data(JsonConfig::default().limit(json_limit))
             .app_data(core_db_pool_data.clone())
+            .data(events.clone())
             .configure(routes)
     })
-        .bind(format!("{}:{}", env::var("HOST").unwrap(), env::var("GRAPHQL_PORT").unwrap()))
-        .expect("Server binding exception")
+        .bind((config.server.host.as_str(), config.server.graphql_port))?
         .run()
         .await
 }
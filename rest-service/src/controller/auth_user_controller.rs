@@ -1,142 +1,285 @@
-use paperclip::actix::{
-    api_v2_operation,
-    web::{self, Query},
-};
+use paperclip::actix::{api_v2_operation, web};
 use paperclip::actix::web::Json;
 use uuid::Uuid;
 
-use error::error::Errors;
-use error::error::StateCode::{DBError, NotFound};
-use error::error::StateCode::PaginationError;
-use yugabyte::db_connection::{CoreDBPool, pgdata_to_pgconnection};
-use yugabyte::engine::auth_user::{count_auth_users, delete_all_auth_users, delete_auth_user_by_id, find_auth_user_by_id, insert_bulk_auth_users, list_all_auth_users};
+use error::error::{Error, Errors};
+use error::error::StateCode::{InvalidCredentials, InvalidToken};
+use yugabyte::auth::policy::Action;
+use yugabyte::auth::principal::Principal;
+use yugabyte::db_connection::{CoreDBPool, with_transaction};
+use yugabyte::engine::auth_user::{count_auth_users, delete_all_auth_users, delete_auth_user_by_id, find_auth_user_by_id, hash_plaintext_passwords, list_all_auth_users, update_auth_user};
 use yugabyte::engine::member::delete_all_members;
-use yugabyte::model::auth_user::AuthUser;
-use yugabyte::model::dto::{PaginatedResponseDTO, PaginationDTO, SuccessResponse};
-use yugabyte::model::user::NewUser;
+use yugabyte::engine::session::{login, logout, refresh_session};
+use yugabyte::model::auth_user::{AuthUser, AuthUserPatch, NewAuthUser};
+use yugabyte::model::dto::{LoginDTO, PaginatedResponseDTO, PaginationDTO, RefreshTokenDTO, SuccessResponse, TokenPair};
+use yugabyte::model::page::PageRequest;
+use yugabyte::model::patch::Patch;
+
+use crate::extractor::{ValidatedJson, ValidatedQuery};
+use crate::controller::check_permission;
 
 #[api_v2_operation]
-pub(crate) fn list_auth_users_api(
-    Query(pagination_dto): Query<PaginationDTO>,
+pub(crate) async fn list_auth_users_api(
+    ValidatedQuery(pagination_dto): ValidatedQuery<PaginationDTO>,
+    principal: Principal,
     pool: web::Data<CoreDBPool>,
 ) -> Result<Json<SuccessResponse<PaginatedResponseDTO<AuthUser>>>, Errors> {
-    // Step 1: Get the connection from pool data.
-    let pg_connection = pgdata_to_pgconnection(pool);
-
-    // Step 2: Count all auth_users.
-    match count_auth_users(&pg_connection) {
-        Ok(auth_users_count) => {
-            // Step 3: List all paginated auth_users.
-            match list_all_auth_users(&pagination_dto, &pg_connection) {
-                Ok(paginated_list) => {
-                    let response = PaginatedResponseDTO {
-                        paginated_list,
-                        count: auth_users_count,
-                    };
-
-                    // Step 4: Fire the response.
-                    Ok(Json(SuccessResponse {
-                        message: format!("Successfully retrieved all auth users."),
-                        data: response,
-                    }))
-                }
-                Err(_) => {
-                    Err(Errors::BadRequest(PaginationError.into()))
+    // The cursors are decoded before touching the database, an invalid one is a bad request.
+    let page_request = PageRequest::from_pagination(&pagination_dto).map_err(Errors::from)?;
+
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: Only the global administrators can list the auth_users.
+        check_permission(&principal, Action::ManageAuthUsers, None, pg_connection)?;
+
+        // Step 3: Count all auth_users.
+        match count_auth_users(pg_connection) {
+            Ok(auth_users_count) => {
+                // Step 4: List all paginated auth_users.
+                match list_all_auth_users(&page_request, pg_connection) {
+                    Ok(page) => {
+                        let response = page.into_response(auth_users_count);
+
+                        // Step 5: Fire the response.
+                        Ok(Json(SuccessResponse {
+                            message: "Successfully retrieved all auth users.".to_string(),
+                            data: response,
+                        }))
+                    }
+                    Err(err) => Err(err.into())
                 }
             }
+            Err(err) => Err(err.into())
         }
-        Err(_) => {
-            Err(Errors::InternalServerError(DBError.into()))
-        }
-    }
+    }).await
 }
 
 #[api_v2_operation]
-pub(crate) fn insert_auth_user_api(
-    new_user: Json<NewUser>,
+pub(crate) async fn insert_auth_user_api(
+    new_user: ValidatedJson<NewAuthUser>,
     pool: web::Data<CoreDBPool>,
 ) -> Result<Json<SuccessResponse<AuthUser>>, Errors> {
-    // Step 1: Get the connection from pool data.
-    let pg_connection = pgdata_to_pgconnection(pool);
-
-    // Step 2: Insert the Auth_User into the database
-    match new_user.add_auth_user(&pg_connection) {
-        // Step 3: Fire the inserted auth_user
-        Ok(inserted_auth_user) => Ok(Json(SuccessResponse {
-            message: format!("Successfully added the new Auth User."),
-            data: inserted_auth_user,
-        })),
-        Err(_) => Err(Errors::InternalServerError(DBError.into()))
-    }
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: Insert the Auth_User into the database
+        match new_user.add_auth_user(pg_connection) {
+            // Step 3: Fire the inserted auth_user
+            Ok(inserted_auth_user) => Ok(Json(SuccessResponse {
+                message: "Successfully added the new Auth User.".to_string(),
+                data: inserted_auth_user,
+            })),
+            Err(err) => Err(err.into())
+        }
+    }).await
 }
 
 #[api_v2_operation]
 pub async fn remove_auth_user_api(
     auth_user_id: web::Path<Uuid>,
+    principal: Principal,
     pool: web::Data<CoreDBPool>,
 ) -> Result<Json<SuccessResponse<bool>>, Errors> {
-    // Step 1: Get the connection from pool data
-    let pg_connection = pgdata_to_pgconnection(pool);
-
-    // Step 2: Delete the auth_user from the database.
-    if !delete_auth_user_by_id(&auth_user_id, &pg_connection) {
-        Err(Errors::InternalServerError(DBError.into()))
-    } else {
-        // Step 3: Fire the response.
-        Ok(Json(SuccessResponse {
-            message: format!("Successfully deleted the auth_user."),
-            data: true,
-        }))
-    }
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: The auth_user can remove itself, the global administrators can remove anyone.
+        if principal.auth_user_id != *auth_user_id {
+            check_permission(&principal, Action::ManageAuthUsers, None, pg_connection)?;
+        }
+
+        // Step 3: Delete the auth_user from the database.
+        match delete_auth_user_by_id(&auth_user_id, pg_connection) {
+            // Step 4: Fire the response.
+            Ok(is_deleted) => Ok(Json(SuccessResponse {
+                message: "Successfully deleted the auth_user.".to_string(),
+                data: is_deleted,
+            })),
+            Err(err) => Err(err.into())
+        }
+    }).await
 }
 
 #[api_v2_operation]
 pub async fn remove_all_auth_users_api(
+    principal: Principal,
     pool: web::Data<CoreDBPool>,
 ) -> Result<Json<SuccessResponse<bool>>, Errors> {
-    // Step 1: Get the connection from pool data
-    let pg_connection = pgdata_to_pgconnection(pool);
-
-    // Step 2: Delete all auth_users from the database.
-    match delete_all_auth_users(&pg_connection) {
-        Ok(deleted_auth_users) => {
-            // Step 3: Delete all members users, and teams from the database.
-            if delete_all_members(&pg_connection).is_ok() {
-                // Step 4: Fire the response.
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: Only the global administrators can remove all auth_users.
+        check_permission(&principal, Action::RemoveAll, None, pg_connection)?;
+
+        // Step 3: Delete all auth_users, then all members in one transaction, nothing is deleted when one of them fails.
+        match with_transaction(pg_connection, |connection| {
+            delete_all_auth_users(connection)?;
+            delete_all_members(connection)
+        }) {
+            // Step 4: Fire the response.
+            Ok(_) => Ok(Json(SuccessResponse {
+                message: "Successfully deleted all auth_users.".to_string(),
+                data: true,
+            })),
+            Err(err) => Err(err.into())
+        }
+    }).await
+}
+
+#[api_v2_operation]
+pub async fn find_auth_user_by_id_api(
+    auth_user_id: web::Path<Uuid>,
+    principal: Principal,
+    pool: web::Data<CoreDBPool>,
+) -> Result<Json<SuccessResponse<AuthUser>>, Errors> {
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: The auth_user can find itself, the global administrators can find anyone.
+        if principal.auth_user_id != *auth_user_id {
+            check_permission(&principal, Action::ManageAuthUsers, None, pg_connection)?;
+        }
+
+        // Step 3: Find the auth_user from the database.
+        match find_auth_user_by_id(&auth_user_id, pg_connection) {
+            Ok(found_auth_user) => {
+                // Step 4: Fire the response
                 Ok(Json(SuccessResponse {
-                    message: format!("Successfully deleted all auth_users."),
-                    data: true,
+                    message: "Successfully found the Auth User.".to_string(),
+                    data: found_auth_user,
                 }))
-            } else {
-                // Step 3: In case an error happened while deleting members users, and teams, I will insert the deleted auth users again.
-                match insert_bulk_auth_users(&deleted_auth_users, &pg_connection) {
-                    Ok(_) => Err(Errors::InternalServerError(DBError.into())),
-                    Err(_) => Err(Errors::InternalServerError(DBError.into()))
-                }
             }
+            Err(err) => Err(err.into())
         }
-        Err(_) => Err(Errors::InternalServerError(DBError.into()))
-    }
+    }).await
 }
 
 #[api_v2_operation]
-pub async fn find_auth_user_by_id_api(
+pub async fn hash_plaintext_passwords_api(
+    principal: Principal,
+    pool: web::Data<CoreDBPool>,
+) -> Result<Json<SuccessResponse<usize>>, Errors> {
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: Only the global administrators can migrate the passwords.
+        check_permission(&principal, Action::ManageAuthUsers, None, pg_connection)?;
+
+        // Step 3: Hash the passwords that were stored before hashing was introduced.
+        match hash_plaintext_passwords(pg_connection) {
+            // Step 4: Fire the number of migrated auth_users.
+            Ok(migrated_count) => Ok(Json(SuccessResponse {
+                message: "Successfully hashed the plaintext passwords.".to_string(),
+                data: migrated_count,
+            })),
+            Err(err) => Err(err.into())
+        }
+    }).await
+}
+
+#[api_v2_operation]
+pub async fn login_api(
+    login_dto: ValidatedJson<LoginDTO>,
+    pool: web::Data<CoreDBPool>,
+) -> Result<Json<SuccessResponse<TokenPair>>, Errors> {
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: Verify the credentials and issue the tokens.
+        match login(&login_dto, pg_connection) {
+            // Step 3: Fire the issued tokens.
+            Ok(token_pair) => Ok(Json(SuccessResponse {
+                message: "Successfully logged in.".to_string(),
+                data: token_pair,
+            })),
+            Err(Error::Unauthorized(_)) => Err(Errors::Unauthorized(InvalidCredentials.into())),
+            Err(err) => Err(err.into())
+        }
+    }).await
+}
+
+#[api_v2_operation]
+pub async fn refresh_token_api(
+    refresh_token_dto: Json<RefreshTokenDTO>,
+    pool: web::Data<CoreDBPool>,
+) -> Result<Json<SuccessResponse<TokenPair>>, Errors> {
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: Rotate the refresh token.
+        match refresh_session(&refresh_token_dto.refresh_token, pg_connection) {
+            // Step 3: Fire the new tokens.
+            Ok(token_pair) => Ok(Json(SuccessResponse {
+                message: "Successfully refreshed the tokens.".to_string(),
+                data: token_pair,
+            })),
+            Err(Error::Unauthorized(_)) => Err(Errors::Unauthorized(InvalidToken.into())),
+            Err(err) => Err(err.into())
+        }
+    }).await
+}
+
+#[api_v2_operation]
+pub async fn logout_api(
+    refresh_token_dto: Json<RefreshTokenDTO>,
+    pool: web::Data<CoreDBPool>,
+) -> Result<Json<SuccessResponse<bool>>, Errors> {
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: Revoke the refresh token.
+        match logout(&refresh_token_dto.refresh_token, pg_connection) {
+            // Step 3: Fire the response.
+            Ok(revoked) => Ok(Json(SuccessResponse {
+                message: "Successfully logged out.".to_string(),
+                data: revoked,
+            })),
+            Err(Error::Unauthorized(_)) => Err(Errors::Unauthorized(InvalidToken.into())),
+            Err(err) => Err(err.into())
+        }
+    }).await
+}
+
+#[api_v2_operation]
+pub async fn update_auth_user_api(
     auth_user_id: web::Path<Uuid>,
+    auth_user_patch: ValidatedJson<AuthUserPatch>,
+    principal: Principal,
     pool: web::Data<CoreDBPool>,
 ) -> Result<Json<SuccessResponse<AuthUser>>, Errors> {
-    // Step 1: Get the connection from pool data
-    let pg_connection = pgdata_to_pgconnection(pool);
-
-    // Step 2: Find the auth_user from the database.
-    match find_auth_user_by_id(&auth_user_id, &pg_connection) {
-        Ok(found_auth_user) => {
-            // Step 3: Fire the response
-            Ok(Json(SuccessResponse {
-                message: format!("Successfully found the Auth User."),
-                data: found_auth_user,
-            }))
-        }
-        Err(_) => Err(Errors::NotFound(NotFound.into()))
-    }
+    // Step 1: A PUT replaces the auth_user, so every field is required.
+    auth_user_patch.require_all().map_err(Errors::from)?;
+
+    // Step 2: Save it like a patch.
+    save_auth_user_patch(auth_user_id.into_inner(), auth_user_patch.into_inner(), principal, pool).await
+}
+
+#[api_v2_operation]
+pub async fn patch_auth_user_api(
+    auth_user_id: web::Path<Uuid>,
+    auth_user_patch: ValidatedJson<AuthUserPatch>,
+    principal: Principal,
+    pool: web::Data<CoreDBPool>,
+) -> Result<Json<SuccessResponse<AuthUser>>, Errors> {
+    save_auth_user_patch(auth_user_id.into_inner(), auth_user_patch.into_inner(), principal, pool).await
 }
 
+async fn save_auth_user_patch(
+    auth_user_id: Uuid,
+    auth_user_patch: AuthUserPatch,
+    principal: Principal,
+    pool: web::Data<CoreDBPool>,
+) -> Result<Json<SuccessResponse<AuthUser>>, Errors> {
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: The auth_user can update itself, the global administrators can update anyone,
+        // and only they can grant or revoke the administration.
+        let stored_auth_user = find_auth_user_by_id(&auth_user_id, pg_connection).map_err(Errors::from)?;
+        let changes_admin = matches!(auth_user_patch.is_admin, Some(is_admin) if is_admin != stored_auth_user.is_admin);
+        if principal.auth_user_id != auth_user_id || changes_admin {
+            check_permission(&principal, Action::ManageAuthUsers, None, pg_connection)?;
+        }
+
+        // Step 3: Change the fields of the patch, a new email is also given to the user.
+        match update_auth_user(&auth_user_id, &auth_user_patch, pg_connection) {
+            // Step 4: Fire the updated auth_user.
+            Ok(updated_auth_user) => Ok(Json(SuccessResponse {
+                message: "Successfully updated the Auth User.".to_string(),
+                data: updated_auth_user,
+            })),
+            Err(err) => Err(err.into())
+        }
+    }).await
+}
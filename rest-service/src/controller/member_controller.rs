@@ -1,143 +1,166 @@
 use actix_web::web::Path;
-use paperclip::actix::{
-    api_v2_operation,
-    web::{self, Query},
-};
+use paperclip::actix::{api_v2_operation, web};
 use paperclip::actix::web::Json;
 use uuid::Uuid;
 
-use error::error::Errors;
-use error::error::StateCode::{DBError, NotFound, PaginationError};
-use yugabyte::db_connection::{CoreDBPool, pgdata_to_pgconnection};
-use yugabyte::engine::member::{count_members, delete_all_members, delete_member_by_id, filter_members_by_name, find_member_by_id, get_all_member_names_by_team_id, insert_bulk_members, list_all_members};
+use error::error::{Error, Errors, StateCode, validate_all};
+use yugabyte::auth::policy::{Action, authorize, authorize_view_user, visible_team_ids};
+use yugabyte::auth::principal::Principal;
+use yugabyte::db_connection::{CoreDBPool, with_snapshot, with_transaction};
+use yugabyte::engine::member::{count_members, delete_all_members, delete_member_by_id, filter_members_by_name, find_member_by_id, get_all_member_names_by_team_id, insert_bulk_members, list_all_members, patch_member, restore_member};
 use yugabyte::engine::user::find_user_by_id;
 use yugabyte::model::dto::{MemberEmail, MemberInfo, MemberName, PaginatedResponseDTO, PaginationDTO, SuccessResponse};
-use yugabyte::model::member::{Member, Name, NewMember};
+use yugabyte::model::member::{Member, MemberPatch, Name, NewMember};
+use yugabyte::model::page::PageRequest;
 use yugabyte::util::utils::current_timestamp;
 
+use crate::extractor::{ValidatedJson, ValidatedQuery};
+use crate::controller::check_permission;
+use crate::etag::{ETagged, IfMatch};
+
 #[api_v2_operation]
 pub async fn find_member_email_api(
     user_id: web::Path<Uuid>,
+    principal: Principal,
     pool: web::Data<CoreDBPool>,
 ) -> Result<Json<SuccessResponse<MemberEmail>>, Errors> {
-    // Step 1: Get the connection from pool data
-    let pg_connection = pgdata_to_pgconnection(pool);
-
-    // Step 2: Find the user from the database.
-    match find_user_by_id(&user_id, &pg_connection) {
-        Ok(found_user) => {
-            let member_email = MemberEmail {
-                name: found_user.name,
-                email: found_user.email,
-            };
-            // Step 3: Fire the response
-            Ok(Json(SuccessResponse {
-                message: format!("Successfully find the Member Email."),
-                data: member_email,
-            }))
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: Find the user from the database.
+        match find_user_by_id(&user_id, pg_connection) {
+            Ok(found_user) => {
+                // Step 3: Only the user itself and the members of its teams can see its email.
+                authorize_view_user(&principal, &found_user, pg_connection).map_err(Errors::from)?;
+
+                let member_email = MemberEmail {
+                    name: found_user.name,
+                    email: found_user.email,
+                };
+                // Step 4: Fire the response
+                Ok(Json(SuccessResponse {
+                    message: "Successfully find the Member Email.".to_string(),
+                    data: member_email,
+                }))
+            }
+            Err(err) => Err(err.into())
         }
-        Err(_) => Err(Errors::NotFound(NotFound.into()))
-    }
+    }).await
 }
 
 #[api_v2_operation]
 pub async fn find_member_info_api(
     web::Path((user_id, member_id)): web::Path<(Uuid, Uuid)>,
+    principal: Principal,
     pool: web::Data<CoreDBPool>,
-) -> Result<Json<SuccessResponse<MemberInfo>>, Errors> {
-    // Step 1: Get the connection from pool data
-    let pg_connection = pgdata_to_pgconnection(pool);
-
-    // Step 2: Find the user from the database.
-    match find_user_by_id(&user_id, &pg_connection) {
-        Ok(found_user) => {
-            // Step 3: Find the member from the database.
-            match find_member_by_id(&member_id, &pg_connection) {
-                Ok(found_member) => {
-                    let member_info = MemberInfo {
-                        name: found_user.name,
-                        email: found_user.email,
-                        identity_num: found_member.identity_num,
-                        role: found_member.role,
-                    };
-                    // Step 4: Fire the response
-                    Ok(Json(SuccessResponse {
-                        message: format!("Successfully find the Member Info."),
-                        data: member_info,
-                    }))
-                }
-                Err(_) => Err(Errors::NotFound(NotFound.into()))
-            }
-        }
-        Err(_) => Err(Errors::NotFound(NotFound.into()))
-    }
+) -> Result<ETagged<SuccessResponse<MemberInfo>>, Errors> {
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: Find the user and the member in one REPEATABLE READ transaction, so both are read from the same snapshot.
+        let (found_user, found_member) = with_snapshot::<_, Error, _>(pg_connection, |connection| {
+            let found_user = find_user_by_id(&user_id, connection)?;
+            let found_member = find_member_by_id(&member_id, connection)?;
+            Ok((found_user, found_member))
+        }).map_err(Errors::from)?;
+
+        // Step 3: Only the members of the team can see the member info.
+        check_permission(&principal, Action::ViewTeam, Some(&found_member.team_id), pg_connection)?;
+
+        let member_version = found_member.version;
+        let member_info = MemberInfo {
+            name: found_user.name,
+            email: found_user.email,
+            identity_num: found_member.identity_num,
+            role: found_member.role,
+        };
+        // Step 4: Fire the response with the version of the member.
+        Ok(ETagged::new(member_version, SuccessResponse {
+            message: "Successfully find the Member Info.".to_string(),
+            data: member_info,
+        }))
+    }).await
 }
 
 #[api_v2_operation]
-pub(crate) fn list_members_api(
-    Query(pagination_dto): Query<PaginationDTO>,
+pub(crate) async fn list_members_api(
+    ValidatedQuery(pagination_dto): ValidatedQuery<PaginationDTO>,
+    principal: Principal,
     pool: web::Data<CoreDBPool>,
 ) -> Result<Json<SuccessResponse<PaginatedResponseDTO<Member>>>, Errors> {
-    // Step 1: Get the connection from pool data.
-    let pg_connection = pgdata_to_pgconnection(pool);
-
-    // Step 2: Count all members.
-    match count_members(&pg_connection) {
-        Ok(members_count) => {
-            // Step 3: List all paginated members.
-            match list_all_members(&pagination_dto, &pg_connection) {
-                Ok(paginated_list) => {
-                    let response = PaginatedResponseDTO {
-                        paginated_list,
-                        count: members_count,
-                    };
-
-                    // Step 4: Fire the response.
-                    Ok(Json(SuccessResponse {
-                        message: format!("Successfully retrieved all members."),
-                        data: response,
-                    }))
-                }
-                Err(_) => {
-                    Err(Errors::BadRequest(PaginationError.into()))
+    // The cursors are decoded before touching the database, an invalid one is a bad request.
+    let page_request = PageRequest::from_pagination(&pagination_dto).map_err(Errors::from)?;
+
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: Only the global administrators can list the deleted members.
+        if page_request.include_deleted {
+            check_permission(&principal, Action::ManageDeleted, None, pg_connection)?;
+        }
+
+        // Step 3: The others only list the members of their teams.
+        let visible_team_ids = visible_team_ids(&principal, pg_connection).map_err(Errors::from)?;
+
+        // Step 4: Count all members.
+        match count_members(page_request.include_deleted, visible_team_ids.as_deref(), pg_connection) {
+            Ok(members_count) => {
+                // Step 5: List all paginated members.
+                match list_all_members(&page_request, visible_team_ids.as_deref(), pg_connection) {
+                    Ok(page) => {
+                        let response = page.into_response(members_count);
+
+                        // Step 6: Fire the response.
+                        Ok(Json(SuccessResponse {
+                            message: "Successfully retrieved all members.".to_string(),
+                            data: response,
+                        }))
+                    }
+                    Err(err) => Err(err.into())
                 }
             }
+            Err(err) => Err(err.into())
         }
-        Err(_) => {
-            Err(Errors::InternalServerError(DBError.into()))
-        }
-    }
+    }).await
 }
 
 #[api_v2_operation]
-pub(crate) fn insert_member_api(
-    new_member: Json<NewMember>,
+pub(crate) async fn insert_member_api(
+    new_member: ValidatedJson<NewMember>,
+    principal: Principal,
     pool: web::Data<CoreDBPool>,
 ) -> Result<Json<SuccessResponse<Member>>, Errors> {
-    // Step 1: Get the connection from pool data.
-    let pg_connection = pgdata_to_pgconnection(pool);
-
-    // Step 2: Insert the member into the database
-    match new_member.insert_member(&pg_connection) {
-        // Step 3: Fire the inserted member
-        Ok(inserted_member) => Ok(Json(SuccessResponse {
-            message: format!("Successfully added the new Member."),
-            data: inserted_member,
-        })),
-        Err(_) => Err(Errors::InternalServerError(DBError.into()))
-    }
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: Only the owners and admins of the team can add members to it.
+        check_permission(&principal, Action::ManageMembers, Some(&new_member.team_id), pg_connection)?;
+
+        // Step 3: Insert the member into the database
+        match new_member.insert_member(pg_connection) {
+            // Step 4: Fire the inserted member
+            Ok(inserted_member) => Ok(Json(SuccessResponse {
+                message: "Successfully added the new Member.".to_string(),
+                data: inserted_member,
+            })),
+            Err(err) => Err(err.into())
+        }
+    }).await
 }
 
 #[api_v2_operation]
-pub(crate) fn insert_bulk_members_api(
+pub(crate) async fn insert_bulk_members_api(
     new_members: Json<Vec<NewMember>>,
+    principal: Principal,
     pool: web::Data<CoreDBPool>,
 ) -> Result<Json<SuccessResponse<Vec<Member>>>, Errors> {
-    // Step 1: Get the connection from pool data.
-    let pg_connection = pgdata_to_pgconnection(pool);
     let mut members = Vec::new();
 
-    // Step 2: Iterate over the New Members and create the list of members to be added in a bulk not to load the execution time of the database.
+    // Step 1: Validate every new member, the invalid fields of all of them are reported together.
+    validate_all(&new_members).map_err(Errors::BadReq)?;
+
+    // Step 2: Collect the teams the members are added to.
+    let mut team_ids: Vec<Uuid> = new_members.iter().map(|new_member| new_member.team_id).collect();
+    team_ids.sort();
+    team_ids.dedup();
+
+    // Step 3: Iterate over the New Members and create the list of members to be added in a bulk not to load the execution time of the database.
     for new_member in new_members.0 {
         let member = Member {
             id: Uuid::new_v4(),
@@ -145,10 +168,12 @@ pub(crate) fn insert_bulk_members_api(
             user_id: new_member.user_id,
             name: new_member.name.clone(),
             identity_num: new_member.identity_num.clone(),
-            role: new_member.role.clone(),
+            role: new_member.role,
             assigned_at: current_timestamp(),
             expired_at: new_member.expired_at,
             modification_date: None,
+            version: 1,
+            deleted_at: None,
         };
         members.push(member);
     }
@@ -160,7 +185,7 @@ pub(crate) fn insert_bulk_members_api(
             user_id: new_member.user_id,
             name: new_member.name.clone(),  // I cloned the name only not the whole new_member object because the string is located in the heap memory.
             identity_num: new_member.identity_num.clone(),
-            role: new_member.role.clone(),
+            role: new_member.role,
             assigned_at: current_timestamp(),
             expired_at: new_member.expired_at,
             modification_date: None,
@@ -169,91 +194,187 @@ pub(crate) fn insert_bulk_members_api(
         members.clone()
     });*/
 
-    // Step 4: Insert the bulk of members into the database.
-    match insert_bulk_members(&members, &pg_connection) {
+    // Step 4: Check that the principal manages every team and insert the bulk of members in one transaction,
+    // with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        let inserted_members = with_transaction(pg_connection, |connection| {
+            for team_id in team_ids.iter() {
+                authorize(&principal, Action::ManageMembers, Some(team_id), connection)?;
+            }
+            insert_bulk_members(&members, connection)
+        }).map_err(Errors::from)?;
+
         // Step 5: Fire the inserted members
-        Ok(inserted_members) => Ok(Json(SuccessResponse {
-            message: format!("Successfully added the bulk of Members."),
+        Ok(Json(SuccessResponse {
+            message: "Successfully added the bulk of Members.".to_string(),
             data: inserted_members,
-        })),
-        Err(_) => Err(Errors::InternalServerError(DBError.into()))
-    }
+        }))
+    }).await
+}
+
+#[api_v2_operation]
+pub(crate) async fn find_member_api(
+    member_id: web::Path<Uuid>,
+    principal: Principal,
+    pool: web::Data<CoreDBPool>,
+) -> Result<ETagged<SuccessResponse<Member>>, Errors> {
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: Find the member from the database.
+        let found_member = find_member_by_id(&member_id, pg_connection).map_err(Errors::from)?;
+
+        // Step 3: Only the members of the team can see it.
+        check_permission(&principal, Action::ViewTeam, Some(&found_member.team_id), pg_connection)?;
+
+        // Step 4: Fire the response with the version of the member.
+        Ok(ETagged::new(found_member.version, SuccessResponse {
+            message: "Successfully found the Member.".to_string(),
+            data: found_member,
+        }))
+    }).await
+}
+
+// The body is a JSON merge patch (RFC 7396), sent as `application/merge-patch+json` or `application/json`.
+#[api_v2_operation]
+pub(crate) async fn patch_member_api(
+    member_id: web::Path<Uuid>,
+    member_patch: ValidatedJson<MemberPatch>,
+    if_match: IfMatch,
+    principal: Principal,
+    pool: web::Data<CoreDBPool>,
+) -> Result<ETagged<SuccessResponse<Member>>, Errors> {
+    let member_patch = member_patch.into_inner();
+
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: Only the owners and admins of the team of the stored member can patch it, in the transaction of the update.
+        let patched_member = with_transaction(pg_connection, |connection| {
+            let stored_member = find_member_by_id(&member_id, connection)?;
+            authorize(&principal, Action::ManageMembers, Some(&stored_member.team_id), connection)?;
+            patch_member(&member_id, &member_patch, if_match.0, connection)
+        }).map_err(Errors::from)?;
+
+        // Step 3: Fire the patched member with its new version.
+        Ok(ETagged::new(patched_member.version, SuccessResponse {
+            message: "Successfully patched the Member.".to_string(),
+            data: patched_member,
+        }))
+    }).await
 }
 
 #[api_v2_operation]
 pub async fn remove_member_api(
     member_id: web::Path<Uuid>,
+    if_match: IfMatch,
+    principal: Principal,
     pool: web::Data<CoreDBPool>,
 ) -> Result<Json<SuccessResponse<bool>>, Errors> {
-    // Step 1: Get the connection from pool data
-    let pg_connection = pgdata_to_pgconnection(pool);
-
-    // Step 2: Delete the member from the database.
-    if !delete_member_by_id(&member_id, &pg_connection) {
-        Err(Errors::InternalServerError(DBError.into()))
-    } else {
-        // Step 3: Fire the response.
-        Ok(Json(SuccessResponse {
-            message: format!("Successfully deleted the member."),
-            data: true,
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: Find the member to know which team it belongs to, only the owners and admins of the team can remove its members.
+        let found_member = find_member_by_id(&member_id, pg_connection).map_err(Errors::from)?;
+        check_permission(&principal, Action::ManageMembers, Some(&found_member.team_id), pg_connection)?;
+
+        // Step 3: Delete the member from the database, when it still has the version of If-Match.
+        let is_deleted = delete_member_by_id(&member_id, if_match.0, pg_connection).map_err(Errors::from)?;
+        if !is_deleted {
+            Err(Errors::NotFound(StateCode::NotFound.into()))
+        } else {
+            // Step 4: Fire the response.
+            Ok(Json(SuccessResponse {
+                message: "Successfully deleted the member.".to_string(),
+                data: true,
+            }))
+        }
+    }).await
+}
+
+#[api_v2_operation]
+pub async fn restore_member_api(
+    member_id: web::Path<Uuid>,
+    principal: Principal,
+    pool: web::Data<CoreDBPool>,
+) -> Result<ETagged<SuccessResponse<Member>>, Errors> {
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: Only the global administrators can restore a deleted member.
+        check_permission(&principal, Action::ManageDeleted, None, pg_connection)?;
+
+        // Step 3: Restore the member in the database.
+        let restored_member = restore_member(&member_id, pg_connection).map_err(Errors::from)?;
+
+        // Step 4: Fire the response with the new version of the member.
+        Ok(ETagged::new(restored_member.version, SuccessResponse {
+            message: "Successfully restored the member.".to_string(),
+            data: restored_member,
         }))
-    }
+    }).await
 }
 
 #[api_v2_operation]
 pub async fn remove_all_members_api(
+    principal: Principal,
     pool: web::Data<CoreDBPool>,
 ) -> Result<Json<SuccessResponse<bool>>, Errors> {
-    // Step 1: Get the connection from pool data
-    let pg_connection = pgdata_to_pgconnection(pool);
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: Only the global administrators can remove all members.
+        check_permission(&principal, Action::RemoveAll, None, pg_connection)?;
 
-    // Step 2: Delete all members from the database.
-    if delete_all_members(&pg_connection).is_ok() {
-        // Step 3: Fire the response.
+        // Step 3: Delete all members from the database.
+        delete_all_members(pg_connection).map_err(Errors::from)?;
+
+        // Step 4: Fire the response.
         Ok(Json(SuccessResponse {
-            message: format!("Successfully deleted all members."),
+            message: "Successfully deleted all members.".to_string(),
             data: true,
         }))
-    } else {
-        Err(Errors::InternalServerError(DBError.into()))
-    }
+    }).await
 }
 
 #[api_v2_operation]
 pub async fn filter_members_by_name_api(
-    other_name: Json<MemberName>,
+    other_name: ValidatedJson<MemberName>,
+    principal: Principal,
     pool: web::Data<CoreDBPool>,
 ) -> Result<Json<SuccessResponse<Vec<Member>>>, Errors> {
-    // Step 1: Get the connection from pool data
-    let pg_connection = pgdata_to_pgconnection(pool);
-
-    // Step 2: Filter members by name.
-    match filter_members_by_name(&other_name.name, &pg_connection) {
-        // Step 3: Fire the response.
-        Ok(filtered_members) => Ok(Json(SuccessResponse {
-            message: format!("Successfully retrieved the filtered members."),
-            data: filtered_members,
-        })),
-        Err(_) => Err(Errors::InternalServerError(DBError.into()))
-    }
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: The others only find the members of their teams.
+        let visible_team_ids = visible_team_ids(&principal, pg_connection).map_err(Errors::from)?;
+
+        // Step 3: Filter members by name.
+        match filter_members_by_name(&other_name.name, visible_team_ids.as_deref(), pg_connection) {
+            // Step 4: Fire the response.
+            Ok(filtered_members) => Ok(Json(SuccessResponse {
+                message: "Successfully retrieved the filtered members.".to_string(),
+                data: filtered_members,
+            })),
+            Err(err) => Err(err.into())
+        }
+    }).await
 }
 
 #[api_v2_operation]
 pub async fn get_all_member_names_related_to_team_api(
     team_id: Path<Uuid>,
+    principal: Principal,
     pool: web::Data<CoreDBPool>,
 ) -> Result<Json<SuccessResponse<Vec<Name>>>, Errors> {
-    // Step 1: Get the connection from pool data
-    let pg_connection = pgdata_to_pgconnection(pool);
-
-    // Step 2: Filter member names related to the required team.
-    match get_all_member_names_by_team_id(&team_id, &pg_connection) {
-        // Step 3: Fire the response.
-        Ok(member_names) => Ok(Json(SuccessResponse {
-            message: format!("Successfully retrieved all member names."),
-            data: member_names,
-        })),
-        Err(_) => Err(Errors::InternalServerError(DBError.into()))
-    }
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: Only the members of the team can see its member names.
+        check_permission(&principal, Action::ViewTeam, Some(&team_id), pg_connection)?;
+
+        // Step 3: Filter member names related to the required team.
+        match get_all_member_names_by_team_id(&team_id, pg_connection) {
+            // Step 4: Fire the response.
+            Ok(member_names) => Ok(Json(SuccessResponse {
+                message: "Successfully retrieved all member names.".to_string(),
+                data: member_names,
+            })),
+            Err(err) => Err(err.into())
+        }
+    }).await
 }
 
@@ -1,23 +1,32 @@
 use actix_web::HttpResponse;
-use dotenv::dotenv;
+use diesel::PgConnection;
 use paperclip::actix::web;
 use paperclip::actix::web::ServiceConfig;
 use tracing_subscriber::EnvFilter;
+use uuid::Uuid;
+
+use error::error::Errors;
+use yugabyte::auth::policy::{Action, authorize};
+use yugabyte::auth::principal::Principal;
+use yugabyte::retry::retry_metrics;
 
 use crate::controller::auth_user_controller::{
-    find_auth_user_by_id_api, insert_auth_user_api, list_auth_users_api,
-    remove_all_auth_users_api, remove_auth_user_api,
+    find_auth_user_by_id_api, hash_plaintext_passwords_api, insert_auth_user_api,
+    list_auth_users_api, login_api, logout_api, patch_auth_user_api, refresh_token_api,
+    remove_all_auth_users_api, remove_auth_user_api, update_auth_user_api,
 };
 use crate::controller::member_controller::{
-    filter_members_by_name_api, find_member_email_api, find_member_info_api,
+    filter_members_by_name_api, find_member_api, find_member_email_api, find_member_info_api,
     get_all_member_names_related_to_team_api, insert_bulk_members_api, insert_member_api,
-    list_members_api, remove_all_members_api, remove_member_api,
+    list_members_api, patch_member_api, remove_all_members_api, remove_member_api, restore_member_api,
 };
 use crate::controller::team_controller::{
     find_team_by_id_api, insert_bulk_teams_api, insert_team_api, list_teams_api,
-    remove_all_teams_api, remove_team_api,
+    patch_team_api, remove_all_teams_api, remove_team_api, restore_team_api, update_team_api,
+};
+use crate::controller::user_controller::{
+    find_user_by_id_api, insert_user_api, list_users_api, patch_user_api, restore_user_api, update_user_api,
 };
-use crate::controller::user_controller::{insert_user_api, list_users_api};
 
 pub(crate) mod auth_user_controller;
 pub(crate) mod member_controller;
@@ -27,25 +36,36 @@ pub(crate) mod user_controller;
 pub fn routes(config: &mut ServiceConfig) {
     config
         .route("/health", web::get().to(|| HttpResponse::Ok().json("Hello World!!")))
+        .route("/metrics/db_retries", web::get().to(|| HttpResponse::Ok().json(retry_metrics())))
         .service(
             web::scope("/auth_user")
                 .route("/list_paginated", web::get().to(list_auth_users_api))
                 .route("/insert", web::post().to(insert_auth_user_api))
                 .route("/remove/{auth_user_id}", web::delete().to(remove_auth_user_api))
                 .route("/remove_all", web::delete().to(remove_all_auth_users_api))
-                .route("/find/{auth_user_id}", web::get().to(find_auth_user_by_id_api)),
+                .route("/find/{auth_user_id}", web::get().to(find_auth_user_by_id_api))
+                .route("/update/{auth_user_id}", web::put().to(update_auth_user_api))
+                .route("/update/{auth_user_id}", web::patch().to(patch_auth_user_api))
+                .route("/hash_plaintext_passwords", web::post().to(hash_plaintext_passwords_api))
+                .route("/login", web::post().to(login_api))
+                .route("/refresh", web::post().to(refresh_token_api))
+                .route("/logout", web::post().to(logout_api)),
         )
         .service(
             web::scope("/member")
-                .route("/find_email", web::get().to(find_member_email_api))
-                .route("/find_info", web::get().to(find_member_info_api))
+                .route("/find_email/{user_id}", web::get().to(find_member_email_api))
+                .route("/find_info/{user_id}/{member_id}", web::get().to(find_member_info_api))
                 .route("/list_paginated", web::get().to(list_members_api))
                 .route("/insert", web::post().to(insert_member_api))
                 .route("/insert_bulk", web::post().to(insert_bulk_members_api))
                 .route("/remove/{member_id}", web::delete().to(remove_member_api))
                 .route("/remove_all", web::delete().to(remove_all_members_api))
+                .route("/restore/{member_id}", web::post().to(restore_member_api))
                 .route("/filter_by_name", web::get().to(filter_members_by_name_api))
-                .route("/member_names_by_team_id/{team_id}", web::get().to(get_all_member_names_related_to_team_api)),
+                .route("/member_names_by_team_id/{team_id}", web::get().to(get_all_member_names_related_to_team_api))
+                // Last, so the paths above are not taken for a member id.
+                .route("/{member_id}", web::get().to(find_member_api))
+                .route("/{member_id}", web::patch().to(patch_member_api)),
         )
         .service(
             web::scope("/team")
@@ -54,20 +74,72 @@ pub fn routes(config: &mut ServiceConfig) {
                 .route("/insert_bulk", web::post().to(insert_bulk_teams_api))
                 .route("/remove/{team_id}", web::delete().to(remove_team_api))
                 .route("/remove_all", web::delete().to(remove_all_teams_api))
-                .route("/find/{team_id}", web::get().to(find_team_by_id_api)),
+                .route("/restore/{team_id}", web::post().to(restore_team_api))
+                .route("/find/{team_id}", web::get().to(find_team_by_id_api))
+                .route("/update/{team_id}", web::put().to(update_team_api))
+                .route("/update/{team_id}", web::patch().to(patch_team_api)),
         )
         .service(
             web::scope("user")
                 .route("/list", web::get().to(list_users_api))
                 .route("/insert", web::post().to(insert_user_api))
+                .route("/find/{user_id}", web::get().to(find_user_by_id_api))
+                .route("/update/{user_id}", web::put().to(update_user_api))
+                .route("/update/{user_id}", web::patch().to(patch_user_api))
+                .route("/restore/{user_id}", web::post().to(restore_user_api))
         );
 }
 
 // Initiate the tracing subscriber for RUST_LOG
-pub fn start_tracing() {
-    dotenv().ok();
-
+pub fn start_tracing(filter: &str) {
     tracing_subscriber::fmt()
-        .with_env_filter(EnvFilter::from_default_env())
+        .with_env_filter(EnvFilter::new(filter))
         .init();
-}
\ No newline at end of file
+}
+
+// Ask the policy layer whether the principal can do the action on the team.
+pub(crate) fn check_permission(
+    principal: &Principal,
+    action: Action,
+    team_id: Option<&Uuid>,
+    pg_connection: &PgConnection,
+) -> Result<(), Errors> {
+    authorize(principal, action, team_id, pg_connection).map_err(Errors::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{App, test};
+    use actix_web::http::StatusCode;
+    use actix_web::rt::System;
+    use paperclip::actix::OpenApiExt;
+
+    use super::*;
+
+    // The routes are matched and their path parameters extracted before the principal is checked,
+    // so an anonymous call is refused with 401, and a path that doesn't match any route is a 404.
+    fn status_of(path: &str) -> StatusCode {
+        let path = path.to_string();
+        System::new("routes").block_on(async move {
+            let mut app = test::init_service(App::new().wrap_api().configure(routes).build()).await;
+            let response = test::call_service(&mut app, test::TestRequest::get().uri(&path).to_request()).await;
+            response.status()
+        })
+    }
+
+    #[test]
+    fn the_find_routes_take_their_ids_from_the_path() {
+        let id = Uuid::new_v4();
+        let other_id = Uuid::new_v4();
+        let paths = [
+            format!("/member/find_email/{}", id),
+            format!("/member/find_info/{}/{}", id, other_id),
+            format!("/user/find/{}", id),
+            format!("/team/find/{}", id),
+        ];
+        for path in paths {
+            assert_eq!(status_of(&path), StatusCode::UNAUTHORIZED, "{}", path);
+        }
+        assert_eq!(status_of("/member/find_info/not-an-id/not-an-id"), StatusCode::NOT_FOUND);
+    }
+}
@@ -1,86 +1,100 @@
-use paperclip::actix::{
-    api_v2_operation,
-    web::{self, Query},
-};
+use paperclip::actix::{api_v2_operation, web};
 use paperclip::actix::web::Json;
 use uuid::Uuid;
 
-use error::error::Errors;
-use error::error::StateCode::{DBError, NotFound, PaginationError};
-use yugabyte::db_connection::{CoreDBPool, pgdata_to_pgconnection};
-use yugabyte::engine::team::{count_teams, delete_all_teams, delete_team_by_id, find_team_by_id, insert_bulk_team, list_all_teams};
+use error::error::{Errors, StateCode, validate_all};
+use yugabyte::auth::policy::Action;
+use yugabyte::auth::principal::Principal;
+use yugabyte::db_connection::CoreDBPool;
+use yugabyte::engine::team::{count_teams, delete_all_teams, delete_team_by_id, find_team_by_id, insert_bulk_team, list_all_teams, restore_team, update_team};
 use yugabyte::model::dto::{PaginatedResponseDTO, PaginationDTO, SuccessResponse};
-use yugabyte::model::team::{NewTeam, Team};
+use yugabyte::model::page::PageRequest;
+use yugabyte::model::patch::Patch;
+use yugabyte::model::team::{NewTeam, Team, TeamPatch};
+
+use crate::extractor::{ValidatedJson, ValidatedQuery};
+use crate::controller::check_permission;
+use crate::etag::{ETagged, IfMatch};
 
 #[api_v2_operation]
-pub(crate) fn list_teams_api(
-    Query(pagination_dto): Query<PaginationDTO>,
+pub(crate) async fn list_teams_api(
+    ValidatedQuery(pagination_dto): ValidatedQuery<PaginationDTO>,
+    principal: Principal,
     pool: web::Data<CoreDBPool>,
 ) -> Result<Json<SuccessResponse<PaginatedResponseDTO<Team>>>, Errors> {
-    // Step 1: Get the connection from pool data.
-    let pg_connection = pgdata_to_pgconnection(pool);
-
-    // Step 2: Count all teams.
-    match count_teams(&pg_connection) {
-        Ok(teams_count) => {
-            // Step 3: List all paginated teams.
-            match list_all_teams(&pagination_dto, &pg_connection) {
-                Ok(paginated_list) => {
-                    let response = PaginatedResponseDTO {
-                        paginated_list,
-                        count: teams_count,
-                    };
-                    // Step 4: Fire the response.
-                    Ok(Json(SuccessResponse {
-                        message: format!("Successfully retrieved all teams."),
-                        data: response,
-                    }))
-                }
-                Err(_) => {
-                    Err(Errors::BadRequest(PaginationError.into()))
+    // The cursors are decoded before touching the database, an invalid one is a bad request.
+    let page_request = PageRequest::from_pagination(&pagination_dto).map_err(Errors::from)?;
+
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: Only the global administrators can list the deleted teams.
+        if page_request.include_deleted {
+            check_permission(&principal, Action::ManageDeleted, None, pg_connection)?;
+        }
+
+        // Step 3: Count all teams.
+        match count_teams(page_request.include_deleted, pg_connection) {
+            Ok(teams_count) => {
+                // Step 4: List all paginated teams.
+                match list_all_teams(&page_request, pg_connection) {
+                    Ok(page) => {
+                        let response = page.into_response(teams_count);
+                        // Step 5: Fire the response.
+                        Ok(Json(SuccessResponse {
+                            message: "Successfully retrieved all teams.".to_string(),
+                            data: response,
+                        }))
+                    }
+                    Err(err) => Err(err.into())
                 }
             }
+            Err(err) => Err(err.into())
         }
-        Err(_) => {
-            Err(Errors::InternalServerError(DBError.into()))
-        }
-    }
+    }).await
 }
 
 #[api_v2_operation]
-pub(crate) fn insert_team_api(
-    new_team: Json<NewTeam>,
+pub(crate) async fn insert_team_api(
+    new_team: ValidatedJson<NewTeam>,
+    principal: Principal,
     pool: web::Data<CoreDBPool>,
 ) -> Result<Json<SuccessResponse<Team>>, Errors> {
-    // Step 1: Get the connection from pool data.
-    let pg_connection = pgdata_to_pgconnection(pool);
-
-    // Step 2: Insert the team into the database
-    match new_team.insert_team(&pg_connection) {
-        // Step 3: Fire the inserted team
-        Ok(inserted_team) => Ok(Json(SuccessResponse {
-            message: format!("Successfully added the new Team."),
-            data: inserted_team,
-        })),
-        Err(_) => Err(Errors::InternalServerError(DBError.into()))
-    }
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: Only the global administrators can create teams.
+        check_permission(&principal, Action::CreateTeam, None, pg_connection)?;
+
+        // Step 3: Insert the team into the database
+        match new_team.insert_team(pg_connection) {
+            // Step 4: Fire the inserted team
+            Ok(inserted_team) => Ok(Json(SuccessResponse {
+                message: "Successfully added the new Team.".to_string(),
+                data: inserted_team,
+            })),
+            Err(err) => Err(err.into())
+        }
+    }).await
 }
 
 #[api_v2_operation]
-pub(crate) fn insert_bulk_teams_api(
+pub(crate) async fn insert_bulk_teams_api(
     new_teams: Json<Vec<NewTeam>>,
+    principal: Principal,
     pool: web::Data<CoreDBPool>,
 ) -> Result<Json<SuccessResponse<Vec<Team>>>, Errors> {
-    // Step 1: Get the connection from pool data.
-    let pg_connection = pgdata_to_pgconnection(pool);
     let mut teams = Vec::new();
 
+    // Step 1: Validate every new team, the invalid fields of all of them are reported together.
+    validate_all(&new_teams).map_err(Errors::BadReq)?;
+
     // Step 2: Iterate over the New Teams and create the list of teams to be added in a bulk not to load the execution time of the database.
     for new_team in new_teams.0 {
         let team = Team {
             id: Uuid::new_v4(),
             name: new_team.name,
             description: new_team.description,
+            version: 1,
+            deleted_at: None,
         };
         teams.push(team);
     }
@@ -96,73 +110,162 @@ pub(crate) fn insert_bulk_teams_api(
         teams.clone()
     });*/
 
-    // Step 4: Insert the bulk of teams into the database.
-    match insert_bulk_team(&teams, &pg_connection) {
-        // Step 5: Fire the inserted teams.
-        Ok(inserted_teams) => Ok(Json(SuccessResponse {
-            message: format!("Successfully added the bulk of Teams."),
-            data: inserted_teams,
-        })),
-        Err(_) => Err(Errors::InternalServerError(DBError.into()))
-    }
+    // Step 3: Insert the bulk of teams with a connection of the pool, on the blocking thread pool,
+    // only the global administrators can create teams.
+    pool.run(move |pg_connection| {
+        check_permission(&principal, Action::CreateTeam, None, pg_connection)?;
+
+        match insert_bulk_team(&teams, pg_connection) {
+            // Step 4: Fire the inserted teams.
+            Ok(inserted_teams) => Ok(Json(SuccessResponse {
+                message: "Successfully added the bulk of Teams.".to_string(),
+                data: inserted_teams,
+            })),
+            Err(err) => Err(err.into())
+        }
+    }).await
 }
 
 #[api_v2_operation]
 pub async fn remove_team_api(
     team_id: web::Path<Uuid>,
+    if_match: IfMatch,
+    principal: Principal,
     pool: web::Data<CoreDBPool>,
 ) -> Result<Json<SuccessResponse<bool>>, Errors> {
-    // Step 1: Get the connection from pool data
-    let pg_connection = pgdata_to_pgconnection(pool);
-
-    // Step 2: Delete the team from the database.
-    if !delete_team_by_id(&team_id, &pg_connection) {
-        Err(Errors::InternalServerError(DBError.into()))
-    } else {
-        // Step 3: Fire the response.
-        Ok(Json(SuccessResponse {
-            message: format!("Successfully deleted the team."),
-            data: true,
-        }))
-    }
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: Only the owners of the team can delete it.
+        check_permission(&principal, Action::DeleteTeam, Some(&team_id), pg_connection)?;
+
+        // Step 3: Delete the team from the database, when it still has the version of If-Match.
+        let is_deleted = delete_team_by_id(&team_id, if_match.0, pg_connection).map_err(Errors::from)?;
+        if !is_deleted {
+            Err(Errors::NotFound(StateCode::NotFound.into()))
+        } else {
+            // Step 4: Fire the response.
+            Ok(Json(SuccessResponse {
+                message: "Successfully deleted the team.".to_string(),
+                data: true,
+            }))
+        }
+    }).await
 }
 
 #[api_v2_operation]
 pub async fn remove_all_teams_api(
+    principal: Principal,
     pool: web::Data<CoreDBPool>,
 ) -> Result<Json<SuccessResponse<bool>>, Errors> {
-    // Step 1: Get the connection from pool data
-    let pg_connection = pgdata_to_pgconnection(pool);
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: Only the global administrators can remove all teams.
+        check_permission(&principal, Action::RemoveAll, None, pg_connection)?;
+
+        // Step 3: Delete all teams from the database.
+        delete_all_teams(pg_connection).map_err(Errors::from)?;
 
-    // Step 2: Delete all teams from the database.
-    if delete_all_teams(&pg_connection).is_ok() {
-        // Step 3: Fire the response.
+        // Step 4: Fire the response.
         Ok(Json(SuccessResponse {
-            message: format!("Successfully deleted all teams."),
+            message: "Successfully deleted all teams.".to_string(),
             data: true,
         }))
-    } else {
-        Err(Errors::InternalServerError(DBError.into()))
-    }
+    }).await
+}
+
+#[api_v2_operation]
+pub async fn restore_team_api(
+    team_id: web::Path<Uuid>,
+    principal: Principal,
+    pool: web::Data<CoreDBPool>,
+) -> Result<ETagged<SuccessResponse<Team>>, Errors> {
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: Only the global administrators can restore a deleted team.
+        check_permission(&principal, Action::ManageDeleted, None, pg_connection)?;
+
+        // Step 3: Restore the team in the database.
+        let restored_team = restore_team(&team_id, pg_connection).map_err(Errors::from)?;
+
+        // Step 4: Fire the response with the new version of the team.
+        Ok(ETagged::new(restored_team.version, SuccessResponse {
+            message: "Successfully restored the team.".to_string(),
+            data: restored_team,
+        }))
+    }).await
 }
 
 #[api_v2_operation]
 pub async fn find_team_by_id_api(
     team_id: web::Path<Uuid>,
+    principal: Principal,
     pool: web::Data<CoreDBPool>,
-) -> Result<Json<SuccessResponse<Team>>, Errors> {
-    // Step 1: Get the connection from pool data
-    let pg_connection = pgdata_to_pgconnection(pool);
+) -> Result<ETagged<SuccessResponse<Team>>, Errors> {
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: Only the members of the team can see it.
+        check_permission(&principal, Action::ViewTeam, Some(&team_id), pg_connection)?;
 
-    // Step 2: Find the team from the database.
-    match find_team_by_id(&team_id, &pg_connection) {
-        Ok(found_team) => {
-            // Step 3: Fire the response
-            Ok(Json(SuccessResponse {
-                message: format!("Successfully found the Team."),
-                data: found_team,
-            }))
+        // Step 3: Find the team from the database.
+        match find_team_by_id(&team_id, pg_connection) {
+            Ok(found_team) => {
+                // Step 4: Fire the response with the version of the team.
+                Ok(ETagged::new(found_team.version, SuccessResponse {
+                    message: "Successfully found the Team.".to_string(),
+                    data: found_team,
+                }))
+            }
+            Err(err) => Err(err.into())
         }
-        Err(_) => Err(Errors::NotFound(NotFound.into()))
-    }
+    }).await
+}
+
+#[api_v2_operation]
+pub async fn update_team_api(
+    team_id: web::Path<Uuid>,
+    team_patch: ValidatedJson<TeamPatch>,
+    if_match: IfMatch,
+    principal: Principal,
+    pool: web::Data<CoreDBPool>,
+) -> Result<ETagged<SuccessResponse<Team>>, Errors> {
+    // Step 1: A PUT replaces the team, so every field is required.
+    team_patch.require_all().map_err(Errors::from)?;
+
+    // Step 2: Save it like a patch.
+    save_team_patch(team_id.into_inner(), team_patch.into_inner(), if_match, principal, pool).await
+}
+
+#[api_v2_operation]
+pub async fn patch_team_api(
+    team_id: web::Path<Uuid>,
+    team_patch: ValidatedJson<TeamPatch>,
+    if_match: IfMatch,
+    principal: Principal,
+    pool: web::Data<CoreDBPool>,
+) -> Result<ETagged<SuccessResponse<Team>>, Errors> {
+    save_team_patch(team_id.into_inner(), team_patch.into_inner(), if_match, principal, pool).await
+}
+
+async fn save_team_patch(
+    team_id: Uuid,
+    team_patch: TeamPatch,
+    if_match: IfMatch,
+    principal: Principal,
+    pool: web::Data<CoreDBPool>,
+) -> Result<ETagged<SuccessResponse<Team>>, Errors> {
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: Only the owners and the admins of the team can update it.
+        check_permission(&principal, Action::UpdateTeam, Some(&team_id), pg_connection)?;
+
+        // Step 3: Change the fields of the patch when the team still has the version of If-Match.
+        match update_team(&team_id, &team_patch, if_match.0, pg_connection) {
+            // Step 4: Fire the updated team with its new version.
+            Ok(updated_team) => Ok(ETagged::new(updated_team.version, SuccessResponse {
+                message: "Successfully updated the Team.".to_string(),
+                data: updated_team,
+            })),
+            Err(err) => Err(err.into())
+        }
+    }).await
 }
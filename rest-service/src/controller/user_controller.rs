@@ -1,67 +1,173 @@
-use paperclip::actix::{
-    api_v2_operation,
-    web::{self, Query},
-};
+use paperclip::actix::{api_v2_operation, web};
 use paperclip::actix::web::Json;
+use uuid::Uuid;
 
 use error::error::Errors;
-use error::error::StateCode::{DBError, PaginationError};
-use yugabyte::db_connection::{CoreDBPool, pgdata_to_pgconnection};
-use yugabyte::engine::user::{count_users, list_all_users};
+use yugabyte::auth::policy::{Action, authorize_view_user};
+use yugabyte::auth::principal::Principal;
+use yugabyte::db_connection::CoreDBPool;
+use yugabyte::engine::user::{count_users, find_user_by_id, list_all_users, restore_user, update_user};
 use yugabyte::model::dto::{PaginatedResponseDTO, PaginationDTO, SuccessResponse};
-use yugabyte::model::user::{NewUser, User};
+use yugabyte::model::page::PageRequest;
+use yugabyte::model::patch::Patch;
+use yugabyte::model::user::{NewUser, User, UserPatch};
+
+use crate::controller::check_permission;
+use crate::etag::{ETagged, IfMatch};
+use crate::extractor::{ValidatedJson, ValidatedQuery};
 
 #[api_v2_operation]
-pub(crate) fn list_users_api(
-    Query(pagination_dto): Query<PaginationDTO>,
+pub(crate) async fn list_users_api(
+    ValidatedQuery(pagination_dto): ValidatedQuery<PaginationDTO>,
+    principal: Principal,
     pool: web::Data<CoreDBPool>,
 ) -> Result<Json<SuccessResponse<PaginatedResponseDTO<User>>>, Errors> {
-    // Step 1: Get the connection from pool data.
-    let pg_connection = pgdata_to_pgconnection(pool);
-
-    // Step 2: Count all users.
-    match count_users(&pg_connection) {
-        Ok(users_count) => {
-            // Step 3: List all paginated users.
-            match list_all_users(&pagination_dto, &pg_connection) {
-                Ok(paginated_list) => {
-                    let response = PaginatedResponseDTO {
-                        paginated_list,
-                        count: users_count,
-                    };
-
-                    // Step 4: Fire the response.
-                    Ok(Json(SuccessResponse {
-                        message: format!("Successfully retrieved all users."),
-                        data: response,
-                    }))
-                }
-                Err(_) => {
-                    Err(Errors::BadRequest(PaginationError.into()))
+    // The cursors are decoded before touching the database, an invalid one is a bad request.
+    let page_request = PageRequest::from_pagination(&pagination_dto).map_err(Errors::from)?;
+
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: Only the global administrators can list the deleted users.
+        if page_request.include_deleted {
+            check_permission(&principal, Action::ManageDeleted, None, pg_connection)?;
+        }
+
+        // Step 3: Count all users.
+        match count_users(page_request.include_deleted, pg_connection) {
+            Ok(users_count) => {
+                // Step 4: List all paginated users.
+                match list_all_users(&page_request, pg_connection) {
+                    Ok(page) => {
+                        let response = page.into_response(users_count);
+
+                        // Step 5: Fire the response.
+                        Ok(Json(SuccessResponse {
+                            message: "Successfully retrieved all users.".to_string(),
+                            data: response,
+                        }))
+                    }
+                    Err(err) => Err(err.into())
                 }
             }
+            Err(err) => Err(err.into())
         }
-        Err(_) => {
-            Err(Errors::InternalServerError(DBError.into()))
-        }
-    }
+    }).await
 }
 
 #[api_v2_operation]
-pub(crate) fn insert_user_api(
-    new_user: Json<NewUser>,
+pub(crate) async fn insert_user_api(
+    new_user: ValidatedJson<NewUser>,
+    principal: Principal,
     pool: web::Data<CoreDBPool>,
 ) -> Result<Json<SuccessResponse<User>>, Errors> {
-    // Step 1: Get the connection from pool data.
-    let pg_connection = pgdata_to_pgconnection(pool);
-
-    // Step 2: Insert the User into the database
-    match new_user.add_user(&pg_connection) {
-        // Step 3: Fire the inserted user
-        Ok(inserted_user) => Ok(Json(SuccessResponse {
-            message: format!("Successfully added the new User."),
-            data: inserted_user,
-        })),
-        Err(_) => Err(Errors::InternalServerError(DBError.into()))
-    }
-}
\ No newline at end of file
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: Insert the User into the database, linked to the auth_user of the principal when it has its email.
+        match new_user.add_user(&principal, pg_connection) {
+            // Step 3: Fire the inserted user
+            Ok(inserted_user) => Ok(Json(SuccessResponse {
+                message: "Successfully added the new User.".to_string(),
+                data: inserted_user,
+            })),
+            Err(err) => Err(err.into())
+        }
+    }).await
+}
+
+#[api_v2_operation]
+pub(crate) async fn find_user_by_id_api(
+    user_id: web::Path<Uuid>,
+    principal: Principal,
+    pool: web::Data<CoreDBPool>,
+) -> Result<ETagged<SuccessResponse<User>>, Errors> {
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: Find the user from the database.
+        let found_user = find_user_by_id(&user_id, pg_connection).map_err(Errors::from)?;
+
+        // Step 3: Only the user itself and the members of its teams can see it.
+        authorize_view_user(&principal, &found_user, pg_connection).map_err(Errors::from)?;
+
+        // Step 4: Fire the response with the version of the user.
+        Ok(ETagged::new(found_user.version, SuccessResponse {
+            message: "Successfully found the User.".to_string(),
+            data: found_user,
+        }))
+    }).await
+}
+
+#[api_v2_operation]
+pub(crate) async fn update_user_api(
+    user_id: web::Path<Uuid>,
+    user_patch: ValidatedJson<UserPatch>,
+    if_match: IfMatch,
+    principal: Principal,
+    pool: web::Data<CoreDBPool>,
+) -> Result<ETagged<SuccessResponse<User>>, Errors> {
+    // Step 1: A PUT replaces the user, so every field is required.
+    user_patch.require_all().map_err(Errors::from)?;
+
+    // Step 2: Save it like a patch.
+    save_user_patch(user_id.into_inner(), user_patch.into_inner(), if_match, principal, pool).await
+}
+
+#[api_v2_operation]
+pub(crate) async fn patch_user_api(
+    user_id: web::Path<Uuid>,
+    user_patch: ValidatedJson<UserPatch>,
+    if_match: IfMatch,
+    principal: Principal,
+    pool: web::Data<CoreDBPool>,
+) -> Result<ETagged<SuccessResponse<User>>, Errors> {
+    save_user_patch(user_id.into_inner(), user_patch.into_inner(), if_match, principal, pool).await
+}
+
+async fn save_user_patch(
+    user_id: Uuid,
+    user_patch: UserPatch,
+    if_match: IfMatch,
+    principal: Principal,
+    pool: web::Data<CoreDBPool>,
+) -> Result<ETagged<SuccessResponse<User>>, Errors> {
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: The user linked to the principal can update itself, the global administrators can update anyone.
+        let stored_user = find_user_by_id(&user_id, pg_connection).map_err(Errors::from)?;
+        if stored_user.auth_user_id != Some(principal.auth_user_id) {
+            check_permission(&principal, Action::ManageAuthUsers, None, pg_connection)?;
+        }
+
+        // Step 3: Change the fields of the patch when the user still has the version of If-Match,
+        // a new email is also given to the auth_user.
+        match update_user(&user_id, &user_patch, if_match.0, pg_connection) {
+            // Step 4: Fire the updated user with its new version.
+            Ok(updated_user) => Ok(ETagged::new(updated_user.version, SuccessResponse {
+                message: "Successfully updated the User.".to_string(),
+                data: updated_user,
+            })),
+            Err(err) => Err(err.into())
+        }
+    }).await
+}
+
+#[api_v2_operation]
+pub async fn restore_user_api(
+    user_id: web::Path<Uuid>,
+    principal: Principal,
+    pool: web::Data<CoreDBPool>,
+) -> Result<ETagged<SuccessResponse<User>>, Errors> {
+    // Step 1: Run the next steps with a connection of the pool, on the blocking thread pool.
+    pool.run(move |pg_connection| {
+        // Step 2: Only the global administrators can restore a deleted user.
+        check_permission(&principal, Action::ManageDeleted, None, pg_connection)?;
+
+        // Step 3: Restore the user in the database.
+        let restored_user = restore_user(&user_id, pg_connection).map_err(Errors::from)?;
+
+        // Step 4: Fire the response with the new version of the user.
+        Ok(ETagged::new(restored_user.version, SuccessResponse {
+            message: "Successfully restored the user.".to_string(),
+            data: restored_user,
+        }))
+    }).await
+}
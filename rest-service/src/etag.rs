@@ -0,0 +1,114 @@
+use std::collections::BTreeMap;
+
+use actix_web::{dev::Payload, Error, FromRequest, HttpRequest, HttpResponse, Responder};
+use actix_web::http::header;
+use futures_util::future::{ready, Ready};
+use paperclip::actix::OperationModifier;
+use paperclip::actix::web::Json;
+use paperclip::v2::models::{DefaultOperationRaw, DefaultSchemaRaw};
+use paperclip::v2::schema::Apiv2Schema;
+use serde::Serialize;
+
+use error::error::{ErrorCode, Errors, StateCode};
+
+/// The version of the `If-Match` header of an update or a delete. It is None when the header is absent or `*`,
+/// then the stored version is not checked.
+#[derive(Debug, Clone, Copy)]
+pub struct IfMatch(pub Option<i32>);
+
+/// A JSON response with the version of its object in the `ETag` header, like `ETag: "3"`.
+pub struct ETagged<T> {
+    version: i32,
+    body: T,
+}
+
+impl<T> ETagged<T> {
+    pub fn new(version: i32, body: T) -> Self {
+        ETagged { version, body }
+    }
+}
+
+pub fn etag(version: i32) -> String {
+    format!("\"{}\"", version)
+}
+
+// Only one strong ETag is accepted, a list or a weak ETag is a bad request.
+fn parse_if_match(value: &str) -> Result<Option<i32>, Errors> {
+    let value = value.trim();
+    if value == "*" {
+        return Ok(None);
+    }
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .and_then(|version| version.parse::<i32>().ok())
+        .map(Some)
+        .ok_or_else(|| Errors::BadRequest(ErrorCode::with_detail(StateCode::BadRequest, "If-Match")))
+}
+
+impl FromRequest for IfMatch {
+    type Error = Error;
+    type Future = Ready<Result<Self, Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let if_match = match req.headers().get(header::IF_MATCH) {
+            None => Ok(IfMatch(None)),
+            Some(value) => value
+                .to_str()
+                .map_err(|_| Errors::BadRequest(ErrorCode::with_detail(StateCode::BadRequest, "If-Match")))
+                .and_then(parse_if_match)
+                .map(IfMatch),
+        };
+        ready(if_match.map_err(Error::from))
+    }
+}
+
+impl<T: Serialize> Responder for ETagged<T> {
+    type Error = Error;
+    type Future = Ready<Result<HttpResponse, Error>>;
+
+    fn respond_to(self, _req: &HttpRequest) -> Self::Future {
+        ready(Ok(HttpResponse::Ok()
+            .set_header(header::ETAG, etag(self.version))
+            .json(self.body)))
+    }
+}
+
+// The OpenAPI spec documents the response like the plain Json one.
+impl Apiv2Schema for IfMatch {}
+
+impl OperationModifier for IfMatch {}
+
+impl<T: Apiv2Schema> Apiv2Schema for ETagged<T> {}
+
+impl<T: Apiv2Schema> OperationModifier for ETagged<T> {
+    fn update_response(op: &mut DefaultOperationRaw) {
+        Json::<T>::update_response(op)
+    }
+
+    fn update_definitions(map: &mut BTreeMap<String, DefaultSchemaRaw>) {
+        Json::<T>::update_definitions(map)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_if_match_takes_one_strong_etag_or_a_star() {
+        assert_eq!(parse_if_match("\"3\"").ok(), Some(Some(3)));
+        assert_eq!(parse_if_match(" \"3\" ").ok(), Some(Some(3)));
+        assert_eq!(parse_if_match("*").ok(), Some(None));
+        assert_eq!(parse_if_match(&etag(7)).ok(), Some(Some(7)));
+    }
+
+    #[test]
+    fn parse_if_match_refuses_the_weak_and_listed_etags() {
+        for value in ["W/\"3\"", "\"3\", \"4\"", "3", "\"three\"", "\"\"", ""] {
+            assert!(matches!(parse_if_match(value), Err(Errors::BadRequest(_))), "{}", value);
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::BTreeMap;
+use std::ops::Deref;
+
+use actix_web::{dev::Payload, Error, FromRequest, HttpRequest};
+use futures_util::future::LocalBoxFuture;
+use paperclip::actix::OperationModifier;
+use paperclip::actix::web::{Json, Query};
+use paperclip::v2::models::{DefaultOperationRaw, DefaultSchemaRaw};
+use paperclip::v2::schema::Apiv2Schema;
+use serde::de::DeserializeOwned;
+use validator::Validate;
+
+use error::error::Errors;
+
+/// A JSON body that has passed its `#[validate]` rules, otherwise the request is rejected
+/// with `Errors::BadReq` listing every invalid field.
+#[derive(Debug)]
+pub struct ValidatedJson<T>(pub T);
+
+/// The query string counterpart of [`ValidatedJson`].
+#[derive(Debug)]
+pub struct ValidatedQuery<T>(pub T);
+
+impl<T> ValidatedJson<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for ValidatedJson<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> Deref for ValidatedQuery<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> FromRequest for ValidatedJson<T>
+    where
+        T: DeserializeOwned + Validate + 'static,
+{
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Error>>;
+    type Config = <Json<T> as FromRequest>::Config;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let json = Json::<T>::from_request(req, payload);
+        Box::pin(async move {
+            let value = json.await?.into_inner();
+            value.validate().map_err(Errors::from)?;
+            Ok(ValidatedJson(value))
+        })
+    }
+}
+
+impl<T> FromRequest for ValidatedQuery<T>
+    where
+        T: DeserializeOwned + Validate + 'static,
+{
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Error>>;
+    type Config = <Query<T> as FromRequest>::Config;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let query = Query::<T>::from_request(req, payload);
+        Box::pin(async move {
+            let value = query.await?.into_inner();
+            value.validate().map_err(Errors::from)?;
+            Ok(ValidatedQuery(value))
+        })
+    }
+}
+
+// The OpenAPI spec documents them like the plain Json and Query extractors.
+impl<T: Apiv2Schema> Apiv2Schema for ValidatedJson<T> {}
+
+impl<T: Apiv2Schema> OperationModifier for ValidatedJson<T> {
+    fn update_parameter(op: &mut DefaultOperationRaw) {
+        Json::<T>::update_parameter(op)
+    }
+
+    fn update_definitions(map: &mut BTreeMap<String, DefaultSchemaRaw>) {
+        Json::<T>::update_definitions(map)
+    }
+}
+
+impl<T: Apiv2Schema> Apiv2Schema for ValidatedQuery<T> {}
+
+impl<T: Apiv2Schema> OperationModifier for ValidatedQuery<T> {
+    fn update_parameter(op: &mut DefaultOperationRaw) {
+        Query::<T>::update_parameter(op)
+    }
+
+    fn update_definitions(map: &mut BTreeMap<String, DefaultSchemaRaw>) {
+        Query::<T>::update_definitions(map)
+    }
+}
@@ -1,34 +1,62 @@
-use std::env;
+// paperclip's nightly mode gives the OpenAPI traits default impls, the custom extractors specialize them.
+#![allow(incomplete_features)]
+#![feature(specialization)]
 
 use actix_web::{App, HttpServer};
 use actix_web::middleware::Logger;
 use paperclip::actix::OpenApiExt;
 use paperclip::actix::web::{Data, JsonConfig};
 
-use yugabyte::db_connection::CoreDBPool;
+use yugabyte::config::{check_config, CliArgs, Config, startup_error};
+use yugabyte::db_connection::{CoreDBPool, DBPoolConvertable};
+use yugabyte::middleware::authentication::Authentication;
+use yugabyte::middleware::request_id::RequestIdentifier;
+use yugabyte::migration::{migrate_on_startup, run_command};
 
 use crate::controller::{routes, start_tracing};
 
 mod controller;
+mod etag;
+mod extractor;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    start_tracing();
-    let core_db_pool_data = Data::new(CoreDBPool::default());
-    dotenv::dotenv().expect("Failed to read .env file");
+    let cli_args = CliArgs::from_env().map_err(startup_error)?;
+    if cli_args.check_config {
+        let config = Config::from_sources(cli_args.config_path.as_deref()).map_err(startup_error)?;
+        return check_config(&config).map_err(startup_error);
+    }
+
+    // Every invalid setting is reported at once.
+    let config = Config::load(cli_args.config_path.as_deref()).map_err(startup_error)?.install();
+    start_tracing(&config.log.filter);
+    let core_db_pool = CoreDBPool::new(&config.database).map_err(startup_error)?;
+    // The admin commands work on the database and exit, without starting the server.
+    if let Some(command) = cli_args.migrate {
+        return run_command(command, core_db_pool.to_pgpool()).map_err(startup_error);
+    }
+    migrate_on_startup(config.database.migrations, core_db_pool.to_pgpool()).map_err(startup_error)?;
+    let core_db_pool_data = Data::new(core_db_pool);
+    let open_api_path = config.server.open_api_path.clone();
+    let json_limit = config.server.json_limit;
 
     HttpServer::new(move || {
         App::new()
+            .wrap(
+                Authentication::from_config(&config.auth, &["/health", "/auth_user/insert", "/auth_user/login", "/auth_user/refresh", "/auth_user/logout"])
+                    .allow(&open_api_path)
+            )
+            // It renders the errors of the handlers and of the authentication as problem+json.
+            .wrap(RequestIdentifier)
             .wrap(Logger::default())
-            .data(JsonConfig::default().limit(4096))
+            .data(JsonConfig::default().limit(json_limit))
             .app_data(core_db_pool_data.clone())
             .wrap_api()
             .configure(routes)
-            .with_json_spec_at(env::var("REST_OPEN_API").unwrap().as_str())
+            .with_json_spec_at(open_api_path.as_str())
             .build()
     })
-        .bind(format!("{}:{}", env::var("HOST").unwrap(), env::var("REST_PORT").unwrap()))
-        .expect("Server binding exception")
+        .bind((config.server.host.as_str(), config.server.rest_port))?
         .run()
         .await
 }
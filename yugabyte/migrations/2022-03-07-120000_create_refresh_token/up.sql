@@ -0,0 +1,17 @@
+-- Your SQL goes here
+-- Every issued refresh token has a row here, so it can be rotated and revoked from the server side.
+CREATE TABLE refresh_token
+(
+    id           UUID PRIMARY KEY,
+    auth_user_id UUID      NOT NULL,
+    created_at   TIMESTAMP NOT NULL,
+    expires_at   TIMESTAMP NOT NULL,
+    revoked_at   TIMESTAMP,
+
+    CONSTRAINT fk_auth_user
+        FOREIGN KEY (auth_user_id)
+            REFERENCES auth_user (id)
+            ON DELETE CASCADE
+);
+
+CREATE INDEX refresh_token_auth_user_id_idx ON refresh_token (auth_user_id);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE auth_user
+    DROP COLUMN is_admin;
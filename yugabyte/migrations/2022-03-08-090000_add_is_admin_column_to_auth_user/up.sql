@@ -0,0 +1,4 @@
+-- Your SQL goes here
+-- Global administrators are promoted manually: UPDATE auth_user SET is_admin = TRUE WHERE email = '...';
+ALTER TABLE auth_user
+ADD COLUMN is_admin BOOLEAN NOT NULL DEFAULT FALSE;
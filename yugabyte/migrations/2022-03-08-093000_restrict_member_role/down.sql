@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE member
+    DROP CONSTRAINT member_role_check;
@@ -0,0 +1,11 @@
+-- Your SQL goes here
+-- The role was a free-form text, the unknown values get the least privileged role.
+UPDATE member
+SET role = lower(trim(role));
+
+UPDATE member
+SET role = 'viewer'
+WHERE role NOT IN ('owner', 'admin', 'viewer');
+
+ALTER TABLE member
+ADD CONSTRAINT member_role_check CHECK (role IN ('owner', 'admin', 'viewer'));
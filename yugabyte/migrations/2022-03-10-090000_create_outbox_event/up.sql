@@ -0,0 +1,13 @@
+-- Your SQL goes here
+-- The member and team changes are written here in the transaction of the change, and relayed to the GraphQL
+-- subscriptions once committed.
+CREATE TABLE outbox_event
+(
+    id         UUID PRIMARY KEY,
+    created_at TIMESTAMP NOT NULL DEFAULT timezone('utc', now()),
+    team_id    UUID      NOT NULL,
+    payload    TEXT      NOT NULL
+);
+
+-- The relay reads the events in ranges of created_at.
+CREATE INDEX outbox_event_created_at_idx ON outbox_event (created_at ASC);
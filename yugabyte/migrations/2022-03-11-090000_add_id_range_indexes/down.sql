@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX member_id_asc_idx;
+DROP INDEX user_id_asc_idx;
+DROP INDEX team_id_asc_idx;
+DROP INDEX auth_user_id_asc_idx;
@@ -0,0 +1,6 @@
+-- Your SQL goes here
+-- The primary keys are hash sharded, the cursor pages scan these indexes in the order of the ids.
+CREATE INDEX auth_user_id_asc_idx ON auth_user (id ASC);
+CREATE INDEX team_id_asc_idx ON team (id ASC);
+CREATE INDEX user_id_asc_idx ON "user" (id ASC);
+CREATE INDEX member_id_asc_idx ON member (id ASC);
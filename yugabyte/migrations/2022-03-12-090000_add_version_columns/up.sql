@@ -0,0 +1,8 @@
+-- Your SQL goes here
+-- The version is incremented by every update, the updates of a stale version are rejected.
+ALTER TABLE member
+ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE team
+ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE "user"
+ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE member
+    DROP COLUMN deleted_at;
+ALTER TABLE team
+    DROP COLUMN deleted_at;
+ALTER TABLE "user"
+    DROP COLUMN deleted_at;
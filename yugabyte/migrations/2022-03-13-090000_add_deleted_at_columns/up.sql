@@ -0,0 +1,8 @@
+-- Your SQL goes here
+-- The deleted rows keep their data until they are restored, the services filter them out.
+ALTER TABLE member
+ADD COLUMN deleted_at TIMESTAMP;
+ALTER TABLE team
+ADD COLUMN deleted_at TIMESTAMP;
+ALTER TABLE "user"
+ADD COLUMN deleted_at TIMESTAMP;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE "user"
+    DROP COLUMN auth_user_id;
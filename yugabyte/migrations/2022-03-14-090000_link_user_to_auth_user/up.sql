@@ -0,0 +1,8 @@
+-- Your SQL goes here
+-- The roles of a principal are resolved through this link, an email alone no longer proves who owns the user.
+ALTER TABLE "user"
+ADD COLUMN auth_user_id UUID UNIQUE REFERENCES auth_user (id) ON DELETE SET NULL;
+UPDATE "user"
+SET auth_user_id = auth_user.id
+FROM auth_user
+WHERE auth_user.email = "user".email;
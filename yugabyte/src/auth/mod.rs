@@ -0,0 +1,4 @@
+pub mod password;
+pub mod policy;
+pub mod principal;
+pub mod token;
@@ -0,0 +1,207 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::password_hash::rand_core::OsRng;
+use lazy_static::*;
+use serde::{Deserialize, Serialize};
+
+use error::error::Error;
+
+use crate::config::config;
+
+const PHC_PREFIX: &str = "$argon2";
+// The password of the dummy hash, its outcome is ignored.
+const DUMMY_PASSWORD: &str = "the password of no auth_user";
+
+lazy_static! {
+    // The cost parameters are read once, so every hash in the process is produced with the same settings.
+    pub static ref PASSWORD_POLICY: PasswordPolicy = config().password;
+    // Hashed once with the policy, so checking it costs as much as checking the hash of a real auth_user.
+    static ref DUMMY_PASSWORD_HASH: Option<String> = hash_password(DUMMY_PASSWORD).ok();
+}
+
+/// Argon2id cost parameters, the `password` section of the [`Config`](crate::config::Config). The defaults follow
+/// the OWASP recommendation (19 MiB of memory, 2 iterations, 1 lane).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PasswordPolicy {
+    pub memory_cost: u32,
+    pub time_cost: u32,
+    pub parallelism: u32,
+}
+
+/// The outcome of checking a plain password against the stored value.
+#[derive(Debug, PartialEq)]
+pub enum PasswordVerification {
+    Invalid,
+    // The password matched; `needs_rehash` is set when the stored value is plaintext
+    // or was hashed with parameters that differ from the current policy.
+    Valid { needs_rehash: bool },
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        PasswordPolicy {
+            memory_cost: 19_456,
+            time_cost: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    pub(crate) fn params(&self) -> Result<Params, Error> {
+        Params::new(self.memory_cost, self.time_cost, self.parallelism, None)
+            .map_err(|e| Error::InternalServerError(e.to_string()))
+    }
+
+    fn hasher(&self) -> Result<Argon2<'static>, Error> {
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params()?))
+    }
+
+    // Hash the plain password into a PHC string like `$argon2id$v=19$m=19456,t=2,p=1$<salt>$<hash>`.
+    pub fn hash_password(&self, plain_password: &str) -> Result<String, Error> {
+        let salt = SaltString::generate(&mut OsRng);
+        self.hasher()?
+            .hash_password(plain_password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|e| Error::InternalServerError(e.to_string()))
+    }
+
+    pub fn verify_password(
+        &self,
+        plain_password: &str,
+        stored_password: &str,
+    ) -> Result<PasswordVerification, Error> {
+        // Rows written before hashing was introduced still hold the plaintext password.
+        if !is_hashed(stored_password) {
+            return Ok(if constant_time_eq(plain_password.as_bytes(), stored_password.as_bytes()) {
+                PasswordVerification::Valid { needs_rehash: true }
+            } else {
+                PasswordVerification::Invalid
+            });
+        }
+
+        let parsed_hash = PasswordHash::new(stored_password)
+            .map_err(|e| Error::InternalServerError(e.to_string()))?;
+        // The parameters are taken from the PHC string itself, so old hashes keep verifying after the policy changes.
+        match Argon2::default().verify_password(plain_password.as_bytes(), &parsed_hash) {
+            Ok(()) => Ok(PasswordVerification::Valid {
+                needs_rehash: self.needs_rehash(&parsed_hash),
+            }),
+            Err(_) => Ok(PasswordVerification::Invalid),
+        }
+    }
+
+    fn needs_rehash(&self, parsed_hash: &PasswordHash) -> bool {
+        if parsed_hash.algorithm != Algorithm::Argon2id.ident() {
+            return true;
+        }
+        match Params::try_from(parsed_hash) {
+            Ok(params) => {
+                params.m_cost() != self.memory_cost
+                    || params.t_cost() != self.time_cost
+                    || params.p_cost() != self.parallelism
+            }
+            Err(_) => true,
+        }
+    }
+}
+
+// Hash the password with the process-wide policy.
+pub fn hash_password(plain_password: &str) -> Result<String, Error> {
+    PASSWORD_POLICY.hash_password(plain_password)
+}
+
+// Verify the password with the process-wide policy.
+pub fn verify_password(
+    plain_password: &str,
+    stored_password: &str,
+) -> Result<PasswordVerification, Error> {
+    PASSWORD_POLICY.verify_password(plain_password, stored_password)
+}
+
+// Spend the time of a password check when the email is unknown, so the login doesn't tell which emails exist.
+pub fn verify_unknown_password(plain_password: &str) {
+    if let Some(dummy_password_hash) = DUMMY_PASSWORD_HASH.as_deref() {
+        let _ = verify_password(plain_password, dummy_password_hash);
+    }
+}
+
+pub fn is_hashed(stored_password: &str) -> bool {
+    stored_password.starts_with(PHC_PREFIX)
+}
+
+fn constant_time_eq(left: &[u8], right: &[u8]) -> bool {
+    if left.len() != right.len() {
+        return false;
+    }
+    left.iter().zip(right).fold(0u8, |acc, (l, r)| acc | (l ^ r)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Keep the tests fast, the cost does not matter for correctness.
+    fn cheap_policy() -> PasswordPolicy {
+        PasswordPolicy {
+            memory_cost: 1024,
+            time_cost: 1,
+            parallelism: 1,
+        }
+    }
+
+    #[test]
+    fn hash_then_verify_succ() {
+        let policy = cheap_policy();
+        let hashed = policy.hash_password("correct horse battery").unwrap();
+
+        assert!(hashed.starts_with("$argon2id$"));
+        assert_eq!(
+            policy.verify_password("correct horse battery", &hashed).unwrap(),
+            PasswordVerification::Valid { needs_rehash: false }
+        );
+        assert_eq!(
+            policy.verify_password("wrong password", &hashed).unwrap(),
+            PasswordVerification::Invalid
+        );
+    }
+
+    #[test]
+    fn verify_requests_rehash_when_policy_changes() {
+        let hashed = cheap_policy().hash_password("correct horse battery").unwrap();
+        let stronger_policy = PasswordPolicy {
+            time_cost: 2,
+            ..cheap_policy()
+        };
+
+        assert_eq!(
+            stronger_policy.verify_password("correct horse battery", &hashed).unwrap(),
+            PasswordVerification::Valid { needs_rehash: true }
+        );
+    }
+
+    #[test]
+    fn the_dummy_hash_costs_like_the_policy() {
+        let dummy_password_hash = DUMMY_PASSWORD_HASH.as_deref().unwrap();
+        assert!(dummy_password_hash.starts_with("$argon2id$"));
+        assert_eq!(
+            verify_password(DUMMY_PASSWORD, dummy_password_hash).unwrap(),
+            PasswordVerification::Valid { needs_rehash: false }
+        );
+    }
+
+    #[test]
+    fn verify_plaintext_legacy_password() {
+        let policy = cheap_policy();
+
+        assert_eq!(
+            policy.verify_password("legacy-password", "legacy-password").unwrap(),
+            PasswordVerification::Valid { needs_rehash: true }
+        );
+        assert_eq!(
+            policy.verify_password("other-password", "legacy-password").unwrap(),
+            PasswordVerification::Invalid
+        );
+    }
+}
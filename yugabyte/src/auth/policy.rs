@@ -0,0 +1,119 @@
+use diesel::PgConnection;
+use uuid::Uuid;
+
+use error::error::Error;
+
+use crate::auth::principal::Principal;
+use crate::engine::member::{find_member_roles, find_members_by_user_id, find_team_ids_of_auth_user};
+use crate::model::member::MemberRole;
+use crate::model::user::User;
+
+/// The actions that are checked against the role of the principal in the team.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    // A new team has no owner yet, so only global administrators create teams and add their first owners.
+    CreateTeam,
+    ViewTeam,
+    UpdateTeam,
+    DeleteTeam,
+    ManageMembers,
+    ManageAuthUsers,
+    RemoveAll,
+    // List and restore the deleted teams, users and members.
+    ManageDeleted,
+}
+
+impl MemberRole {
+    pub fn allows(&self, action: Action) -> bool {
+        match action {
+            Action::ViewTeam => true,
+            Action::UpdateTeam | Action::ManageMembers => matches!(self, MemberRole::Owner | MemberRole::Admin),
+            Action::DeleteTeam => *self == MemberRole::Owner,
+            // These actions are not bound to a team, only global administrators can do them.
+            Action::CreateTeam | Action::ManageAuthUsers | Action::RemoveAll | Action::ManageDeleted => false,
+        }
+    }
+}
+
+// Answer whether the principal can do the action on the team. Global administrators can do everything,
+// the others are judged by their roles in the team, and can't do anything when there is no team.
+pub fn can(
+    principal: &Principal,
+    action: Action,
+    other_team_id: Option<&Uuid>,
+    connection: &PgConnection,
+) -> Result<bool, Error> {
+    if principal.is_admin {
+        return Ok(true);
+    }
+    match other_team_id {
+        Some(other_team_id) => Ok(roles_allow(&find_member_roles(&principal.auth_user_id, other_team_id, connection)?, action)),
+        None => Ok(false),
+    }
+}
+
+// Whether one of the roles of the principal in a team allows the action.
+pub fn roles_allow(member_roles: &[MemberRole], action: Action) -> bool {
+    member_roles.iter().any(|member_role| member_role.allows(action))
+}
+
+pub fn forbidden(principal: &Principal, action: Action) -> Error {
+    Error::Forbidden(format!("{} is not allowed to {:?}", principal.email, action))
+}
+
+// Like can, but it fails with Error::Forbidden when the action is not allowed.
+pub fn authorize(
+    principal: &Principal,
+    action: Action,
+    other_team_id: Option<&Uuid>,
+    connection: &PgConnection,
+) -> Result<(), Error> {
+    if can(principal, action, other_team_id, connection)? {
+        Ok(())
+    } else {
+        Err(forbidden(principal, action))
+    }
+}
+
+// The teams whose members the principal can see, every role can view its team. None stands for every team,
+// the global administrators see them all.
+pub fn visible_team_ids(principal: &Principal, connection: &PgConnection) -> Result<Option<Vec<Uuid>>, Error> {
+    if principal.is_admin {
+        return Ok(None);
+    }
+    find_team_ids_of_auth_user(&principal.auth_user_id, connection).map(Some)
+}
+
+// A user can be seen by the principal linked to it, and by the principals who can view one of its teams.
+pub fn authorize_view_user(principal: &Principal, other_user: &User, connection: &PgConnection) -> Result<(), Error> {
+    if other_user.auth_user_id == Some(principal.auth_user_id) {
+        return Ok(());
+    }
+    let visible_team_ids = match visible_team_ids(principal, connection)? {
+        Some(visible_team_ids) => visible_team_ids,
+        None => return Ok(()),
+    };
+    let memberships = find_members_by_user_id(&other_user.id, connection)?;
+    if memberships.iter().any(|membership| visible_team_ids.contains(&membership.team_id)) {
+        Ok(())
+    } else {
+        Err(forbidden(principal, Action::ViewTeam))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn member_role_allows() {
+        assert!(MemberRole::Owner.allows(Action::DeleteTeam));
+        assert!(!MemberRole::Admin.allows(Action::DeleteTeam));
+        assert!(MemberRole::Admin.allows(Action::ManageMembers));
+        assert!(!MemberRole::Viewer.allows(Action::ManageMembers));
+        assert!(MemberRole::Viewer.allows(Action::ViewTeam));
+        assert!(!MemberRole::Owner.allows(Action::RemoveAll));
+        assert!(!MemberRole::Owner.allows(Action::ManageDeleted));
+        assert!(!MemberRole::Owner.allows(Action::CreateTeam));
+    }
+}
@@ -0,0 +1,71 @@
+use actix_web::{dev, FromRequest, HttpRequest};
+use futures_util::future::{err, ok, Ready};
+use paperclip::actix::Apiv2Security;
+use uuid::Uuid;
+
+use error::error::{Error, Errors};
+use error::error::StateCode::InvalidToken;
+
+use crate::auth::token::{Claims, TOKEN_SETTINGS, TokenType};
+use crate::model::auth_user::AuthUser;
+
+/// The authenticated caller. The authentication middleware puts it into the request extensions,
+/// and the REST handlers receive it as an extractor argument.
+#[derive(Debug, Clone, Apiv2Security)]
+#[openapi(
+    apiKey,
+    in = "header",
+    name = "Authorization",
+    description = "Use the format 'Bearer <access_token>'"
+)]
+pub struct Principal {
+    pub auth_user_id: Uuid,
+    pub email: String,
+    pub is_admin: bool,
+}
+
+impl From<Claims> for Principal {
+    fn from(claims: Claims) -> Self {
+        Principal {
+            auth_user_id: claims.sub,
+            email: claims.email,
+            is_admin: claims.adm,
+        }
+    }
+}
+
+impl From<&AuthUser> for Principal {
+    fn from(auth_user: &AuthUser) -> Self {
+        Principal {
+            auth_user_id: auth_user.id,
+            email: auth_user.email.clone(),
+            is_admin: auth_user.is_admin,
+        }
+    }
+}
+
+impl FromRequest for Principal {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _: &mut dev::Payload) -> Self::Future {
+        match req.extensions().get::<Principal>() {
+            Some(principal) => ok(principal.clone()),
+            None => err(Errors::Unauthorized(InvalidToken.into()).into()),
+        }
+    }
+}
+
+// Unwrap the principal of the GraphQL resolvers that are reachable anonymously.
+pub fn require_principal(principal: &Option<Principal>) -> Result<&Principal, Error> {
+    principal
+        .as_ref()
+        .ok_or_else(|| Error::Unauthorized("Authentication is required".to_string()))
+}
+
+// Verify an `Authorization` value (`Bearer <access_token>`), the WebSocket clients send it in their connection_init payload.
+pub fn principal_from_authorization(value: &str) -> Result<Principal, Error> {
+    let token = value.strip_prefix("Bearer ").unwrap_or(value).trim();
+    TOKEN_SETTINGS.verify(token, TokenType::Access).map(Principal::from)
+}
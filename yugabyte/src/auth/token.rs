@@ -0,0 +1,169 @@
+use chrono::Utc;
+use jsonwebtoken::{decode, DecodingKey, encode, EncodingKey, Header, Validation};
+use lazy_static::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use error::error::Error;
+
+use crate::auth::principal::Principal;
+use crate::config::{AuthConfig, config};
+
+lazy_static! {
+    pub static ref TOKEN_SETTINGS: TokenSettings = TokenSettings::from(&config().auth);
+}
+
+/// Signing secret and lifetimes of the issued tokens, taken from the `auth` section of the [`Config`](crate::config::Config).
+#[derive(Debug, Clone)]
+pub struct TokenSettings {
+    pub secret: Option<String>,
+    pub access_token_ttl: i64,
+    pub refresh_token_ttl: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenType {
+    Access,
+    Refresh,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claims {
+    // The id of the auth_user the token was issued for.
+    pub sub: Uuid,
+    pub email: String,
+    // It is set for global administrators.
+    pub adm: bool,
+    pub typ: TokenType,
+    // For refresh tokens it is the id of the refresh_token row, which allows revoking the token.
+    pub jti: Uuid,
+    pub iat: i64,
+    pub exp: i64,
+}
+
+impl From<&AuthConfig> for TokenSettings {
+    fn from(auth_config: &AuthConfig) -> Self {
+        TokenSettings {
+            secret: auth_config.jwt_secret.clone().filter(|secret| !secret.is_empty()),
+            access_token_ttl: auth_config.access_token_ttl_seconds,
+            refresh_token_ttl: auth_config.refresh_token_ttl_seconds,
+        }
+    }
+}
+
+impl TokenSettings {
+
+    fn secret(&self) -> Result<&[u8], Error> {
+        self.secret
+            .as_ref()
+            .map(|secret| secret.as_bytes())
+            .ok_or_else(|| Error::InternalServerError("JWT_SECRET is not configured".to_string()))
+    }
+
+    pub fn ttl(&self, token_type: TokenType) -> i64 {
+        match token_type {
+            TokenType::Access => self.access_token_ttl,
+            TokenType::Refresh => self.refresh_token_ttl,
+        }
+    }
+
+    pub fn issue(
+        &self,
+        principal: &Principal,
+        token_type: TokenType,
+        jti: Uuid,
+    ) -> Result<String, Error> {
+        let issued_at = Utc::now().timestamp();
+        let claims = Claims {
+            sub: principal.auth_user_id,
+            email: principal.email.clone(),
+            adm: principal.is_admin,
+            typ: token_type,
+            jti,
+            iat: issued_at,
+            exp: issued_at + self.ttl(token_type),
+        };
+        encode(&Header::default(), &claims, &EncodingKey::from_secret(self.secret()?))
+            .map_err(|e| Error::InternalServerError(e.to_string()))
+    }
+
+    // Check the signature, the expiration and the type of the token.
+    pub fn verify(&self, token: &str, token_type: TokenType) -> Result<Claims, Error> {
+        self.decode(token, token_type, &Validation::default())
+    }
+
+    // Like verify, but it accepts expired tokens, this is used to revoke a refresh token on logout.
+    pub fn verify_ignoring_expiration(&self, token: &str, token_type: TokenType) -> Result<Claims, Error> {
+        let validation = Validation {
+            validate_exp: false,
+            ..Validation::default()
+        };
+        self.decode(token, token_type, &validation)
+    }
+
+    fn decode(&self, token: &str, token_type: TokenType, validation: &Validation) -> Result<Claims, Error> {
+        let claims = decode::<Claims>(token, &DecodingKey::from_secret(self.secret()?), validation)
+            .map_err(|e| Error::Unauthorized(e.to_string()))?
+            .claims;
+        if claims.typ != token_type {
+            return Err(Error::Unauthorized("Unexpected token type".to_string()));
+        }
+        Ok(claims)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn principal() -> Principal {
+        Principal {
+            auth_user_id: Uuid::new_v4(),
+            email: "user@mail.com".to_string(),
+            is_admin: false,
+        }
+    }
+
+    fn settings() -> TokenSettings {
+        TokenSettings {
+            secret: Some("test-secret".to_string()),
+            access_token_ttl: 60,
+            refresh_token_ttl: 120,
+        }
+    }
+
+    #[test]
+    fn issue_then_verify_succ() {
+        let principal = principal();
+        let token = settings()
+            .issue(&principal, TokenType::Access, Uuid::new_v4())
+            .unwrap();
+
+        let claims = settings().verify(&token, TokenType::Access).unwrap();
+        assert_eq!(claims.sub, principal.auth_user_id);
+        assert_eq!(claims.exp - claims.iat, 60);
+    }
+
+    #[test]
+    fn verify_rejects_other_token_type() {
+        let token = settings()
+            .issue(&principal(), TokenType::Refresh, Uuid::new_v4())
+            .unwrap();
+
+        assert!(settings().verify(&token, TokenType::Access).is_err());
+    }
+
+    #[test]
+    fn verify_rejects_other_secret() {
+        let token = settings()
+            .issue(&principal(), TokenType::Access, Uuid::new_v4())
+            .unwrap();
+        let other_settings = TokenSettings {
+            secret: Some("other-secret".to_string()),
+            ..settings()
+        };
+
+        assert!(other_settings.verify(&token, TokenType::Access).is_err());
+    }
+}
@@ -1,8 +1,180 @@
-use crate::db_connection::PgPool;
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use diesel::PgConnection;
+use diesel::sql_types::Text;
+use tracing::warn;
+use uuid::Uuid;
+
+use error::error::Error;
+
+use crate::auth::policy::{Action, forbidden, roles_allow};
+use crate::auth::principal::{Principal, require_principal};
+use crate::db_connection::{get_connection, PgPool, PgPooledConnection};
+use crate::engine::member::find_team_roles_of_auth_user;
+use crate::event_bus::EventBus;
+use crate::loader::Loaders;
+use crate::model::member::MemberRole;
+use crate::util::raw_query::raw_query;
 
 pub struct GraphQLContext {
     pub pool: PgPool,
+    // It is None for anonymous calls, which can only reach the login mutation.
+    pub principal: Option<Principal>,
+    // The relationship fields load their rows through them, they live as long as the request.
+    pub loaders: Loaders,
+    // The subscriptions listen to the committed changes through it.
+    pub events: Arc<EventBus>,
+    // The roles of the principal in each of its teams, loaded by the first check of the request.
+    team_roles: Mutex<Option<HashMap<Uuid, Vec<MemberRole>>>>,
+    // The time limit of the request and when it started, the subscriptions have none.
+    timeout: Option<(Duration, Instant)>,
+}
+
+impl GraphQLContext {
+    pub fn new(pool: PgPool, principal: Option<Principal>, events: Arc<EventBus>) -> Self {
+        GraphQLContext {
+            pool,
+            principal,
+            loaders: Loaders::default(),
+            events,
+            team_roles: Mutex::new(None),
+            timeout: None,
+        }
+    }
+
+    // Limit the request to the timeout from now on, see connection.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some((timeout, Instant::now()));
+        self
+    }
+
+    pub fn principal(&self) -> Result<&Principal, Error> {
+        require_principal(&self.principal)
+    }
+
+    // The resolvers fail with a service-unavailable error when the pool is exhausted. The request keeps running on
+    // its blocking thread after it timed out, so the connections are refused past the timeout, and the statements
+    // run before are cancelled by the database when the time is up.
+    pub fn connection(&self) -> Result<RequestConnection, Error> {
+        let (timeout, started) = match self.timeout {
+            Some(timeout) => timeout,
+            None => return Ok(RequestConnection { connection: get_connection(&self.pool)?, limited: false }),
+        };
+        let remaining = timeout.saturating_sub(started.elapsed());
+        if remaining.is_zero() {
+            return Err(Error::Timeout(timeout.as_millis() as u64));
+        }
+        let connection = get_connection(&self.pool)?;
+        raw_query("SELECT set_config('statement_timeout', $1, false)")
+            .bind::<Text, _>(remaining.as_millis().max(1).to_string())
+            .execute(&connection)?;
+        Ok(RequestConnection { connection, limited: true })
+    }
+
+    // Like policy::authorize, for the relationship fields that are checked once per parent: the roles of the
+    // principal are read once per request. The mutations keep using policy::authorize, they can change the roles.
+    pub fn authorize(&self, action: Action, team_id: Option<&Uuid>) -> Result<(), Error> {
+        let principal = self.principal()?;
+        if principal.is_admin {
+            return Ok(());
+        }
+        let allowed = match team_id {
+            Some(team_id) => self.with_team_roles(|team_roles| {
+                team_roles.get(team_id).is_some_and(|member_roles| roles_allow(member_roles, action))
+            })?,
+            None => false,
+        };
+        if allowed {
+            Ok(())
+        } else {
+            Err(forbidden(principal, action))
+        }
+    }
+
+    // Like policy::visible_team_ids, from the roles read once per request.
+    pub fn visible_team_ids(&self) -> Result<Option<Vec<Uuid>>, Error> {
+        if self.principal()?.is_admin {
+            return Ok(None);
+        }
+        self.with_team_roles(|team_roles| Some(team_roles.keys().copied().collect()))
+    }
+
+    fn with_team_roles<T, F>(&self, read: F) -> Result<T, Error>
+        where
+            F: FnOnce(&HashMap<Uuid, Vec<MemberRole>>) -> T,
+    {
+        let principal = self.principal()?;
+        let mut team_roles = self.team_roles.lock().unwrap();
+        if let Some(team_roles) = team_roles.as_ref() {
+            return Ok(read(team_roles));
+        }
+        let pg_connection = &self.connection()?;
+        let mut loaded_team_roles: HashMap<Uuid, Vec<MemberRole>> = HashMap::new();
+        for (team_id, member_role) in find_team_roles_of_auth_user(&principal.auth_user_id, pg_connection)? {
+            loaded_team_roles.entry(team_id).or_default().push(member_role);
+        }
+        Ok(read(team_roles.insert(loaded_team_roles)))
+    }
 }
 
 // This impl allows us to pass in GraphQLContext as the Context for GraphQL objects.
 impl juniper::Context for GraphQLContext {}
+
+/// A connection of the pool checked out by a GraphQL request, its statement timeout is reset when it goes back.
+pub struct RequestConnection {
+    connection: PgPooledConnection,
+    limited: bool,
+}
+
+impl Deref for RequestConnection {
+    type Target = PgConnection;
+
+    fn deref(&self) -> &PgConnection {
+        &self.connection
+    }
+}
+
+impl Drop for RequestConnection {
+    fn drop(&mut self) {
+        if self.limited {
+            if let Err(err) = raw_query("SET statement_timeout = DEFAULT").execute(&self.connection) {
+                warn!(%err, "Failed to reset the statement timeout of a connection");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::dsl::sql;
+    use diesel::RunQueryDsl;
+
+    use crate::config::{config, DatabaseConfig};
+    use crate::db_connection::init_pool;
+
+    use super::*;
+
+    fn statement_timeout(connection: &PgConnection) -> String {
+        diesel::select(sql::<Text>("current_setting('statement_timeout')"))
+            .get_result(connection)
+            .unwrap()
+    }
+
+    #[test]
+    fn connection_is_limited_to_the_time_left() {
+        // One connection, so the reset one is checked out again.
+        let pool = init_pool(&DatabaseConfig { pool_max_size: 1, ..config().database.clone() }).unwrap();
+        let context = GraphQLContext::new(pool.clone(), None, Arc::new(EventBus::default()))
+            .with_timeout(Duration::from_secs(60));
+        let connection = context.connection().unwrap();
+        assert_ne!(statement_timeout(&connection), "0");
+        drop(connection);
+        assert_eq!(statement_timeout(&get_connection(&pool).unwrap()), "0");
+
+        let timed_out = GraphQLContext::new(pool, None, Arc::new(EventBus::default())).with_timeout(Duration::ZERO);
+        assert!(matches!(timed_out.connection(), Err(Error::Timeout(0))));
+    }
+}
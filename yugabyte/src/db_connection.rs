@@ -1,6 +1,18 @@
-use actix_web::{HttpResponse, web};
+use std::fmt::Debug;
+use std::time::Duration;
+
+use actix_web::error::BlockingError;
+use actix_web::web;
 use diesel::{pg::PgConnection, r2d2::PooledConnection};
+use diesel::Connection;
+use diesel::connection::TransactionManager;
 use diesel::r2d2::{ConnectionManager, Pool, PoolError};
+use diesel::result::Error as DieselError;
+
+use error::error::Error;
+
+use crate::config::DatabaseConfig;
+use crate::retry::{RETRY_POLICY, Retryable};
 
 pub type PgPool = Pool<ConnectionManager<PgConnection>>;
 pub type PgPooledConnection = PooledConnection<ConnectionManager<PgConnection>>;
@@ -8,11 +20,32 @@ pub type PgPooledConnection = PooledConnection<ConnectionManager<PgConnection>>;
 pub struct CoreDBPool(pub PgPool);
 
 impl CoreDBPool {
-    // Create db connection pool for core database
-    pub fn default() -> CoreDBPool {
-        dotenv::dotenv().expect("Failed to read .env file");
-        let url = std::env::var("DATABASE_URL").unwrap();
-        CoreDBPool(init_pool(url.as_str()).expect("Failed to create pool for core DB"))
+    // Create db connection pool for core database, it fails when the database can't be reached.
+    pub fn new(database_config: &DatabaseConfig) -> Result<CoreDBPool, Error> {
+        init_pool(database_config)
+            .map(CoreDBPool)
+            .map_err(|e| Error::ConnectionError(format!("Failed to create pool for core DB: {}", e)))
+    }
+
+    // Run the database work with a connection of the pool on the blocking thread pool of actix, so the workers keep
+    // serving the other requests while it waits on the database. The handlers await it instead of blocking.
+    // A pool that has no free connection within `database.pool_timeout_ms` fails with a ConnectionError, served as 503.
+    pub async fn run<T, E, F>(&self, unit_of_work: F) -> Result<T, E>
+        where
+            F: FnOnce(&PgConnection) -> Result<T, E> + Send + 'static,
+            T: Send + 'static,
+            E: From<Error> + Send + Debug + 'static,
+    {
+        let pool = self.0.clone();
+        web::block(move || {
+            let connection = get_connection(&pool)?;
+            unit_of_work(&connection)
+        })
+            .await
+            .map_err(|err| match err {
+                BlockingError::Error(err) => err,
+                BlockingError::Canceled => E::from(Error::InternalServerError("The database task was canceled".to_string())),
+            })
     }
 }
 
@@ -26,28 +59,68 @@ impl DBPoolConvertable for CoreDBPool {
     }
 }
 
-// Convert data of pool type to pgconnection
-pub fn pgdata_to_pgconnection<T: DBPoolConvertable>(pgdata: web::Data<T>) -> PgPooledConnection {
-    pgdata
-        .to_pgpool()
-        .get()
-        .map_err(|e| HttpResponse::InternalServerError().json(e.to_string()))
-        .expect("Getting pg connection exception")
+// Check out a connection, an exhausted or unreachable pool is a ConnectionError instead of a panic.
+pub fn get_connection(pool: &PgPool) -> Result<PgPooledConnection, Error> {
+    pool.get().map_err(|e| Error::ConnectionError(e.to_string()))
+}
+
+// Run the unit of work in a database transaction, it is committed when the closure returns Ok and rolled back otherwise.
+// A pooled connection derefs to PgConnection, so `&pg_connection` can be passed directly. Nested calls become
+// savepoints, which lets engine functions that use it be composed inside a bigger transaction.
+// The outermost transaction is run again when it fails with a serialization or restart read error, so the closure
+// must be safe to repeat; nested calls leave the retry to the outermost one because the whole transaction is aborted.
+pub fn with_transaction<T, E, F>(connection: &PgConnection, mut unit_of_work: F) -> Result<T, E>
+    where
+        F: FnMut(&PgConnection) -> Result<T, E>,
+        E: From<DieselError> + Retryable + std::fmt::Display,
+{
+    if TransactionManager::<PgConnection>::get_transaction_depth(connection.transaction_manager()) > 0 {
+        return connection.transaction(|| unit_of_work(connection));
+    }
+    RETRY_POLICY.run(|| connection.transaction(|| unit_of_work(connection)))
 }
 
-// Initiate pgpool from the database in database_url
-pub fn init_pool(database_url: &str) -> Result<PgPool, PoolError> {
-    let manager = ConnectionManager::<PgConnection>::new(database_url);
-    Pool::builder().build(manager)
+// Run the reads of the unit of work in a read only REPEATABLE READ transaction, so they all see the same snapshot.
+// It is retried like with_transaction, and can't be nested in another transaction.
+pub fn with_snapshot<T, E, F>(connection: &PgConnection, mut unit_of_work: F) -> Result<T, E>
+    where
+        F: FnMut(&PgConnection) -> Result<T, E>,
+        E: From<DieselError> + Retryable + std::fmt::Display,
+{
+    RETRY_POLICY.run(|| {
+        connection
+            .build_transaction()
+            .repeatable_read()
+            .read_only()
+            .run(|| unit_of_work(connection))
+    })
+}
+
+// Initiate pgpool from the database in database_url, with its size and checkout timeout.
+pub fn init_pool(database_config: &DatabaseConfig) -> Result<PgPool, PoolError> {
+    let manager = ConnectionManager::<PgConnection>::new(database_config.url.as_str());
+    Pool::builder()
+        .max_size(database_config.pool_max_size)
+        .connection_timeout(Duration::from_millis(database_config.pool_timeout_ms))
+        .build(manager)
 }
 
 #[cfg(test)]
 mod tests {
+    use diesel::dsl::sql;
+    use diesel::RunQueryDsl;
+    use diesel::sql_types::Text;
+
+    use crate::config::config;
+
     use super::*;
 
     #[test]
     fn init_pool_from_str_failed() -> Result<(), ()> {
-        let pool = init_pool("NotCorrect");
+        let pool = init_pool(&DatabaseConfig {
+            url: "NotCorrect".to_string(),
+            ..DatabaseConfig::default()
+        });
         match pool {
             Err(_) => Ok(()),
             _ => Err(()),
@@ -56,12 +129,22 @@ mod tests {
 
     #[test]
     fn init_pool_from_str_succ() -> Result<(), ()> {
-        dotenv::dotenv().expect("Failed to read .env file");
-        let url = std::env::var("DATABASE_URL").unwrap();
-        let pool = init_pool(url.as_str());
+        let pool = init_pool(&config().database);
         match pool {
             Ok(_) => Ok(()),
             _ => Err(()),
         }
     }
+
+    #[test]
+    fn with_snapshot_reads_in_a_repeatable_read_transaction() {
+        let pool = init_pool(&config().database).unwrap();
+        let connection = pool.get().unwrap();
+        let isolation = with_snapshot::<_, Error, _>(&connection, |connection| {
+            diesel::select(sql::<Text>("current_setting('transaction_isolation')"))
+                .get_result::<String>(connection)
+                .map_err(Error::from)
+        });
+        assert_eq!(isolation.unwrap(), "repeatable read");
+    }
 }
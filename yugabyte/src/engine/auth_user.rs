@@ -1,28 +1,41 @@
-use diesel::{ExpressionMethods, RunQueryDsl};
+use diesel::{ExpressionMethods, OptionalExtension, RunQueryDsl, TextExpressionMethods};
 use diesel::PgConnection;
 use diesel::QueryDsl;
 use uuid::Uuid;
 
 use error::error::Error;
 
-use crate::model::auth_user::AuthUser;
-use crate::model::dto::PaginationDTO;
-use crate::model::user::NewUser;
-use crate::schema::auth_user::dsl::auth_user;
+use crate::auth::password::{hash_password, is_hashed, PasswordVerification, verify_password, verify_unknown_password};
+use crate::db_connection::with_transaction;
+use crate::engine::refresh_token::revoke_all_refresh_tokens_of_auth_user;
+use crate::model::auth_user::{AuthUser, AuthUserPatch, NewAuthUser};
+use crate::model::page::{Page, PageRequest};
+use crate::model::patch::Patch;
+use crate::schema::auth_user::dsl::{auth_user, email, password};
 use crate::schema::auth_user::dsl::id as auth_user_id;
+use crate::schema::user::dsl::user;
+use crate::schema::user::dsl::auth_user_id as user_auth_user_id;
+use crate::schema::user::dsl::email as user_email;
+use crate::schema::user::dsl::version as user_version;
 
-impl NewUser {
-    pub fn add_auth_user(&self, connection: &PgConnection) -> Result<AuthUser, Error> {
-        // todo: don't forget to hash the password
-        let initialized_auth_user = AuthUser {
+impl NewAuthUser {
+    // Build the auth_user row with the password already hashed, so the plain password never reaches the database.
+    pub fn to_auth_user(&self) -> Result<AuthUser, Error> {
+        Ok(AuthUser {
             id: Uuid::new_v4(),
             email: self.email.clone(),
-            password: self.password.clone(),
-        };
+            password: hash_password(&self.password)?,
+            is_admin: false,
+        })
+    }
+
+    pub fn add_auth_user(&self, connection: &PgConnection) -> Result<AuthUser, Error> {
+        let initialized_auth_user = self.to_auth_user()?;
+        check_email_of_other_user(&initialized_auth_user.email, &initialized_auth_user.id, connection)?;
         diesel::insert_into(auth_user)
             .values(&initialized_auth_user)
             .get_result(connection)
-            .map_err(|_| Error::DuplicationError)
+            .map_err(Error::from)
     }
 }
 
@@ -30,48 +43,141 @@ pub fn insert_bulk_auth_users(
     other_auth_users: &Vec<AuthUser>,
     connection: &PgConnection,
 ) -> Result<Vec<AuthUser>, Error> {
+    // Rows that are re-inserted after a delete already carry a hash, only the plain ones are hashed here.
+    let mut hashed_auth_users = Vec::with_capacity(other_auth_users.len());
+    for other_auth_user in other_auth_users {
+        let mut hashed_auth_user = other_auth_user.clone();
+        if !is_hashed(&hashed_auth_user.password) {
+            hashed_auth_user.password = hash_password(&hashed_auth_user.password)?;
+        }
+        check_email_of_other_user(&hashed_auth_user.email, &hashed_auth_user.id, connection)?;
+        hashed_auth_users.push(hashed_auth_user);
+    }
+
     diesel::insert_into(auth_user)
-        .values(other_auth_users)
+        .values(&hashed_auth_users)
         .get_results::<AuthUser>(connection)
-        .map_err(|_| Error::DuplicationError)
+        .map_err(Error::from)
+}
+
+// Refuse an email that belongs to a user linked to another auth_user, the two would otherwise look like one person.
+fn check_email_of_other_user(
+    other_email: &str,
+    other_auth_user_id: &Uuid,
+    connection: &PgConnection,
+) -> Result<(), Error> {
+    let linked_auth_user_id = user
+        .filter(user_email.eq(other_email))
+        .select(user_auth_user_id)
+        .first::<Option<Uuid>>(connection)
+        .optional()
+        .map_err(Error::from)?;
+    match linked_auth_user_id {
+        Some(Some(linked_auth_user_id)) if linked_auth_user_id != *other_auth_user_id => {
+            Err(Error::UniqueViolation("email".to_string()))
+        }
+        _ => Ok(()),
+    }
+}
+
+// Check the credentials and return the matched auth_user, the stored hash is upgraded
+// transparently when it is plaintext or was produced with outdated cost parameters.
+pub fn verify_auth_user_credentials(
+    other_email: &str,
+    plain_password: &str,
+    connection: &PgConnection,
+) -> Result<Option<AuthUser>, Error> {
+    let found_auth_user = match auth_user
+        .filter(email.eq(other_email))
+        .first::<AuthUser>(connection)
+    {
+        Ok(found_auth_user) => found_auth_user,
+        Err(diesel::result::Error::NotFound) => {
+            verify_unknown_password(plain_password);
+            return Ok(None);
+        }
+        Err(err) => return Err(Error::from(err)),
+    };
+
+    match verify_password(plain_password, &found_auth_user.password)? {
+        PasswordVerification::Invalid => Ok(None),
+        PasswordVerification::Valid { needs_rehash: false } => Ok(Some(found_auth_user)),
+        PasswordVerification::Valid { needs_rehash: true } => {
+            diesel::update(auth_user.find(&found_auth_user.id))
+                .set(password.eq(hash_password(plain_password)?))
+                .get_result::<AuthUser>(connection)
+                .map(Some)
+                .map_err(Error::from)
+        }
+    }
 }
 
+// Hash every password that is still stored in plaintext, it returns the number of migrated rows.
+pub fn hash_plaintext_passwords(connection: &PgConnection) -> Result<usize, Error> {
+    let plaintext_auth_users = auth_user
+        .filter(password.not_like("$argon2%"))
+        .load::<AuthUser>(connection)
+        .map_err(Error::from)?;
+
+    for plaintext_auth_user in &plaintext_auth_users {
+        diesel::update(auth_user.find(&plaintext_auth_user.id))
+            .set(password.eq(hash_password(&plaintext_auth_user.password)?))
+            .execute(connection)
+            .map_err(Error::from)?;
+    }
+    Ok(plaintext_auth_users.len())
+}
+
+// List the page of auth_users in the order of their ids, the cursors are compared to the ids
+// so a page is not shifted by the rows added or removed before it.
 pub fn list_all_auth_users(
-    pagination_dto: &PaginationDTO,
+    page_request: &PageRequest,
     connection: &PgConnection,
-) -> Result<Vec<AuthUser>, Error> {
-    auth_user
-        .limit(pagination_dto.page_size as i64)
-        .offset(pagination_dto.offset as i64)
+) -> Result<Page<AuthUser>, Error> {
+    let mut query = auth_user.into_boxed();
+    if let Some(after) = &page_request.after {
+        query = query.filter(auth_user_id.gt(after.0));
+    }
+    if let Some(before) = &page_request.before {
+        query = query.filter(auth_user_id.lt(before.0));
+    }
+    query = if page_request.backward {
+        query.order(auth_user_id.desc())
+    } else {
+        query.order(auth_user_id.asc())
+    };
+    let rows = query
+        .offset(page_request.offset)
+        .limit(page_request.size + 1)
         .load::<AuthUser>(connection)
-        .map_err(|err| Error::DBError(err))
+        .map_err(Error::from)?;
+    Ok(Page::from_rows(rows, page_request))
 }
 
 pub fn count_auth_users(connection: &PgConnection) -> Result<i64, Error> {
     auth_user
         .count()
         .get_result(connection)
-        .map_err(|e| Error::DBError(e))
+        .map_err(Error::from)
 }
 
 // todo: need to return the deleted AuthUser to use it in the GraphQL
-pub fn delete_auth_user_by_id(other_auth_user_id: &Uuid, connection: &PgConnection) -> bool {
+// An unknown id is a NotFound, so it can be told apart from a failed query.
+pub fn delete_auth_user_by_id(other_auth_user_id: &Uuid, connection: &PgConnection) -> Result<bool, Error> {
     match diesel::delete(
         auth_user.filter(auth_user_id.eq(other_auth_user_id))
     )
         .execute(connection)
-        .map_err(|e| Error::DBError(e)) {
-        // I can return the deleted object but I decided to return bool to show you that I can manage the code.
-        Ok(0) => false,
-        Ok(1) => true,
-        _ => false,
+        .map_err(Error::from)? {
+        0 => Err(Error::NotFound("Cannot find the auth_user".to_string())),
+        _ => Ok(true),
     }
 }
 
-pub fn delete_all_auth_users(connection: &PgConnection) -> Result<Vec<AuthUser>, Error> {
+pub fn delete_all_auth_users(connection: &PgConnection) -> Result<usize, Error> {
     diesel::delete(auth_user)
-        .get_results::<AuthUser>(connection)
-        .map_err(|err| Error::DBError(err))
+        .execute(connection)
+        .map_err(Error::from)
 }
 
 pub fn find_auth_user_by_id(
@@ -81,5 +187,62 @@ pub fn find_auth_user_by_id(
     auth_user
         .find(other_auth_user_id)
         .get_result::<AuthUser>(connection)
-        .map_err(|err| Error::DBError(err))
+        .map_err(Error::from)
+}
+
+// Change the fields set in the patch, the others keep their stored values. A new email is also given to the
+// linked user in the same transaction. A new password revokes the refresh tokens, the sessions opened with the old one end when their access token expires.
+pub fn update_auth_user(
+    other_auth_user_id: &Uuid,
+    auth_user_patch: &AuthUserPatch,
+    connection: &PgConnection,
+) -> Result<AuthUser, Error> {
+    // Diesel refuses an update without any column, an empty patch changes nothing.
+    if auth_user_patch.is_empty() {
+        return find_auth_user_by_id(other_auth_user_id, connection);
+    }
+    let hashed_patch = AuthUserPatch {
+        password: match &auth_user_patch.password {
+            Some(plain_password) => Some(hash_password(plain_password)?),
+            None => None,
+        },
+        ..auth_user_patch.clone()
+    };
+
+    with_transaction(connection, |connection| {
+        let stored_auth_user = find_auth_user_by_id(other_auth_user_id, connection)?;
+        if let Some(new_email) = &hashed_patch.email {
+            check_email_of_other_user(new_email, other_auth_user_id, connection)?;
+        }
+        let updated_auth_user = diesel::update(auth_user.find(other_auth_user_id))
+            .set(&hashed_patch)
+            .get_result::<AuthUser>(connection)
+            .map_err(Error::from)?;
+        if updated_auth_user.email != stored_auth_user.email {
+            // The email of the user changes too, so the ETags the clients hold for it become stale.
+            diesel::update(user.filter(user_auth_user_id.eq(other_auth_user_id)))
+                .set((user_email.eq(&updated_auth_user.email), user_version.eq(user_version + 1)))
+                .execute(connection)
+                .map_err(Error::from)?;
+        }
+        if hashed_patch.password.is_some() {
+            revoke_all_refresh_tokens_of_auth_user(other_auth_user_id, connection)?;
+        }
+        Ok(updated_auth_user)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::config;
+    use crate::db_connection::{get_connection, init_pool};
+
+    use super::*;
+
+    #[test]
+    fn delete_auth_user_by_id_of_an_unknown_id_is_not_found() {
+        let pool = init_pool(&config().database).unwrap();
+        let connection = get_connection(&pool).unwrap();
+        assert!(matches!(delete_auth_user_by_id(&Uuid::new_v4(), &connection), Err(Error::NotFound(_))));
+    }
 }
@@ -0,0 +1,71 @@
+use chrono::NaiveDateTime;
+use diesel::{ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
+use diesel::expression::dsl::max;
+use uuid::Uuid;
+
+use error::error::Error;
+
+use crate::model::event::{DomainEvent, NewOutboxEvent, OutboxEvent};
+use crate::schema::outbox_event::dsl::{created_at, outbox_event};
+
+// Write the events to the outbox, they must be published in the transaction of the change
+// so they are only relayed once it is committed.
+pub fn publish_events(
+    events: &[DomainEvent],
+    connection: &PgConnection,
+) -> Result<(), Error> {
+    if events.is_empty() {
+        return Ok(());
+    }
+    let mut new_outbox_events = Vec::with_capacity(events.len());
+    for event in events {
+        new_outbox_events.push(NewOutboxEvent {
+            id: Uuid::new_v4(),
+            team_id: event.team_id(),
+            payload: serde_json::to_string(event).map_err(|e| Error::InternalServerError(e.to_string()))?,
+        });
+    }
+    diesel::insert_into(outbox_event)
+        .values(&new_outbox_events)
+        .execute(connection)
+        .map(|_| ())
+        .map_err(Error::from)
+}
+
+pub fn publish_event(event: DomainEvent, connection: &PgConnection) -> Result<(), Error> {
+    publish_events(&[event], connection)
+}
+
+pub fn list_events_since(
+    since: NaiveDateTime,
+    connection: &PgConnection,
+) -> Result<Vec<OutboxEvent>, Error> {
+    outbox_event
+        .filter(created_at.ge(since))
+        .order(created_at.asc())
+        .load::<OutboxEvent>(connection)
+        .map_err(Error::from)
+}
+
+pub fn delete_events_before(
+    before: NaiveDateTime,
+    connection: &PgConnection,
+) -> Result<usize, Error> {
+    diesel::delete(outbox_event.filter(created_at.lt(before)))
+        .execute(connection)
+        .map_err(Error::from)
+}
+
+impl OutboxEvent {
+    pub fn event(&self) -> Result<DomainEvent, Error> {
+        serde_json::from_str(&self.payload).map_err(|e| Error::InternalServerError(e.to_string()))
+    }
+}
+
+// The time of the latest event, the relay starts after it so it doesn't replay the history.
+pub fn latest_event_time(connection: &PgConnection) -> Result<Option<NaiveDateTime>, Error> {
+    outbox_event
+        .select(max(created_at))
+        .first::<Option<NaiveDateTime>>(connection)
+        .map_err(Error::from)
+}
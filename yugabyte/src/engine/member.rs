@@ -1,12 +1,29 @@
-use diesel::{ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
+use std::collections::HashSet;
+
+use chrono::NaiveDateTime;
+use diesel::{ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, RunQueryDsl};
 use uuid::Uuid;
 
 use error::error::Error;
 
-use crate::model::dto::PaginationDTO;
-use crate::model::member::{Member, Name, NewMember};
-use crate::schema::member::dsl::{expired_at, identity_num, member, modification_date, name, role};
+use crate::db_connection::with_transaction;
+use crate::engine::event::{publish_event, publish_events};
+use crate::engine::version::{check_version, lost_race};
+use crate::model::page::{Page, PageRequest};
+use crate::model::event::DomainEvent;
+use crate::model::member::{Member, MemberPatch, MemberRole, Name, NewMember};
+use crate::model::patch::Patch;
+use crate::schema::member::dsl::{deleted_at, expired_at, identity_num, member, modification_date, name, role, version};
 use crate::schema::member::dsl::id as member_id;
+use crate::schema::member::dsl::team_id as member_team_id;
+use crate::schema::member::dsl::user_id as member_user_id;
+use crate::schema::team::dsl::deleted_at as team_deleted_at;
+use crate::schema::team::dsl::id as team_id;
+use crate::schema::team::dsl::team;
+use crate::schema::user::dsl::deleted_at as user_deleted_at;
+use crate::schema::user::dsl::auth_user_id as user_auth_user_id;
+use crate::schema::user::dsl::id as user_id;
+use crate::schema::user::dsl::user;
 use crate::util::utils::current_timestamp;
 
 impl NewMember {
@@ -17,15 +34,22 @@ impl NewMember {
             user_id: self.user_id,
             name: self.name.clone(),
             identity_num: self.identity_num.clone(),
-            role: self.role.clone(),
+            role: self.role,
             assigned_at: current_timestamp(),
             expired_at: self.expired_at,
             modification_date: None,
+            version: 1,
+            deleted_at: None,
         };
-        diesel::insert_into(member)
-            .values(initialized_member)
-            .get_result(connection)
-            .map_err(|_| Error::DuplicationError)
+        with_transaction(connection, |connection| {
+            check_live_parents(&[&initialized_member], connection)?;
+            let inserted_member: Member = diesel::insert_into(member)
+                .values(&initialized_member)
+                .get_result(connection)
+                .map_err(Error::from)?;
+            publish_event(DomainEvent::MemberAdded(inserted_member.clone()), connection)?;
+            Ok(inserted_member)
+        })
     }
 }
 
@@ -33,45 +57,246 @@ pub fn insert_bulk_members(
     other_members: &Vec<Member>,
     connection: &PgConnection,
 ) -> Result<Vec<Member>, Error> {
-    diesel::insert_into(member)
-        .values(other_members)
-        .get_results::<Member>(connection)
-        .map_err(|_| Error::DuplicationError)
+    with_transaction(connection, |connection| {
+        check_live_parents(&other_members.iter().collect::<Vec<&Member>>(), connection)?;
+        let inserted_members = diesel::insert_into(member)
+            .values(other_members)
+            .get_results::<Member>(connection)
+            .map_err(Error::from)?;
+        let events: Vec<DomainEvent> = inserted_members.iter().cloned().map(DomainEvent::MemberAdded).collect();
+        publish_events(&events, connection)?;
+        Ok(inserted_members)
+    })
 }
 
+// The foreign keys still accept a deleted team or user, so the members are checked against the live ones.
+fn check_live_parents(other_members: &[&Member], connection: &PgConnection) -> Result<(), Error> {
+    let team_ids: HashSet<Uuid> = other_members.iter().map(|other_member| other_member.team_id).collect();
+    let live_teams: i64 = team
+        .filter(team_id.eq_any(team_ids.iter().collect::<Vec<&Uuid>>()))
+        .filter(team_deleted_at.is_null())
+        .count()
+        .get_result(connection)
+        .map_err(Error::from)?;
+    if live_teams as usize != team_ids.len() {
+        return Err(Error::ForeignKeyViolation("team".to_string()));
+    }
+    let user_ids: HashSet<Uuid> = other_members.iter().map(|other_member| other_member.user_id).collect();
+    let live_users: i64 = user
+        .filter(user_id.eq_any(user_ids.iter().collect::<Vec<&Uuid>>()))
+        .filter(user_deleted_at.is_null())
+        .count()
+        .get_result(connection)
+        .map_err(Error::from)?;
+    if live_users as usize != user_ids.len() {
+        return Err(Error::ForeignKeyViolation("user".to_string()));
+    }
+    Ok(())
+}
+
+// List the page of members in the order of their ids, the cursors are compared to the ids
+// so a page is not shifted by the rows added or removed before it.
 pub fn list_all_members(
-    pagination_dto: &PaginationDTO,
+    page_request: &PageRequest,
+    visible_team_ids: Option<&[Uuid]>,
     connection: &PgConnection,
-) -> Result<Vec<Member>, Error> {
-    member
-        .limit(pagination_dto.page_size as i64)
-        .offset(pagination_dto.offset as i64)
+) -> Result<Page<Member>, Error> {
+    let mut query = member.into_boxed();
+    if !page_request.include_deleted {
+        query = query.filter(deleted_at.is_null());
+    }
+    if let Some(visible_team_ids) = visible_team_ids {
+        query = query.filter(member_team_id.eq_any(visible_team_ids));
+    }
+    if let Some(after) = &page_request.after {
+        query = query.filter(member_id.gt(after.0));
+    }
+    if let Some(before) = &page_request.before {
+        query = query.filter(member_id.lt(before.0));
+    }
+    query = if page_request.backward {
+        query.order(member_id.desc())
+    } else {
+        query.order(member_id.asc())
+    };
+    let rows = query
+        .offset(page_request.offset)
+        .limit(page_request.size + 1)
         .load::<Member>(connection)
-        .map_err(|err| Error::DBError(err))
+        .map_err(Error::from)?;
+    Ok(Page::from_rows(rows, page_request))
 }
 
-pub fn count_members(connection: &PgConnection) -> Result<i64, Error> {
-    member
+pub fn count_members(
+    include_deleted: bool,
+    visible_team_ids: Option<&[Uuid]>,
+    connection: &PgConnection,
+) -> Result<i64, Error> {
+    let mut query = member.into_boxed();
+    if !include_deleted {
+        query = query.filter(deleted_at.is_null());
+    }
+    if let Some(visible_team_ids) = visible_team_ids {
+        query = query.filter(member_team_id.eq_any(visible_team_ids));
+    }
+    query
         .count()
         .get_result(connection)
-        .map_err(|e| Error::DBError(e))
+        .map_err(Error::from)
 }
 
-pub fn delete_member_by_id(other_member_id: &Uuid, connection: &PgConnection) -> bool {
-    match diesel::delete(member.filter(member_id.eq(other_member_id)))
-        .execute(connection)
-        .map_err(|e| Error::DBError(e)) {
-        Ok(0) => false,
-        Ok(1) => true,
-        _ => false,
-    }
+// Deleting a member only marks it as deleted, it can be restored while its team and user are not deleted.
+// It is false when there is no live member to delete.
+pub fn delete_member_by_id(
+    other_member_id: &Uuid,
+    expected_version: Option<i32>,
+    connection: &PgConnection,
+) -> Result<bool, Error> {
+    with_transaction(connection, |connection| {
+        let stored_member = match find_member_by_id(other_member_id, connection) {
+            Ok(stored_member) => stored_member,
+            Err(Error::NotFound(_)) => return Ok(false),
+            Err(err) => return Err(err),
+        };
+        check_version(expected_version, stored_member.version)?;
+        let deleted_member = diesel::update(
+            member
+                .find(other_member_id)
+                .filter(deleted_at.is_null())
+                .filter(version.eq(stored_member.version)),
+        )
+            .set((deleted_at.eq(current_timestamp()), version.eq(version + 1)))
+            .get_result::<Member>(connection)
+            .optional()
+            .map_err(Error::from)?
+            .ok_or_else(|| lost_race(stored_member.version, stored_member_version(other_member_id, connection)))?;
+        publish_event(DomainEvent::MemberRemoved(deleted_member), connection)?;
+        Ok(true)
+    })
 }
 
 // todo: // todo: need to return the list of deleted items to use it in the GraphQL like what I did in the auth_user engine
 pub fn delete_all_members(connection: &PgConnection) -> Result<usize, Error> {
-    diesel::delete(member)
-        .execute(connection)
-        .map_err(|err| Error::DBError(err))
+    with_transaction(connection, |connection| {
+        let deleted_members = diesel::update(member.filter(deleted_at.is_null()))
+            .set((deleted_at.eq(current_timestamp()), version.eq(version + 1)))
+            .get_results::<Member>(connection)
+            .map_err(Error::from)?;
+        publish_removed_members(&deleted_members, connection)?;
+        Ok(deleted_members.len())
+    })
+}
+
+// The members of the deleted teams get the deletion date of their team, each one is reported as removed.
+pub(crate) fn delete_members_by_team_ids(
+    other_team_ids: &[Uuid],
+    deletion_date: NaiveDateTime,
+    connection: &PgConnection,
+) -> Result<usize, Error> {
+    let deleted_members = diesel::update(member.filter(member_team_id.eq_any(other_team_ids)).filter(deleted_at.is_null()))
+        .set((deleted_at.eq(deletion_date), version.eq(version + 1)))
+        .get_results::<Member>(connection)
+        .map_err(Error::from)?;
+    publish_removed_members(&deleted_members, connection)?;
+    Ok(deleted_members.len())
+}
+
+// The members of the deleted users get the deletion date of their user, each one is reported as removed.
+pub(crate) fn delete_members_by_user_ids(
+    other_user_ids: &[Uuid],
+    deletion_date: NaiveDateTime,
+    connection: &PgConnection,
+) -> Result<usize, Error> {
+    let deleted_members = diesel::update(member.filter(member_user_id.eq_any(other_user_ids)).filter(deleted_at.is_null()))
+        .set((deleted_at.eq(deletion_date), version.eq(version + 1)))
+        .get_results::<Member>(connection)
+        .map_err(Error::from)?;
+    publish_removed_members(&deleted_members, connection)?;
+    Ok(deleted_members.len())
+}
+
+// Restore the members deleted with the team, those whose user is deleted too stay deleted. Each restored member is
+// reported as added.
+pub(crate) fn restore_members_by_team_id(
+    other_team_id: &Uuid,
+    deletion_date: NaiveDateTime,
+    connection: &PgConnection,
+) -> Result<usize, Error> {
+    let live_user_ids = user.filter(user_deleted_at.is_null()).select(user_id);
+    let restored_members = diesel::update(
+        member
+            .filter(member_team_id.eq(other_team_id))
+            .filter(deleted_at.eq(deletion_date))
+            .filter(member_user_id.eq_any(live_user_ids)),
+    )
+        .set((deleted_at.eq(None::<NaiveDateTime>), version.eq(version + 1)))
+        .get_results::<Member>(connection)
+        .map_err(Error::from)?;
+    publish_added_members(&restored_members, connection)?;
+    Ok(restored_members.len())
+}
+
+// Restore the members deleted with the user, those whose team is deleted too stay deleted. Each restored member is
+// reported as added.
+pub(crate) fn restore_members_by_user_id(
+    other_user_id: &Uuid,
+    deletion_date: NaiveDateTime,
+    connection: &PgConnection,
+) -> Result<usize, Error> {
+    let live_team_ids = team.filter(team_deleted_at.is_null()).select(team_id);
+    let restored_members = diesel::update(
+        member
+            .filter(member_user_id.eq(other_user_id))
+            .filter(deleted_at.eq(deletion_date))
+            .filter(member_team_id.eq_any(live_team_ids)),
+    )
+        .set((deleted_at.eq(None::<NaiveDateTime>), version.eq(version + 1)))
+        .get_results::<Member>(connection)
+        .map_err(Error::from)?;
+    publish_added_members(&restored_members, connection)?;
+    Ok(restored_members.len())
+}
+
+// Bring back the deleted member, its team and its user have to be restored first.
+// A member that is not deleted is returned as it is.
+pub fn restore_member(other_member_id: &Uuid, connection: &PgConnection) -> Result<Member, Error> {
+    with_transaction(connection, |connection| {
+        let stored_member = member
+            .find(other_member_id)
+            .get_result::<Member>(connection)
+            .map_err(Error::from)?;
+        if stored_member.deleted_at.is_none() {
+            return Ok(stored_member);
+        }
+        check_live_parents(&[&stored_member], connection)?;
+        let restored_member = diesel::update(member.find(other_member_id).filter(version.eq(stored_member.version)))
+            .set((deleted_at.eq(None::<NaiveDateTime>), version.eq(version + 1)))
+            .get_result::<Member>(connection)
+            .optional()
+            .map_err(Error::from)?
+            .ok_or_else(|| lost_race(stored_member.version, stored_member_version(other_member_id, connection)))?;
+        publish_event(DomainEvent::MemberAdded(restored_member.clone()), connection)?;
+        Ok(restored_member)
+    })
+}
+
+// The version of the member, deleted or not, for the requests that lost the race against another change.
+fn stored_member_version(other_member_id: &Uuid, connection: &PgConnection) -> Result<i32, Error> {
+    member
+        .find(other_member_id)
+        .select(version)
+        .get_result::<i32>(connection)
+        .map_err(Error::from)
+}
+
+fn publish_removed_members(deleted_members: &[Member], connection: &PgConnection) -> Result<(), Error> {
+    let events: Vec<DomainEvent> = deleted_members.iter().cloned().map(DomainEvent::MemberRemoved).collect();
+    publish_events(&events, connection)
+}
+
+fn publish_added_members(restored_members: &[Member], connection: &PgConnection) -> Result<(), Error> {
+    let events: Vec<DomainEvent> = restored_members.iter().cloned().map(DomainEvent::MemberAdded).collect();
+    publish_events(&events, connection)
 }
 
 pub fn find_member_by_id(
@@ -80,42 +305,293 @@ pub fn find_member_by_id(
 ) -> Result<Member, Error> {
     member
         .find(other_member_id)
+        .filter(deleted_at.is_null())
         .get_result::<Member>(connection)
-        .map_err(|err| Error::DBError(err))
+        .map_err(Error::from)
 }
 
+// Overwrite the member, when its stored version is still the version of the incoming member.
 pub fn update_member(
     incoming_member: &Member,
     connection: &PgConnection,
 ) -> Result<Member, Error> {
-    diesel::update(member.find(&incoming_member.id))
-        .set((
-            name.eq(&incoming_member.name),
-            identity_num.eq(&incoming_member.identity_num),
-            role.eq(&incoming_member.role),
-            modification_date.eq(current_timestamp()),
-            expired_at.eq(&incoming_member.expired_at),
-        ))
-        .get_result::<Member>(connection)
-        .map_err(|e| Error::DBError(e))
+    with_transaction(connection, |connection| {
+        let stored_member = find_member_by_id(&incoming_member.id, connection)?;
+        check_version(Some(incoming_member.version), stored_member.version)?;
+        let updated_member = diesel::update(member.find(&incoming_member.id).filter(version.eq(stored_member.version)))
+            .set((
+                name.eq(&incoming_member.name),
+                identity_num.eq(&incoming_member.identity_num),
+                role.eq(&incoming_member.role),
+                modification_date.eq(current_timestamp()),
+                expired_at.eq(&incoming_member.expired_at),
+                version.eq(version + 1),
+            ))
+            .get_result::<Member>(connection)
+            .optional()
+            .map_err(Error::from)?
+            .ok_or_else(|| lost_race(stored_member.version, stored_member_version(&incoming_member.id, connection)))?;
+        publish_event(DomainEvent::MemberUpdated(updated_member.clone()), connection)?;
+        Ok(updated_member)
+    })
+}
+
+// Apply a merge patch to the member, an empty patch leaves it, its modification date and its version as they are.
+pub fn patch_member(
+    other_member_id: &Uuid,
+    member_patch: &MemberPatch,
+    expected_version: Option<i32>,
+    connection: &PgConnection,
+) -> Result<Member, Error> {
+    let changeset = member_patch.clone().into_changeset(current_timestamp());
+    with_transaction(connection, |connection| {
+        let stored_member = find_member_by_id(other_member_id, connection)?;
+        check_version(expected_version, stored_member.version)?;
+        if member_patch.is_empty() {
+            return Ok(stored_member);
+        }
+        let updated_member = diesel::update(member.find(other_member_id).filter(version.eq(stored_member.version)))
+            .set((&changeset, version.eq(version + 1)))
+            .get_result::<Member>(connection)
+            .optional()
+            .map_err(Error::from)?
+            .ok_or_else(|| lost_race(stored_member.version, stored_member_version(other_member_id, connection)))?;
+        publish_event(DomainEvent::MemberUpdated(updated_member.clone()), connection)?;
+        Ok(updated_member)
+    })
 }
 
 pub fn filter_members_by_name(
     other_name: &String,
+    visible_team_ids: Option<&[Uuid]>,
     connection: &PgConnection,
 ) -> Result<Vec<Member>, Error> {
-    member
+    let mut query = member
         .filter(name.eq(other_name))
+        .filter(deleted_at.is_null())
+        .into_boxed();
+    if let Some(visible_team_ids) = visible_team_ids {
+        query = query.filter(member_team_id.eq_any(visible_team_ids));
+    }
+    query
         .get_results::<Member>(connection)
-        .map_err(|e| Error::DBError(e))
+        .map_err(Error::from)
+}
+
+pub fn find_members_by_team_id(
+    other_team_id: &Uuid,
+    connection: &PgConnection,
+) -> Result<Vec<Member>, Error> {
+    member
+        .filter(member_team_id.eq(other_team_id))
+        .filter(deleted_at.is_null())
+        .load::<Member>(connection)
+        .map_err(Error::from)
+}
+
+// Load the members of all the given teams in one `team_id = ANY($1)` query.
+pub fn find_members_by_team_ids(
+    other_team_ids: &[Uuid],
+    connection: &PgConnection,
+) -> Result<Vec<Member>, Error> {
+    member
+        .filter(member_team_id.eq_any(other_team_ids))
+        .filter(deleted_at.is_null())
+        .load::<Member>(connection)
+        .map_err(Error::from)
+}
+
+// Load the members of all the given users in one `user_id = ANY($1)` query.
+pub fn find_members_by_user_ids(
+    other_user_ids: &[Uuid],
+    connection: &PgConnection,
+) -> Result<Vec<Member>, Error> {
+    member
+        .filter(member_user_id.eq_any(other_user_ids))
+        .filter(deleted_at.is_null())
+        .load::<Member>(connection)
+        .map_err(Error::from)
+}
+
+pub fn find_members_by_user_id(
+    other_user_id: &Uuid,
+    connection: &PgConnection,
+) -> Result<Vec<Member>, Error> {
+    member
+        .filter(member_user_id.eq(other_user_id))
+        .filter(deleted_at.is_null())
+        .load::<Member>(connection)
+        .map_err(Error::from)
 }
 
 pub fn get_all_member_names_by_team_id(
     other_team_id: &Uuid,
     connection: &PgConnection,
 ) -> Result<Vec<Name>, Error> {
-    let query = format!("SELECT name FROM member WHERE team_id::text = '{}'", other_team_id);
-    diesel::sql_query(query)
-        .load::<Name>(connection)
-        .map_err(|e| Error::DBError(e))
-}
\ No newline at end of file
+    let names = member
+        .filter(member_team_id.eq(other_team_id))
+        .filter(deleted_at.is_null())
+        .select(name)
+        .load::<String>(connection)?;
+    Ok(names.into_iter().map(|member_name| Name { name: member_name }).collect())
+}
+
+// The roles in the team of the user linked to the auth_user.
+pub fn find_member_roles(
+    other_auth_user_id: &Uuid,
+    other_team_id: &Uuid,
+    connection: &PgConnection,
+) -> Result<Vec<MemberRole>, Error> {
+    member
+        .inner_join(user)
+        .filter(user_auth_user_id.eq(other_auth_user_id))
+        .filter(member_team_id.eq(other_team_id))
+        .filter(deleted_at.is_null())
+        .select(role)
+        .load::<MemberRole>(connection)
+        .map_err(Error::from)
+}
+
+// The teams where the user linked to the auth_user is a member.
+pub fn find_team_ids_of_auth_user(
+    other_auth_user_id: &Uuid,
+    connection: &PgConnection,
+) -> Result<Vec<Uuid>, Error> {
+    member
+        .inner_join(user)
+        .filter(user_auth_user_id.eq(other_auth_user_id))
+        .filter(deleted_at.is_null())
+        .select(member_team_id)
+        .distinct()
+        .load::<Uuid>(connection)
+        .map_err(Error::from)
+}
+
+// The roles of the user linked to the auth_user in every team it is a member of.
+pub fn find_team_roles_of_auth_user(
+    other_auth_user_id: &Uuid,
+    connection: &PgConnection,
+) -> Result<Vec<(Uuid, MemberRole)>, Error> {
+    member
+        .inner_join(user)
+        .filter(user_auth_user_id.eq(other_auth_user_id))
+        .filter(deleted_at.is_null())
+        .select((member_team_id, role))
+        .load::<(Uuid, MemberRole)>(connection)
+        .map_err(Error::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::Connection;
+
+    use crate::auth::principal::Principal;
+    use crate::config::config;
+    use crate::db_connection::{get_connection, init_pool};
+    use crate::engine::team::{delete_team_by_id, restore_team};
+    use crate::engine::user::{delete_user_by_id, restore_user};
+    use crate::model::event::OutboxEvent;
+    use crate::model::team::NewTeam;
+    use crate::model::user::NewUser;
+    use crate::schema::outbox_event::dsl::outbox_event;
+    use crate::schema::outbox_event::dsl::team_id as event_team_id;
+
+    use super::*;
+
+    // A member of a new team and a new user, the user isn't linked to an auth_user.
+    fn insert_member(connection: &PgConnection) -> Result<Member, Error> {
+        let new_team = NewTeam { name: "Soft team".to_string(), description: "Soft deleted".to_string() };
+        let inserted_team = new_team.insert_team(connection)?;
+        let principal = Principal { auth_user_id: Uuid::new_v4(), email: "admin@example.com".to_string(), is_admin: false };
+        let new_user = NewUser { email: format!("{}@example.com", Uuid::new_v4()), name: "Soft user".to_string() };
+        let inserted_user = new_user.add_user(&principal, connection)?;
+        let new_member = NewMember {
+            team_id: inserted_team.id,
+            user_id: inserted_user.id,
+            name: "Soft member".to_string(),
+            identity_num: "42".to_string(),
+            ..NewMember::default()
+        };
+        new_member.insert_member(connection)
+    }
+
+    fn team_events(other_team_id: &Uuid, connection: &PgConnection) -> Result<Vec<DomainEvent>, Error> {
+        outbox_event
+            .filter(event_team_id.eq(other_team_id))
+            .load::<OutboxEvent>(connection)
+            .map_err(Error::from)?
+            .iter()
+            .map(OutboxEvent::event)
+            .collect()
+    }
+
+    #[test]
+    fn deleting_a_team_soft_deletes_and_reports_its_members() {
+        let pool = init_pool(&config().database).unwrap();
+        let connection = get_connection(&pool).unwrap();
+        connection.test_transaction::<_, Error, _>(|| {
+            let inserted_member = insert_member(&connection)?;
+            let team_ids = [inserted_member.team_id];
+            assert!(delete_team_by_id(&inserted_member.team_id, None, &connection)?);
+
+            assert!(matches!(find_member_by_id(&inserted_member.id, &connection), Err(Error::NotFound(_))));
+            assert_eq!(count_members(false, Some(&team_ids), &connection)?, 0);
+            assert_eq!(count_members(true, Some(&team_ids), &connection)?, 1);
+
+            let mut page_request = PageRequest::from_connection_args(None, None, None, None)?;
+            assert!(list_all_members(&page_request, Some(&team_ids), &connection)?.items.is_empty());
+            page_request.include_deleted = true;
+            let listed_members = list_all_members(&page_request, Some(&team_ids), &connection)?.items;
+            assert_eq!(listed_members.len(), 1);
+            assert!(listed_members[0].deleted_at.is_some());
+
+            let removed = team_events(&inserted_member.team_id, &connection)?.into_iter().any(|event| {
+                matches!(event, DomainEvent::MemberRemoved(removed_member) if removed_member.id == inserted_member.id)
+            });
+            assert!(removed);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn restoring_a_team_restores_and_reports_the_members_of_live_users() {
+        let pool = init_pool(&config().database).unwrap();
+        let connection = get_connection(&pool).unwrap();
+        connection.test_transaction::<_, Error, _>(|| {
+            let inserted_member = insert_member(&connection)?;
+            assert!(delete_team_by_id(&inserted_member.team_id, None, &connection)?);
+            assert!(delete_user_by_id(&inserted_member.user_id, None, &connection)?);
+
+            // The member was deleted with its team, restoring the user leaves it deleted.
+            restore_user(&inserted_member.user_id, &connection)?;
+            assert!(find_member_by_id(&inserted_member.id, &connection).is_err());
+
+            restore_team(&inserted_member.team_id, &connection)?;
+            let restored_member = find_member_by_id(&inserted_member.id, &connection)?;
+            assert!(restored_member.deleted_at.is_none());
+            assert!(restored_member.version > inserted_member.version);
+
+            let added = team_events(&inserted_member.team_id, &connection)?.into_iter().any(|event| {
+                matches!(event, DomainEvent::MemberAdded(added_member) if added_member.id == inserted_member.id)
+            });
+            assert!(added);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn a_deleted_member_is_only_restored_with_live_parents() {
+        let pool = init_pool(&config().database).unwrap();
+        let connection = get_connection(&pool).unwrap();
+        connection.test_transaction::<_, Error, _>(|| {
+            let inserted_member = insert_member(&connection)?;
+            assert!(delete_member_by_id(&inserted_member.id, Some(inserted_member.version), &connection)?);
+            assert!(!delete_member_by_id(&inserted_member.id, None, &connection)?);
+            assert!(restore_member(&inserted_member.id, &connection)?.deleted_at.is_none());
+
+            assert!(delete_team_by_id(&inserted_member.team_id, None, &connection)?);
+            assert!(matches!(restore_member(&inserted_member.id, &connection), Err(Error::ForeignKeyViolation(_))));
+            Ok(())
+        });
+    }
+}
@@ -1,4 +1,8 @@
 pub mod auth_user;
+pub mod event;
 pub mod member;
+pub mod refresh_token;
+pub mod session;
 pub mod team;
 pub mod user;
+pub mod version;
@@ -0,0 +1,66 @@
+use chrono::Duration;
+use diesel::{ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
+use uuid::Uuid;
+
+use error::error::Error;
+
+use crate::model::refresh_token::RefreshToken;
+use crate::schema::refresh_token::dsl::{auth_user_id, refresh_token, revoked_at};
+use crate::util::utils::current_timestamp;
+
+pub fn insert_refresh_token(
+    other_auth_user_id: &Uuid,
+    time_to_live: i64,
+    connection: &PgConnection,
+) -> Result<RefreshToken, Error> {
+    let now = current_timestamp();
+    let initialized_refresh_token = RefreshToken {
+        id: Uuid::new_v4(),
+        auth_user_id: *other_auth_user_id,
+        created_at: now,
+        expires_at: now + Duration::seconds(time_to_live),
+        revoked_at: None,
+    };
+    diesel::insert_into(refresh_token)
+        .values(&initialized_refresh_token)
+        .get_result(connection)
+        .map_err(Error::from)
+}
+
+pub fn find_refresh_token_by_id(
+    other_refresh_token_id: &Uuid,
+    connection: &PgConnection,
+) -> Result<RefreshToken, Error> {
+    refresh_token
+        .find(other_refresh_token_id)
+        .get_result::<RefreshToken>(connection)
+        .map_err(Error::from)
+}
+
+pub fn revoke_refresh_token(
+    other_refresh_token_id: &Uuid,
+    connection: &PgConnection,
+) -> Result<usize, Error> {
+    diesel::update(
+        refresh_token
+            .find(other_refresh_token_id)
+            .filter(revoked_at.is_null())
+    )
+        .set(revoked_at.eq(current_timestamp()))
+        .execute(connection)
+        .map_err(Error::from)
+}
+
+pub fn revoke_all_refresh_tokens_of_auth_user(
+    other_auth_user_id: &Uuid,
+    connection: &PgConnection,
+) -> Result<usize, Error> {
+    diesel::update(
+        refresh_token
+            .filter(auth_user_id.eq(other_auth_user_id))
+            .filter(revoked_at.is_null())
+    )
+        .set(revoked_at.eq(current_timestamp()))
+        .execute(connection)
+        .map_err(Error::from)
+}
@@ -0,0 +1,71 @@
+use diesel::PgConnection;
+use uuid::Uuid;
+
+use error::error::Error;
+
+use crate::auth::principal::Principal;
+use crate::auth::token::{TOKEN_SETTINGS, TokenType};
+use crate::db_connection::with_transaction;
+use crate::engine::auth_user::{find_auth_user_by_id, verify_auth_user_credentials};
+use crate::engine::refresh_token::{find_refresh_token_by_id, insert_refresh_token, revoke_all_refresh_tokens_of_auth_user, revoke_refresh_token};
+use crate::model::auth_user::AuthUser;
+use crate::model::dto::{LoginDTO, TokenPair};
+use crate::util::utils::current_timestamp;
+
+// Verify the credentials and open a new session for the auth_user.
+pub fn login(login_dto: &LoginDTO, connection: &PgConnection) -> Result<TokenPair, Error> {
+    match verify_auth_user_credentials(&login_dto.email, &login_dto.password, connection)? {
+        Some(found_auth_user) => issue_token_pair(&found_auth_user, connection),
+        None => Err(Error::Unauthorized("Invalid email or password".to_string())),
+    }
+}
+
+// Exchange a refresh token for a new token pair. The used refresh token is revoked,
+// and presenting an already revoked token revokes the whole family of the auth_user,
+// because it means that the token has been stolen and replayed.
+pub fn refresh_session(other_refresh_token: &str, connection: &PgConnection) -> Result<TokenPair, Error> {
+    let claims = TOKEN_SETTINGS.verify(other_refresh_token, TokenType::Refresh)?;
+    let stored_refresh_token = find_refresh_token_by_id(&claims.jti, connection)
+        .map_err(|_| Error::Unauthorized("Unknown refresh token".to_string()))?;
+
+    if stored_refresh_token.revoked_at.is_some() {
+        revoke_all_refresh_tokens_of_auth_user(&stored_refresh_token.auth_user_id, connection)?;
+        return Err(Error::Unauthorized("The refresh token has been revoked".to_string()));
+    }
+    if stored_refresh_token.expires_at < current_timestamp() {
+        return Err(Error::Unauthorized("The refresh token has expired".to_string()));
+    }
+
+    with_transaction(connection, |connection| {
+        // Nothing is revoked when a concurrent request has already rotated the same token.
+        if revoke_refresh_token(&stored_refresh_token.id, connection)? == 0 {
+            return Err(Error::Unauthorized("The refresh token has been revoked".to_string()));
+        }
+        let found_auth_user = find_auth_user_by_id(&stored_refresh_token.auth_user_id, connection)?;
+        issue_token_pair(&found_auth_user, connection)
+    })
+}
+
+// Revoke the refresh token, the access token stays valid until it expires.
+pub fn logout(other_refresh_token: &str, connection: &PgConnection) -> Result<bool, Error> {
+    let claims = TOKEN_SETTINGS.verify_ignoring_expiration(other_refresh_token, TokenType::Refresh)?;
+
+    revoke_refresh_token(&claims.jti, connection).map(|revoked_count| revoked_count == 1)
+}
+
+fn issue_token_pair(other_auth_user: &AuthUser, connection: &PgConnection) -> Result<TokenPair, Error> {
+    let stored_refresh_token = insert_refresh_token(
+        &other_auth_user.id,
+        TOKEN_SETTINGS.ttl(TokenType::Refresh),
+        connection,
+    )?;
+
+    let principal = Principal::from(other_auth_user);
+
+    Ok(TokenPair {
+        access_token: TOKEN_SETTINGS.issue(&principal, TokenType::Access, Uuid::new_v4())?,
+        refresh_token: TOKEN_SETTINGS.issue(&principal, TokenType::Refresh, stored_refresh_token.id)?,
+        token_type: "Bearer".to_string(),
+        expires_in: TOKEN_SETTINGS.ttl(TokenType::Access) as i32,
+    })
+}
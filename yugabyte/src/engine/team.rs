@@ -1,24 +1,40 @@
-use diesel::{ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
+use chrono::NaiveDateTime;
+use diesel::{ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, RunQueryDsl};
 use uuid::Uuid;
 
 use error::error::Error;
 
-use crate::model::dto::PaginationDTO;
-use crate::model::team::{NewTeam, Team};
-use crate::schema::team::dsl::{description, name, team};
+use crate::db_connection::with_transaction;
+use crate::engine::event::{publish_event, publish_events};
+use crate::engine::member::{delete_members_by_team_ids, restore_members_by_team_id};
+use crate::engine::version::{check_version, lost_race};
+use crate::model::page::{Page, PageRequest};
+use crate::model::event::{DomainEvent, TeamChangeKind};
+use crate::model::patch::Patch;
+use crate::model::team::{NewTeam, Team, TeamPatch};
+use crate::schema::team::dsl::deleted_at as team_deleted_at;
+use crate::schema::team::dsl::team;
 use crate::schema::team::dsl::id as team_id;
+use crate::schema::team::dsl::version as team_version;
+use crate::util::utils::current_timestamp;
 
 impl NewTeam {
     pub fn insert_team(&self, connection: &PgConnection) -> Result<Team, Error> {
         let initialized_member = Team {
             id: Uuid::new_v4(),
             name: self.name.clone(), // I cloned the name only not the whole team object because the string is located in the heap memory.
-            description: self.description.clone() // I cloned the description only not the whole team object because the string is located in the heap memory.
+            description: self.description.clone(), // I cloned the description only not the whole team object because the string is located in the heap memory.
+            version: 1,
+            deleted_at: None,
         };
-        diesel::insert_into(team)
-            .values(initialized_member)
-            .get_result(connection)
-            .map_err(|_| Error::DuplicationError)
+        with_transaction(connection, |connection| {
+            let inserted_team: Team = diesel::insert_into(team)
+                .values(&initialized_member)
+                .get_result(connection)
+                .map_err(Error::from)?;
+            publish_event(DomainEvent::team_changed(TeamChangeKind::Created, &inserted_team), connection)?;
+            Ok(inserted_team)
+        })
     }
 }
 
@@ -26,44 +42,140 @@ pub fn insert_bulk_team(
     other_teams: &Vec<Team>,
     connection: &PgConnection,
 ) -> Result<Vec<Team>, Error> {
-    diesel::insert_into(team)
-        .values(other_teams)
-        .get_results::<Team>(connection)
-        .map_err(|_| Error::DuplicationError)
+    with_transaction(connection, |connection| {
+        let inserted_teams = diesel::insert_into(team)
+            .values(other_teams)
+            .get_results::<Team>(connection)
+            .map_err(Error::from)?;
+        publish_team_changes(TeamChangeKind::Created, &inserted_teams, connection)?;
+        Ok(inserted_teams)
+    })
 }
 
+// List the page of teams in the order of their ids, the cursors are compared to the ids
+// so a page is not shifted by the rows added or removed before it.
 pub fn list_all_teams(
-    pagination_dto: &PaginationDTO,
+    page_request: &PageRequest,
     connection: &PgConnection,
-) -> Result<Vec<Team>, Error> {
-    team
-        .limit(pagination_dto.page_size as i64)
-        .offset(pagination_dto.offset as i64)
+) -> Result<Page<Team>, Error> {
+    let mut query = team.into_boxed();
+    if !page_request.include_deleted {
+        query = query.filter(team_deleted_at.is_null());
+    }
+    if let Some(after) = &page_request.after {
+        query = query.filter(team_id.gt(after.0));
+    }
+    if let Some(before) = &page_request.before {
+        query = query.filter(team_id.lt(before.0));
+    }
+    query = if page_request.backward {
+        query.order(team_id.desc())
+    } else {
+        query.order(team_id.asc())
+    };
+    let rows = query
+        .offset(page_request.offset)
+        .limit(page_request.size + 1)
         .load::<Team>(connection)
-        .map_err(|err| Error::DBError(err))
+        .map_err(Error::from)?;
+    Ok(Page::from_rows(rows, page_request))
 }
 
-pub fn count_teams(connection: &PgConnection) -> Result<i64, Error> {
-    team
+pub fn count_teams(include_deleted: bool, connection: &PgConnection) -> Result<i64, Error> {
+    let mut query = team.into_boxed();
+    if !include_deleted {
+        query = query.filter(team_deleted_at.is_null());
+    }
+    query
         .count()
         .get_result(connection)
-        .map_err(|e| Error::DBError(e))
+        .map_err(Error::from)
 }
 
-pub fn delete_team_by_id(other_team_id: &Uuid, connection: &PgConnection) -> bool {
-    match diesel::delete(team.filter(team_id.eq(other_team_id)))
-        .execute(connection)
-        .map_err(|e| Error::DBError(e)) {
-        Ok(0) => false,
-        Ok(1) => true,
-        _ => false,
-    }
+// Deleting a team only marks it and its members as deleted, they keep the same timestamp
+// so restoring the team brings back the members deleted with it. It is false when there is no live team to delete.
+pub fn delete_team_by_id(
+    other_team_id: &Uuid,
+    expected_version: Option<i32>,
+    connection: &PgConnection,
+) -> Result<bool, Error> {
+    with_transaction(connection, |connection| {
+        let stored_team = match find_team_by_id(other_team_id, connection) {
+            Ok(stored_team) => stored_team,
+            Err(Error::NotFound(_)) => return Ok(false),
+            Err(err) => return Err(err),
+        };
+        check_version(expected_version, stored_team.version)?;
+        let deleted_teams = soft_delete_teams(Some((other_team_id, stored_team.version)), connection)?;
+        if deleted_teams.is_empty() {
+            return Err(lost_race(stored_team.version, stored_team_version(other_team_id, connection)));
+        }
+        publish_team_changes(TeamChangeKind::Deleted, &deleted_teams, connection)?;
+        Ok(true)
+    })
 }
 
 pub fn delete_all_teams(connection: &PgConnection) -> Result<usize, Error> {
-    diesel::delete(team)
-        .execute(connection)
-        .map_err(|err| Error::DBError(err))
+    with_transaction(connection, |connection| {
+        let deleted_teams = soft_delete_teams(None, connection)?;
+        publish_team_changes(TeamChangeKind::Deleted, &deleted_teams, connection)?;
+        Ok(deleted_teams.len())
+    })
+}
+
+// Mark the live teams, all of them or only the one with the given id and version, as deleted.
+fn soft_delete_teams(only_team: Option<(&Uuid, i32)>, connection: &PgConnection) -> Result<Vec<Team>, Error> {
+    let deletion_date = current_timestamp();
+    let mut query = diesel::update(team.filter(team_deleted_at.is_null()))
+        .set((team_deleted_at.eq(deletion_date), team_version.eq(team_version + 1)))
+        .into_boxed();
+    if let Some((only_team_id, read_version)) = only_team {
+        query = query.filter(team_id.eq(only_team_id)).filter(team_version.eq(read_version));
+    }
+    let deleted_teams = query
+        .get_results::<Team>(connection)
+        .map_err(Error::from)?;
+    let deleted_team_ids: Vec<Uuid> = deleted_teams.iter().map(|deleted_team| deleted_team.id).collect();
+    delete_members_by_team_ids(&deleted_team_ids, deletion_date, connection)?;
+    Ok(deleted_teams)
+}
+
+// Bring back the deleted team with the members deleted along with it, a member whose user is
+// still deleted stays deleted. A team that is not deleted is returned as it is.
+pub fn restore_team(other_team_id: &Uuid, connection: &PgConnection) -> Result<Team, Error> {
+    with_transaction(connection, |connection| {
+        let stored_team = team
+            .find(other_team_id)
+            .get_result::<Team>(connection)
+            .map_err(Error::from)?;
+        let deletion_date = match stored_team.deleted_at {
+            Some(deletion_date) => deletion_date,
+            None => return Ok(stored_team),
+        };
+        let restored_team = diesel::update(team.find(other_team_id).filter(team_version.eq(stored_team.version)))
+            .set((team_deleted_at.eq(None::<NaiveDateTime>), team_version.eq(team_version + 1)))
+            .get_result::<Team>(connection)
+            .optional()
+            .map_err(Error::from)?
+            .ok_or_else(|| lost_race(stored_team.version, stored_team_version(other_team_id, connection)))?;
+        restore_members_by_team_id(other_team_id, deletion_date, connection)?;
+        publish_event(DomainEvent::team_changed(TeamChangeKind::Restored, &restored_team), connection)?;
+        Ok(restored_team)
+    })
+}
+
+// The version of the team, deleted or not, for the requests that lost the race against another change.
+fn stored_team_version(other_team_id: &Uuid, connection: &PgConnection) -> Result<i32, Error> {
+    team
+        .find(other_team_id)
+        .select(team_version)
+        .get_result::<i32>(connection)
+        .map_err(Error::from)
+}
+
+fn publish_team_changes(kind: TeamChangeKind, teams: &[Team], connection: &PgConnection) -> Result<(), Error> {
+    let events: Vec<DomainEvent> = teams.iter().map(|changed_team| DomainEvent::team_changed(kind, changed_team)).collect();
+    publish_events(&events, connection)
 }
 
 pub fn find_team_by_id(
@@ -72,20 +184,71 @@ pub fn find_team_by_id(
 ) -> Result<Team, Error> {
     team
         .find(other_team_id)
+        .filter(team_deleted_at.is_null())
         .get_result::<Team>(connection)
-        .map_err(|err| Error::DBError(err))
+        .map_err(Error::from)
 }
 
 
-pub fn update_auth_user(
-    incoming_team: &Team,
+// Load all the teams with the given ids in one `id = ANY($1)` query.
+pub fn find_teams_by_ids(
+    other_team_ids: &[Uuid],
+    connection: &PgConnection,
+) -> Result<Vec<Team>, Error> {
+    team
+        .filter(team_id.eq_any(other_team_ids))
+        .filter(team_deleted_at.is_null())
+        .load::<Team>(connection)
+        .map_err(Error::from)
+}
+
+// Change the fields set in the patch, the others keep their stored values.
+pub fn update_team(
+    other_team_id: &Uuid,
+    team_patch: &TeamPatch,
+    expected_version: Option<i32>,
     connection: &PgConnection,
 ) -> Result<Team, Error> {
-    diesel::update(team.find(&incoming_team.id))
-        .set((
-            name.eq(&incoming_team.name),
-            description.eq(&incoming_team.description),
-        ))
-        .get_result::<Team>(connection)
-        .map_err(|e| Error::DBError(e))
+    with_transaction(connection, |connection| {
+        let stored_team = find_team_by_id(other_team_id, connection)?;
+        check_version(expected_version, stored_team.version)?;
+        // An empty patch changes nothing, not even the version.
+        if team_patch.is_empty() {
+            return Ok(stored_team);
+        }
+        let updated_team = diesel::update(team.find(other_team_id).filter(team_version.eq(stored_team.version)))
+            .set((team_patch, team_version.eq(team_version + 1)))
+            .get_result::<Team>(connection)
+            .optional()
+            .map_err(Error::from)?
+            .ok_or_else(|| lost_race(stored_team.version, stored_team_version(other_team_id, connection)))?;
+        publish_event(DomainEvent::team_changed(TeamChangeKind::Updated, &updated_team), connection)?;
+        Ok(updated_team)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::Connection;
+
+    use crate::config::config;
+    use crate::db_connection::{get_connection, init_pool};
+
+    use super::*;
+
+    #[test]
+    fn delete_team_by_id_checks_the_version() {
+        let pool = init_pool(&config().database).unwrap();
+        let connection = get_connection(&pool).unwrap();
+        connection.test_transaction::<_, Error, _>(|| {
+            let new_team = NewTeam { name: "Versioned team".to_string(), description: "Deleted twice".to_string() };
+            let inserted_team = new_team.insert_team(&connection)?;
+
+            let mismatch = delete_team_by_id(&inserted_team.id, Some(inserted_team.version + 1), &connection);
+            assert!(matches!(mismatch, Err(Error::VersionMismatch(_, _))));
+            assert!(delete_team_by_id(&inserted_team.id, Some(inserted_team.version), &connection)?);
+            assert!(!delete_team_by_id(&inserted_team.id, None, &connection)?);
+            Ok(())
+        });
+    }
 }
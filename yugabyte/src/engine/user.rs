@@ -1,23 +1,46 @@
-use diesel::{PgConnection, QueryDsl, RunQueryDsl};
+use chrono::NaiveDateTime;
+use diesel::{ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, RunQueryDsl};
 use uuid::Uuid;
 
 use error::error::Error;
 
-use crate::model::dto::PaginationDTO;
-use crate::model::user::{NewUser, User};
+use crate::auth::principal::Principal;
+use crate::db_connection::with_transaction;
+use crate::engine::member::{delete_members_by_user_ids, restore_members_by_user_id};
+use crate::engine::version::{check_version, lost_race};
+use crate::model::page::{Page, PageRequest};
+use crate::model::patch::Patch;
+use crate::model::user::{NewUser, User, UserPatch};
+use crate::schema::auth_user::dsl::auth_user;
+use crate::schema::auth_user::dsl::email as auth_user_email;
+use crate::schema::auth_user::dsl::id as auth_user_id;
+use crate::schema::user::dsl::deleted_at as user_deleted_at;
+use crate::schema::user::dsl::email as user_email;
 use crate::schema::user::dsl::user;
+use crate::schema::user::dsl::id as user_id;
+use crate::schema::user::dsl::version as user_version;
+use crate::util::utils::current_timestamp;
 
 impl NewUser {
-    pub fn add_user(&self, connection: &PgConnection) -> Result<User, Error> {
-        let initialized_user = User {
+    // Build the user row, linked to the auth_user with the same email when the principal vouches for it.
+    pub fn to_user(&self, principal: &Principal, connection: &PgConnection) -> Result<User, Error> {
+        Ok(User {
             id: Uuid::new_v4(),
             email: self.email.clone(),
             name: self.name.clone(),
-        };
+            version: 1,
+            deleted_at: None,
+            auth_user_id: linked_auth_user_id(&self.email, principal, connection)?,
+        })
+    }
+
+    pub fn add_user(&self, principal: &Principal, connection: &PgConnection) -> Result<User, Error> {
+        let initialized_user = self.to_user(principal, connection)?;
+        check_deleted_duplication(&[&self.email], connection)?;
         diesel::insert_into(user)
             .values(&initialized_user)
             .get_result(connection)
-            .map_err(|_| Error::DuplicationError)
+            .map_err(Error::from)
     }
 }
 
@@ -25,28 +48,82 @@ pub fn insert_bulk_users(
     other_users: &Vec<User>,
     connection: &PgConnection,
 ) -> Result<Vec<User>, Error> {
+    let emails: Vec<&str> = other_users.iter().map(|other_user| other_user.email.as_str()).collect();
+    check_deleted_duplication(&emails, connection)?;
     diesel::insert_into(user)
         .values(other_users)
         .get_results::<User>(connection)
-        .map_err(|_| Error::DuplicationError)
+        .map_err(Error::from)
+}
+
+// Anyone can sign up with any email, so the email alone doesn't link a user to an auth_user. The link is
+// only made when the principal is that auth_user or a global administrator.
+fn linked_auth_user_id(
+    other_email: &str,
+    principal: &Principal,
+    connection: &PgConnection,
+) -> Result<Option<Uuid>, Error> {
+    let found_auth_user_id = auth_user
+        .filter(auth_user_email.eq(other_email))
+        .select(auth_user_id)
+        .first::<Uuid>(connection)
+        .optional()
+        .map_err(Error::from)?;
+    Ok(found_auth_user_id.filter(|found_auth_user_id| principal.is_admin || *found_auth_user_id == principal.auth_user_id))
+}
+
+// A deleted user still holds its email, so a new user with the same email is told to restore it.
+fn check_deleted_duplication(emails: &[&str], connection: &PgConnection) -> Result<(), Error> {
+    let deleted_users: i64 = user
+        .filter(user_email.eq_any(emails))
+        .filter(user_deleted_at.is_not_null())
+        .count()
+        .get_result(connection)
+        .map_err(Error::from)?;
+    if deleted_users > 0 {
+        return Err(Error::DeletedDuplicationError);
+    }
+    Ok(())
 }
 
+// List the page of users in the order of their ids, the cursors are compared to the ids
+// so a page is not shifted by the rows added or removed before it.
 pub fn list_all_users(
-    pagination_dto: &PaginationDTO,
+    page_request: &PageRequest,
     connection: &PgConnection,
-) -> Result<Vec<User>, Error> {
-    user
-        .limit(pagination_dto.page_size as i64)
-        .offset(pagination_dto.offset as i64)
+) -> Result<Page<User>, Error> {
+    let mut query = user.into_boxed();
+    if !page_request.include_deleted {
+        query = query.filter(user_deleted_at.is_null());
+    }
+    if let Some(after) = &page_request.after {
+        query = query.filter(user_id.gt(after.0));
+    }
+    if let Some(before) = &page_request.before {
+        query = query.filter(user_id.lt(before.0));
+    }
+    query = if page_request.backward {
+        query.order(user_id.desc())
+    } else {
+        query.order(user_id.asc())
+    };
+    let rows = query
+        .offset(page_request.offset)
+        .limit(page_request.size + 1)
         .load::<User>(connection)
-        .map_err(|err| Error::DBError(err))
+        .map_err(Error::from)?;
+    Ok(Page::from_rows(rows, page_request))
 }
 
-pub fn count_users(connection: &PgConnection) -> Result<i64, Error> {
-    user
+pub fn count_users(include_deleted: bool, connection: &PgConnection) -> Result<i64, Error> {
+    let mut query = user.into_boxed();
+    if !include_deleted {
+        query = query.filter(user_deleted_at.is_null());
+    }
+    query
         .count()
         .get_result(connection)
-        .map_err(|e| Error::DBError(e))
+        .map_err(Error::from)
 }
 
 pub fn find_user_by_id(
@@ -55,6 +132,161 @@ pub fn find_user_by_id(
 ) -> Result<User, Error> {
     user
         .find(other_user_id)
+        .filter(user_deleted_at.is_null())
         .get_result::<User>(connection)
-        .map_err(|err| Error::DBError(err))
+        .map_err(Error::from)
+}
+
+// Load all the users with the given ids in one `id = ANY($1)` query.
+pub fn find_users_by_ids(
+    other_user_ids: &[Uuid],
+    connection: &PgConnection,
+) -> Result<Vec<User>, Error> {
+    user
+        .filter(user_id.eq_any(other_user_ids))
+        .filter(user_deleted_at.is_null())
+        .load::<User>(connection)
+        .map_err(Error::from)
+}
+
+// Change the fields set in the patch, the others keep their stored values. A new email is also given to
+// the linked auth_user in the same transaction.
+pub fn update_user(
+    other_user_id: &Uuid,
+    user_patch: &UserPatch,
+    expected_version: Option<i32>,
+    connection: &PgConnection,
+) -> Result<User, Error> {
+    with_transaction(connection, |connection| {
+        let stored_user = find_user_by_id(other_user_id, connection)?;
+        check_version(expected_version, stored_user.version)?;
+        // An empty patch changes nothing, not even the version.
+        if user_patch.is_empty() {
+            return Ok(stored_user);
+        }
+        if let Some(new_email) = &user_patch.email {
+            check_deleted_duplication(&[new_email], connection)?;
+        }
+        let updated_user = diesel::update(user.find(other_user_id).filter(user_version.eq(stored_user.version)))
+            .set((user_patch, user_version.eq(user_version + 1)))
+            .get_result::<User>(connection)
+            .optional()
+            .map_err(Error::from)?
+            .ok_or_else(|| lost_race(stored_user.version, stored_user_version(other_user_id, connection)))?;
+        if updated_user.email != stored_user.email {
+            if let Some(linked_auth_user_id) = updated_user.auth_user_id {
+                diesel::update(auth_user.find(linked_auth_user_id))
+                    .set(auth_user_email.eq(&updated_user.email))
+                    .execute(connection)
+                    .map_err(Error::from)?;
+            }
+        }
+        Ok(updated_user)
+    })
+}
+
+// Deleting a user only marks it and its members as deleted, they keep the same timestamp
+// so restoring the user brings back the members deleted with it. It is false when there is no live user to delete.
+pub fn delete_user_by_id(
+    other_user_id: &Uuid,
+    expected_version: Option<i32>,
+    connection: &PgConnection,
+) -> Result<bool, Error> {
+    with_transaction(connection, |connection| {
+        let stored_user = match find_user_by_id(other_user_id, connection) {
+            Ok(stored_user) => stored_user,
+            Err(Error::NotFound(_)) => return Ok(false),
+            Err(err) => return Err(err),
+        };
+        check_version(expected_version, stored_user.version)?;
+        if soft_delete_users(Some((other_user_id, stored_user.version)), connection)? == 0 {
+            return Err(lost_race(stored_user.version, stored_user_version(other_user_id, connection)));
+        }
+        Ok(true)
+    })
+}
+
+pub fn delete_all_users(connection: &PgConnection) -> Result<usize, Error> {
+    with_transaction(connection, |connection| soft_delete_users(None, connection))
+}
+
+// Mark the live users, all of them or only the one with the given id and version, as deleted.
+fn soft_delete_users(only_user: Option<(&Uuid, i32)>, connection: &PgConnection) -> Result<usize, Error> {
+    let deletion_date = current_timestamp();
+    let mut query = diesel::update(user.filter(user_deleted_at.is_null()))
+        .set((user_deleted_at.eq(deletion_date), user_version.eq(user_version + 1)))
+        .into_boxed();
+    if let Some((only_user_id, read_version)) = only_user {
+        query = query.filter(user_id.eq(only_user_id)).filter(user_version.eq(read_version));
+    }
+    let deleted_user_ids = query
+        .returning(user_id)
+        .get_results::<Uuid>(connection)
+        .map_err(Error::from)?;
+    delete_members_by_user_ids(&deleted_user_ids, deletion_date, connection)?;
+    Ok(deleted_user_ids.len())
+}
+
+// The version of the user, deleted or not, for the requests that lost the race against another change.
+fn stored_user_version(other_user_id: &Uuid, connection: &PgConnection) -> Result<i32, Error> {
+    user
+        .find(other_user_id)
+        .select(user_version)
+        .get_result::<i32>(connection)
+        .map_err(Error::from)
+}
+
+// Bring back the deleted user with the members deleted along with it, a member whose team is
+// still deleted stays deleted. A user that is not deleted is returned as it is.
+pub fn restore_user(other_user_id: &Uuid, connection: &PgConnection) -> Result<User, Error> {
+    with_transaction(connection, |connection| {
+        let stored_user = user
+            .find(other_user_id)
+            .get_result::<User>(connection)
+            .map_err(Error::from)?;
+        let deletion_date = match stored_user.deleted_at {
+            Some(deletion_date) => deletion_date,
+            None => return Ok(stored_user),
+        };
+        let restored_user = diesel::update(user.find(other_user_id).filter(user_version.eq(stored_user.version)))
+            .set((user_deleted_at.eq(None::<NaiveDateTime>), user_version.eq(user_version + 1)))
+            .get_result::<User>(connection)
+            .optional()
+            .map_err(Error::from)?
+            .ok_or_else(|| lost_race(stored_user.version, stored_user_version(other_user_id, connection)))?;
+        restore_members_by_user_id(other_user_id, deletion_date, connection)?;
+        Ok(restored_user)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::Connection;
+
+    use crate::config::config;
+    use crate::db_connection::{get_connection, init_pool};
+
+    use super::*;
+
+    #[test]
+    fn the_email_of_a_deleted_user_is_a_deleted_duplication() {
+        let pool = init_pool(&config().database).unwrap();
+        let connection = get_connection(&pool).unwrap();
+        connection.test_transaction::<_, Error, _>(|| {
+            let principal = Principal { auth_user_id: Uuid::new_v4(), email: "admin@example.com".to_string(), is_admin: false };
+            let new_user = NewUser { email: format!("{}@example.com", Uuid::new_v4()), name: "Deleted user".to_string() };
+            let inserted_user = new_user.add_user(&principal, &connection)?;
+            assert!(delete_user_by_id(&inserted_user.id, None, &connection)?);
+            assert!(matches!(find_user_by_id(&inserted_user.id, &connection), Err(Error::NotFound(_))));
+
+            assert!(matches!(new_user.add_user(&principal, &connection), Err(Error::DeletedDuplicationError)));
+            let other_users = vec![new_user.to_user(&principal, &connection)?];
+            assert!(matches!(insert_bulk_users(&other_users, &connection), Err(Error::DeletedDuplicationError)));
+
+            let restored_user = restore_user(&inserted_user.id, &connection)?;
+            assert!(restored_user.deleted_at.is_none());
+            assert_eq!(find_user_by_id(&inserted_user.id, &connection)?.email, new_user.email);
+            Ok(())
+        });
+    }
 }
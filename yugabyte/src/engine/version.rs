@@ -0,0 +1,39 @@
+use error::error::Error;
+
+// Fail when the client read another version than the stored one, no expected version matches any.
+pub fn check_version(expected_version: Option<i32>, stored_version: i32) -> Result<(), Error> {
+    match expected_version {
+        Some(expected_version) if expected_version != stored_version => {
+            Err(Error::VersionMismatch(expected_version, stored_version))
+        }
+        _ => Ok(()),
+    }
+}
+
+// The UPDATE filtered on the version that was read changed no row: another request changed the object in between,
+// and the mismatch carries the version it stored. The object is only not found when it was removed meanwhile.
+pub fn lost_race(read_version: i32, current_version: Result<i32, Error>) -> Error {
+    match current_version {
+        Ok(current_version) => Error::VersionMismatch(read_version, current_version),
+        Err(err) => err,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_version_only_fails_on_another_version() {
+        assert!(check_version(None, 3).is_ok());
+        assert!(check_version(Some(3), 3).is_ok());
+        assert!(matches!(check_version(Some(2), 3), Err(Error::VersionMismatch(2, 3))));
+    }
+
+    #[test]
+    fn lost_race_reports_the_current_version() {
+        assert!(matches!(lost_race(2, Ok(3)), Error::VersionMismatch(2, 3)));
+        let removed = lost_race(2, Err(Error::NotFound("Cannot find the object".to_string())));
+        assert!(matches!(removed, Error::NotFound(_)));
+    }
+}
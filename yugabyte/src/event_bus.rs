@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use chrono::NaiveDateTime;
+use futures_channel::mpsc::{channel, Receiver, Sender};
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use error::error::Error;
+
+use crate::db_connection::{get_connection, PgPool};
+use crate::engine::event::{delete_events_before, latest_event_time, list_events_since};
+use crate::model::event::{DomainEvent, OutboxEvent};
+
+// The events a slow subscriber can lag behind before it misses some.
+const SUBSCRIBER_BUFFER: usize = 256;
+
+/// Delivers the relayed events to the subscriptions of this process.
+#[derive(Default)]
+pub struct EventBus {
+    subscribers: Mutex<Vec<Sender<DomainEvent>>>,
+}
+
+impl EventBus {
+    pub fn subscribe(&self) -> Receiver<DomainEvent> {
+        let (sender, receiver) = channel(SUBSCRIBER_BUFFER);
+        self.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    // Send the event to every subscriber, and forget the subscribers that went away.
+    pub fn publish(&self, event: &DomainEvent) {
+        self.subscribers.lock().unwrap().retain_mut(|subscriber| match subscriber.try_send(event.clone()) {
+            Ok(()) => true,
+            Err(err) if err.is_full() => {
+                warn!(team_id = %event.team_id(), "Dropping an event for a slow subscriber");
+                true
+            }
+            Err(_) => false,
+        });
+    }
+}
+
+/// How the outbox is relayed, the `outbox` section of the [`Config`](crate::config::Config).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RelaySettings {
+    pub poll_interval_ms: u64,
+    // The events are read again for this long, so the transactions that commit late are not missed.
+    pub lookback_seconds: i64,
+    // The events older than this are deleted from the outbox.
+    pub retention_seconds: i64,
+}
+
+impl Default for RelaySettings {
+    fn default() -> Self {
+        RelaySettings {
+            poll_interval_ms: 500,
+            lookback_seconds: 10,
+            retention_seconds: 60 * 60,
+        }
+    }
+}
+
+/// Reads the committed events from the outbox and keeps the ids of the recent ones,
+/// so the events read again in the lookback window are relayed once.
+pub struct OutboxRelay {
+    settings: RelaySettings,
+    high_water: Option<NaiveDateTime>,
+    relayed: HashMap<Uuid, NaiveDateTime>,
+}
+
+impl OutboxRelay {
+    pub fn new(settings: RelaySettings, high_water: Option<NaiveDateTime>) -> Self {
+        OutboxRelay {
+            settings,
+            high_water,
+            relayed: HashMap::new(),
+        }
+    }
+
+    // Keep the events that were not relayed yet, in the order they were created.
+    pub fn select_new(&mut self, outbox_events: Vec<OutboxEvent>) -> Vec<OutboxEvent> {
+        let mut new_events = Vec::new();
+        for outbox_event in outbox_events {
+            if self.relayed.insert(outbox_event.id, outbox_event.created_at).is_none() {
+                new_events.push(outbox_event);
+            }
+        }
+        self.high_water = self.relayed.values().max().cloned().or(self.high_water);
+        if let Some(window_start) = self.window_start() {
+            self.relayed.retain(|_, created_at| *created_at >= window_start);
+        }
+        new_events
+    }
+
+    fn window_start(&self) -> Option<NaiveDateTime> {
+        self.high_water.map(|high_water| high_water - chrono::Duration::seconds(self.settings.lookback_seconds))
+    }
+
+    pub fn poll(&mut self, bus: &EventBus, pool: &PgPool) -> Result<(), Error> {
+        let pg_connection = get_connection(pool)?;
+        let since = self.window_start().unwrap_or(NaiveDateTime::from_timestamp(0, 0));
+        let outbox_events = list_events_since(since, &pg_connection)?;
+        for outbox_event in self.select_new(outbox_events) {
+            match outbox_event.event() {
+                Ok(event) => bus.publish(&event),
+                Err(err) => error!(id = %outbox_event.id, error = %err, "Skipping an unreadable outbox event"),
+            }
+        }
+        if let Some(high_water) = self.high_water {
+            delete_events_before(high_water - chrono::Duration::seconds(self.settings.retention_seconds), &pg_connection)?;
+        }
+        Ok(())
+    }
+}
+
+// Relay the outbox to the bus from a background thread, the queries are blocking.
+pub fn spawn_outbox_relay(pool: PgPool, bus: Arc<EventBus>, settings: RelaySettings) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let high_water = get_connection(&pool)
+            .and_then(|pg_connection| latest_event_time(&pg_connection))
+            .unwrap_or_else(|err| {
+                warn!(error = %err, "Can't read the latest outbox event, relaying from the beginning");
+                None
+            });
+        let mut relay = OutboxRelay::new(settings, high_water);
+        loop {
+            if let Err(err) = relay.poll(&bus, &pool) {
+                warn!(error = %err, "Failed to relay the outbox");
+            }
+            thread::sleep(Duration::from_millis(settings.poll_interval_ms));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outbox_event(id: Uuid, seconds: i64) -> OutboxEvent {
+        OutboxEvent {
+            id,
+            created_at: NaiveDateTime::from_timestamp(1_000 + seconds, 0),
+            team_id: Uuid::nil(),
+            payload: String::new(),
+        }
+    }
+
+    #[test]
+    fn select_new_relays_each_event_once() {
+        let mut relay = OutboxRelay::new(RelaySettings::default(), None);
+        let first = Uuid::new_v4();
+        let late = Uuid::new_v4();
+
+        assert_eq!(relay.select_new(vec![outbox_event(first, 5)]).len(), 1);
+        // A transaction that committed late shows up with an older created_at, inside the lookback window.
+        let new_events = relay.select_new(vec![outbox_event(late, 1), outbox_event(first, 5)]);
+        assert_eq!(new_events.iter().map(|event| event.id).collect::<Vec<_>>(), vec![late]);
+    }
+}
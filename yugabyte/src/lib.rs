@@ -1,6 +1,16 @@
+// The derives and the `table!` macro of diesel 1.4 implement its traits inside functions, which newer compilers lint.
+#![allow(non_local_definitions)]
+
+pub mod auth;
+pub mod config;
 pub mod db_connection;
 pub mod engine;
+pub mod event_bus;
+pub mod loader;
+pub mod migration;
+pub mod middleware;
 pub mod model;
+pub mod retry;
 pub mod schema;
 pub mod util;
 pub mod context;
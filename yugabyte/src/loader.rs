@@ -0,0 +1,227 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::sync::Mutex;
+
+use diesel::PgConnection;
+use uuid::Uuid;
+
+use error::error::Error;
+
+use crate::context::GraphQLContext;
+use crate::engine::member::{find_members_by_team_ids, find_members_by_user_ids};
+use crate::engine::team::find_teams_by_ids;
+use crate::engine::user::find_users_by_ids;
+use crate::model::member::Member;
+use crate::model::team::Team;
+use crate::model::user::User;
+
+// Load the values of the given keys, the keys without a value are left out of the map.
+pub type BatchFetch<K, V> = fn(&[K], &PgConnection) -> Result<HashMap<K, V>, Error>;
+
+/// A request scoped cache of the rows loaded by the GraphQL relationship fields.
+///
+/// Juniper resolves the fields one after the other, so the resolvers that return a list queue the keys
+/// their items will need with [`BatchLoader::defer`]. The first [`BatchLoader::load`] of a missing key
+/// fetches it together with every queued key in one query, and the next ones are read from the cache.
+pub struct BatchLoader<K, V> {
+    fetch: BatchFetch<K, V>,
+    state: Mutex<LoaderState<K, V>>,
+}
+
+struct LoaderState<K, V> {
+    pending: HashSet<K>,
+    // None marks the keys that were fetched without finding a value.
+    cache: HashMap<K, Option<V>>,
+}
+
+impl<K, V> BatchLoader<K, V>
+    where
+        K: Eq + Hash + Clone,
+        V: Clone,
+{
+    pub fn new(fetch: BatchFetch<K, V>) -> Self {
+        BatchLoader {
+            fetch,
+            state: Mutex::new(LoaderState {
+                pending: HashSet::new(),
+                cache: HashMap::new(),
+            }),
+        }
+    }
+
+    // Queue the keys, so they are fetched with the next missing key.
+    pub fn defer<I: IntoIterator<Item=K>>(&self, keys: I) {
+        let mut state = self.state.lock().unwrap();
+        for key in keys {
+            if !state.cache.contains_key(&key) {
+                state.pending.insert(key);
+            }
+        }
+    }
+
+    // Cache a value that was already loaded by another query.
+    pub fn prime(&self, key: K, value: V) {
+        let mut state = self.state.lock().unwrap();
+        state.pending.remove(&key);
+        state.cache.insert(key, Some(value));
+    }
+
+    pub fn load(&self, key: K, context: &GraphQLContext) -> Result<Option<V>, Error> {
+        self.load_with(key, |keys| {
+            let pg_connection = context.connection()?;
+            (self.fetch)(keys, &pg_connection)
+        })
+    }
+
+    fn load_with<F>(&self, key: K, fetch: F) -> Result<Option<V>, Error>
+        where
+            F: FnOnce(&[K]) -> Result<HashMap<K, V>, Error>,
+    {
+        let mut keys: Vec<K> = {
+            let mut state = self.state.lock().unwrap();
+            if let Some(value) = state.cache.get(&key) {
+                return Ok(value.clone());
+            }
+            state.pending.remove(&key);
+            state.pending.drain().collect()
+        };
+        keys.push(key.clone());
+
+        // The lock is released while the query runs, a failed fetch puts the keys back for the next load.
+        let mut values = match fetch(&keys) {
+            Ok(values) => values,
+            Err(err) => {
+                let mut state = self.state.lock().unwrap();
+                state.pending.extend(keys.into_iter().filter(|other_key| *other_key != key));
+                return Err(err);
+            }
+        };
+        let mut state = self.state.lock().unwrap();
+        for other_key in keys {
+            let value = values.remove(&other_key);
+            state.cache.insert(other_key, value);
+        }
+        Ok(state.cache.get(&key).cloned().flatten())
+    }
+}
+
+/// The loaders of the relationship fields, one set is created for every GraphQL request.
+pub struct Loaders {
+    pub teams: BatchLoader<Uuid, Team>,
+    pub users: BatchLoader<Uuid, User>,
+    pub members_by_team: BatchLoader<Uuid, Vec<Member>>,
+    pub members_by_user: BatchLoader<Uuid, Vec<Member>>,
+}
+
+impl Default for Loaders {
+    fn default() -> Self {
+        Loaders {
+            teams: BatchLoader::new(fetch_teams),
+            users: BatchLoader::new(fetch_users),
+            members_by_team: BatchLoader::new(fetch_members_by_team),
+            members_by_user: BatchLoader::new(fetch_members_by_user),
+        }
+    }
+}
+
+impl Loaders {
+    // Queue the teams and the users of the members, for their `team` and `user` fields.
+    pub fn defer_members(&self, members: &[Member]) {
+        self.teams.defer(members.iter().map(|member| member.team_id));
+        self.users.defer(members.iter().map(|member| member.user_id));
+    }
+
+    // Cache the teams and queue their members, for their `members` field.
+    pub fn defer_teams(&self, teams: &[Team]) {
+        self.members_by_team.defer(teams.iter().map(|team| team.id));
+        for team in teams {
+            self.teams.prime(team.id, team.clone());
+        }
+    }
+
+    // Cache the users and queue their members, for their `memberships` field.
+    pub fn defer_users(&self, users: &[User]) {
+        self.members_by_user.defer(users.iter().map(|user| user.id));
+        for user in users {
+            self.users.prime(user.id, user.clone());
+        }
+    }
+}
+
+fn fetch_teams(team_ids: &[Uuid], connection: &PgConnection) -> Result<HashMap<Uuid, Team>, Error> {
+    Ok(find_teams_by_ids(team_ids, connection)?
+        .into_iter()
+        .map(|team| (team.id, team))
+        .collect())
+}
+
+fn fetch_users(user_ids: &[Uuid], connection: &PgConnection) -> Result<HashMap<Uuid, User>, Error> {
+    Ok(find_users_by_ids(user_ids, connection)?
+        .into_iter()
+        .map(|user| (user.id, user))
+        .collect())
+}
+
+fn fetch_members_by_team(team_ids: &[Uuid], connection: &PgConnection) -> Result<HashMap<Uuid, Vec<Member>>, Error> {
+    let mut members_by_team: HashMap<Uuid, Vec<Member>> = HashMap::new();
+    for member in find_members_by_team_ids(team_ids, connection)? {
+        members_by_team.entry(member.team_id).or_default().push(member);
+    }
+    Ok(members_by_team)
+}
+
+fn fetch_members_by_user(user_ids: &[Uuid], connection: &PgConnection) -> Result<HashMap<Uuid, Vec<Member>>, Error> {
+    let mut members_by_user: HashMap<Uuid, Vec<Member>> = HashMap::new();
+    for member in find_members_by_user_ids(user_ids, connection)? {
+        members_by_user.entry(member.user_id).or_default().push(member);
+    }
+    Ok(members_by_user)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    fn no_fetch(_: &[u32], _: &PgConnection) -> Result<HashMap<u32, String>, Error> {
+        unreachable!()
+    }
+
+    #[test]
+    fn load_fetches_the_deferred_keys_once() {
+        let loader = BatchLoader::new(no_fetch);
+        let fetched_keys = RefCell::new(Vec::new());
+        let fetch = |keys: &[u32]| {
+            let mut keys = keys.to_vec();
+            keys.sort();
+            fetched_keys.borrow_mut().push(keys.clone());
+            // The key 3 doesn't exist.
+            Ok(keys.into_iter().filter(|key| *key != 3).map(|key| (key, key.to_string())).collect())
+        };
+
+        loader.defer(vec![2, 3]);
+        assert_eq!(loader.load_with(1, fetch).unwrap(), Some("1".to_string()));
+        assert_eq!(loader.load_with(2, fetch).unwrap(), Some("2".to_string()));
+        assert_eq!(loader.load_with(3, fetch).unwrap(), None);
+        assert_eq!(*fetched_keys.borrow(), vec![vec![1, 2, 3]]);
+    }
+
+    #[test]
+    fn failed_load_keeps_the_deferred_keys() {
+        let loader = BatchLoader::new(no_fetch);
+        let fetched_keys = RefCell::new(Vec::new());
+        let fetch = |keys: &[u32]| {
+            let mut keys = keys.to_vec();
+            keys.sort();
+            fetched_keys.borrow_mut().push(keys.clone());
+            Ok(keys.into_iter().map(|key| (key, key.to_string())).collect())
+        };
+
+        loader.defer(vec![2, 3]);
+        assert!(loader.load_with(1, |_| Err(Error::InternalServerError("down".to_string()))).is_err());
+        assert_eq!(loader.load_with(1, fetch).unwrap(), Some("1".to_string()));
+        assert_eq!(loader.load_with(3, fetch).unwrap(), Some("3".to_string()));
+        assert_eq!(*fetched_keys.borrow(), vec![vec![1, 2, 3]]);
+    }
+}
@@ -0,0 +1,150 @@
+use std::rc::Rc;
+use std::task::{Context, Poll};
+
+use actix_web::{Error, HttpMessage};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{AUTHORIZATION, HeaderMap};
+use futures_util::future::{err, LocalBoxFuture, ok, Ready};
+
+use error::error::Errors;
+use error::error::StateCode::InvalidToken;
+
+use crate::auth::principal::Principal;
+use crate::auth::token::{TOKEN_SETTINGS, TokenType};
+use crate::config::AuthConfig;
+
+/// Validates the bearer access token of every request and stores the [`Principal`] in the
+/// request extensions. Requests without a valid token are rejected with 401, except for:
+/// - the allowlisted paths, which are public and never rejected.
+/// - the optional paths, which accept anonymous calls but still reject invalid tokens.
+///   The GraphQL endpoint is one of them, because `login` must stay reachable.
+///
+/// A path ending with `*` matches every path that starts with it.
+#[derive(Clone)]
+pub struct Authentication {
+    allowlist: Rc<Vec<String>>,
+    optional: Rc<Vec<String>>,
+}
+
+impl Authentication {
+    pub fn new(allowlist: Vec<String>) -> Self {
+        Authentication {
+            allowlist: Rc::new(allowlist),
+            optional: Rc::new(Vec::new()),
+        }
+    }
+
+    // Take the allowlist of the config (`auth.allowlist`), and fall back to the given paths.
+    pub fn from_config(auth_config: &AuthConfig, default_allowlist: &[&str]) -> Self {
+        let allowlist = match &auth_config.allowlist {
+            Some(allowlist) => allowlist.clone(),
+            None => default_allowlist.iter().map(|path| path.to_string()).collect(),
+        };
+        Authentication::new(allowlist)
+    }
+
+    pub fn allow(mut self, path: &str) -> Self {
+        Rc::make_mut(&mut self.allowlist).push(path.to_string());
+        self
+    }
+
+    pub fn optional(mut self, path: &str) -> Self {
+        Rc::make_mut(&mut self.optional).push(path.to_string());
+        self
+    }
+}
+
+impl<S, B> Transform<S> for Authentication
+    where
+        S: Service<Request=ServiceRequest, Response=ServiceResponse<B>, Error=Error>,
+        S::Future: 'static,
+        B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = AuthenticationMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(AuthenticationMiddleware {
+            service,
+            allowlist: self.allowlist.clone(),
+            optional: self.optional.clone(),
+        })
+    }
+}
+
+pub struct AuthenticationMiddleware<S> {
+    service: S,
+    allowlist: Rc<Vec<String>>,
+    optional: Rc<Vec<String>>,
+}
+
+impl<S, B> Service for AuthenticationMiddleware<S>
+    where
+        S: Service<Request=ServiceRequest, Response=ServiceResponse<B>, Error=Error>,
+        S::Future: 'static,
+        B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let is_public = matches_any(&self.allowlist, req.path());
+        let is_optional = matches_any(&self.optional, req.path());
+
+        match bearer_token(req.headers()).map(|token| TOKEN_SETTINGS.verify(token, TokenType::Access)) {
+            Some(Ok(claims)) => {
+                req.extensions_mut().insert(Principal::from(claims));
+            }
+            // A public path never fails, even when the caller sends a stale token.
+            Some(Err(_)) if !is_public => {
+                return Box::pin(err(Errors::Unauthorized(InvalidToken.into()).into()));
+            }
+            None if !is_public && !is_optional => {
+                return Box::pin(err(Errors::Unauthorized(InvalidToken.into()).into()));
+            }
+            _ => {}
+        }
+
+        Box::pin(self.service.call(req))
+    }
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim())
+}
+
+fn matches_any(paths: &[String], path: &str) -> bool {
+    paths.iter().any(|allowed| match allowed.strip_suffix('*') {
+        Some(prefix) => path.starts_with(prefix),
+        None => path == allowed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_any_exact_and_prefix() {
+        let paths = vec!["/health".to_string(), "/api/spec/*".to_string()];
+
+        assert!(matches_any(&paths, "/health"));
+        assert!(matches_any(&paths, "/api/spec/rest"));
+        assert!(!matches_any(&paths, "/health/check"));
+        assert!(!matches_any(&paths, "/member/remove_all"));
+    }
+}
@@ -0,0 +1,127 @@
+use std::task::{Context, Poll};
+
+use actix_web::{Error, HttpMessage, HttpResponse};
+use actix_web::error::InternalError;
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::HeaderValue;
+use actix_web::http::header::{HeaderMap, HeaderName};
+use futures_util::future::{LocalBoxFuture, ok, Ready};
+use uuid::Uuid;
+
+use error::error::Errors;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// The id of the request, it is taken from the `X-Request-Id` header of the caller or generated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestId(pub String);
+
+/// Tags every request with a [`RequestId`], echoes it in the `X-Request-Id` response header, and renders
+/// the [`Errors`] raised by the handlers or the inner middlewares as problem+json bodies that carry
+/// the path of the request as `instance` and the request id.
+///
+/// It must wrap the other middlewares, so the rejections of [`Authentication`](super::authentication::Authentication)
+/// get the same body.
+#[derive(Clone, Default)]
+pub struct RequestIdentifier;
+
+impl<S> Transform<S> for RequestIdentifier
+    where
+        S: Service<Request=ServiceRequest, Response=ServiceResponse, Error=Error>,
+        S::Future: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RequestIdentifierMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(RequestIdentifierMiddleware { service })
+    }
+}
+
+pub struct RequestIdentifierMiddleware<S> {
+    service: S,
+}
+
+impl<S> Service for RequestIdentifierMiddleware<S>
+    where
+        S: Service<Request=ServiceRequest, Response=ServiceResponse, Error=Error>,
+        S::Future: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let request_id = req
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .filter(|value| is_valid_request_id(value))
+            .map(|value| value.to_string())
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+        req.extensions_mut().insert(RequestId(request_id.clone()));
+        let instance = req.path().to_string();
+        let future = self.service.call(req);
+
+        Box::pin(async move {
+            match future.await {
+                Ok(res) => {
+                    let mut res = match res.response().error().and_then(|err| err.as_error::<Errors>()) {
+                        Some(errors) => {
+                            let response = errors.problem_response(Some(instance), Some(request_id.clone()));
+                            res.into_response(response)
+                        }
+                        None => res,
+                    };
+                    insert_request_id(res.headers_mut(), &request_id);
+                    Ok(res)
+                }
+                // The inner middlewares fail without a response, so the rendered problem is carried by the error.
+                Err(err) => {
+                    let mut response = match err.as_error::<Errors>() {
+                        Some(errors) => errors.problem_response(Some(instance), Some(request_id.clone())),
+                        None => HttpResponse::from_error(err),
+                    };
+                    insert_request_id(response.headers_mut(), &request_id);
+                    let cause = format!("request {} failed", request_id);
+                    Err(InternalError::from_response(cause, response).into())
+                }
+            }
+        })
+    }
+}
+
+fn insert_request_id(headers: &mut HeaderMap, request_id: &str) {
+    if let Ok(value) = HeaderValue::from_str(request_id) {
+        headers.insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+    }
+}
+
+// Accept the ids of the callers only when they are short and printable, so they can't pollute the logs.
+fn is_valid_request_id(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= 128
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_request_id() {
+        assert!(is_valid_request_id("2f1c7a3e-5b9d-4e1a-9c3b-0d8e7f6a5b4c"));
+        assert!(!is_valid_request_id(""));
+        assert!(!is_valid_request_id("id with spaces"));
+        assert!(!is_valid_request_id(&"a".repeat(129)));
+    }
+}
@@ -0,0 +1,303 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+use diesel::{Connection, PgConnection};
+use diesel::connection::{SimpleConnection, TransactionManager};
+use diesel::sql_types::Text;
+use diesel_migrations::{MigrationConnection, setup_database};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use error::error::Error;
+
+use crate::db_connection::{get_connection, PgPool};
+use crate::util::raw_query::raw_query;
+
+// The table of the single row locked while the migrations run, so the replicas starting at once apply them one by one.
+// YugabyteDB has no advisory lock, a row lock works on both databases.
+const MIGRATION_LOCK_TABLE: &str = "CREATE TABLE IF NOT EXISTS __migration_lock (id INTEGER PRIMARY KEY);
+    INSERT INTO __migration_lock (id) VALUES (1) ON CONFLICT DO NOTHING;";
+
+// Embed the up and down scripts of a directory of `yugabyte/migrations`, the version is the one diesel records.
+macro_rules! migration {
+    ($version:literal, $name:literal) => {
+        EmbeddedMigration {
+            version: $version,
+            name: $name,
+            up_sql: include_str!(concat!("../migrations/", $name, "/up.sql")),
+            down_sql: include_str!(concat!("../migrations/", $name, "/down.sql")),
+        }
+    };
+}
+
+/// The migrations of `yugabyte/migrations` compiled into the binaries, in the order they are applied.
+/// A new migration directory must be added here too, the tests check that none is missing.
+pub static MIGRATIONS: &[EmbeddedMigration] = &[
+    migration!("00000000000000", "00000000000000_diesel_initial_setup"),
+    migration!("20220228045410", "2022-02-28-045410_create_auth_user"),
+    migration!("20220228045419", "2022-02-28-045419_create_team"),
+    migration!("20220228045426", "2022-02-28-045426_create_user"),
+    migration!("20220228045432", "2022-02-28-045432_create_member"),
+    migration!("20220228235338", "2022-02-28-235338_add_modification_date_column_to_member"),
+    migration!("20220307120000", "2022-03-07-120000_create_refresh_token"),
+    migration!("20220308090000", "2022-03-08-090000_add_is_admin_column_to_auth_user"),
+    migration!("20220308093000", "2022-03-08-093000_restrict_member_role"),
+    migration!("20220310090000", "2022-03-10-090000_create_outbox_event"),
+    migration!("20220311090000", "2022-03-11-090000_add_id_range_indexes"),
+    migration!("20220312090000", "2022-03-12-090000_add_version_columns"),
+    migration!("20220313090000", "2022-03-13-090000_add_deleted_at_columns"),
+    migration!("20220314090000", "2022-03-14-090000_link_user_to_auth_user"),
+];
+
+#[derive(Debug)]
+pub struct EmbeddedMigration {
+    // The version recorded in `__diesel_schema_migrations`, like the diesel CLI does.
+    pub version: &'static str,
+    // The name of the directory.
+    pub name: &'static str,
+    up_sql: &'static str,
+    down_sql: &'static str,
+}
+
+/// What the services do with the pending migrations when they start.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MigrationMode {
+    // Apply the pending migrations.
+    #[default]
+    Run,
+    // Refuse to start while a migration is pending, for the deployments that migrate with `migrate up`.
+    Verify,
+    Skip,
+}
+
+/// The `migrate up|down|status` admin commands of both binaries.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MigrateCommand {
+    // Apply every pending migration.
+    Up,
+    // Revert the latest applied migration.
+    Down,
+    // List the migrations and whether they are applied.
+    Status,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationStatus {
+    pub name: &'static str,
+    pub applied: bool,
+}
+
+impl FromStr for MigrationMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode {
+            "run" => Ok(MigrationMode::Run),
+            "verify" => Ok(MigrationMode::Verify),
+            "skip" => Ok(MigrationMode::Skip),
+            _ => Err(format!("unknown migration mode {}, expected run, verify or skip", mode)),
+        }
+    }
+}
+
+impl FromStr for MigrateCommand {
+    type Err = String;
+
+    fn from_str(command: &str) -> Result<Self, Self::Err> {
+        match command {
+            "up" => Ok(MigrateCommand::Up),
+            "down" => Ok(MigrateCommand::Down),
+            "status" => Ok(MigrateCommand::Status),
+            _ => Err(format!("unknown migrate command {}, expected up, down or status", command)),
+        }
+    }
+}
+
+impl fmt::Display for MigrationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = if self.applied { "applied" } else { "pending" };
+        write!(f, "{:<8} {}", state, self.name)
+    }
+}
+
+// Apply, check or skip the pending migrations, depending on the mode of the config.
+pub fn migrate_on_startup(mode: MigrationMode, pool: &PgPool) -> Result<(), Error> {
+    match mode {
+        MigrationMode::Run => {
+            let pg_connection = get_connection(pool)?;
+            for name in run_pending(&pg_connection)? {
+                info!(migration = name, "Applied the migration");
+            }
+            Ok(())
+        }
+        MigrationMode::Verify => {
+            let pg_connection = get_connection(pool)?;
+            let pending = pending(&pg_connection)?;
+            if pending.is_empty() {
+                Ok(())
+            } else {
+                Err(Error::PendingMigrations(pending.iter().map(|name| name.to_string()).collect()))
+            }
+        }
+        MigrationMode::Skip => Ok(()),
+    }
+}
+
+// Run an admin command and print its outcome.
+pub fn run_command(command: MigrateCommand, pool: &PgPool) -> Result<(), Error> {
+    let pg_connection = get_connection(pool)?;
+    match command {
+        MigrateCommand::Up => {
+            let applied = run_pending(&pg_connection)?;
+            for name in &applied {
+                println!("Applied {}", name);
+            }
+            if applied.is_empty() {
+                println!("No pending migration");
+            }
+        }
+        MigrateCommand::Down => match revert_latest(&pg_connection)? {
+            Some(name) => println!("Reverted {}", name),
+            None => println!("No applied migration"),
+        },
+        MigrateCommand::Status => {
+            for migration in status(&pg_connection)? {
+                println!("{}", migration);
+            }
+        }
+    }
+    Ok(())
+}
+
+// Apply the pending migrations in order, each one in its savepoint, and return their names.
+// They are committed together with the lock transaction, a failed migration rolls back the whole run.
+pub fn run_pending(connection: &PgConnection) -> Result<Vec<&'static str>, Error> {
+    with_migration_lock(connection, || {
+        // The pending migrations are read under the lock, the ones another replica applied meanwhile are left out.
+        let applied = applied_versions(connection)?;
+        let mut names = Vec::new();
+        for migration in MIGRATIONS.iter().filter(|migration| !applied.contains(migration.version)) {
+            connection.transaction::<_, Error, _>(|| {
+                connection
+                    .batch_execute(migration.up_sql)
+                    .map_err(|e| Error::Migration(format!("Failed to apply {}: {}", migration.name, e)))?;
+                connection.insert_new_migration(migration.version)?;
+                Ok(())
+            })?;
+            names.push(migration.name);
+        }
+        Ok(names)
+    })
+}
+
+// Revert the latest applied migration and return its name, or None when nothing is applied.
+pub fn revert_latest(connection: &PgConnection) -> Result<Option<&'static str>, Error> {
+    with_migration_lock(connection, || {
+        setup_database(connection)?;
+        let version = match connection.latest_run_migration_version()? {
+            Some(version) => version,
+            None => return Ok(None),
+        };
+        let migration = MIGRATIONS
+            .iter()
+            .find(|migration| migration.version == version)
+            .ok_or_else(|| Error::Migration(format!("The applied migration {} isn't embedded in the service", version)))?;
+
+        connection.transaction::<_, Error, _>(|| {
+            connection
+                .batch_execute(migration.down_sql)
+                .map_err(|e| Error::Migration(format!("Failed to revert {}: {}", migration.name, e)))?;
+            raw_query("DELETE FROM __diesel_schema_migrations WHERE version = $1")
+                .bind::<Text, _>(migration.version)
+                .execute(connection)?;
+            Ok(())
+        })?;
+        Ok(Some(migration.name))
+    })
+}
+
+pub fn status(connection: &PgConnection) -> Result<Vec<MigrationStatus>, Error> {
+    let applied = applied_versions(connection)?;
+    Ok(MIGRATIONS
+        .iter()
+        .map(|migration| MigrationStatus {
+            name: migration.name,
+            applied: applied.contains(migration.version),
+        })
+        .collect())
+}
+
+pub fn pending(connection: &PgConnection) -> Result<Vec<&'static str>, Error> {
+    Ok(status(connection)?
+        .into_iter()
+        .filter(|migration| !migration.applied)
+        .map(|migration| migration.name)
+        .collect())
+}
+
+fn applied_versions(connection: &PgConnection) -> Result<HashSet<String>, Error> {
+    setup_database(connection)?;
+    Ok(connection.previously_run_migration_versions()?)
+}
+
+// Hold the lock row of the migrations while `unit_of_work` runs in its transaction, the other replicas wait for it.
+// The commit or the rollback releases the lock, the error of the work wins over the one of the rollback.
+fn with_migration_lock<T, F>(connection: &PgConnection, unit_of_work: F) -> Result<T, Error>
+    where
+        F: FnOnce() -> Result<T, Error>,
+{
+    connection.batch_execute(MIGRATION_LOCK_TABLE)?;
+    let transaction_manager = connection.transaction_manager();
+    transaction_manager.begin_transaction(connection)?;
+    let result = raw_query("SELECT id FROM __migration_lock WHERE id = 1 FOR UPDATE")
+        .execute(connection)
+        .and_then(|_| unit_of_work());
+    match result {
+        Ok(value) => {
+            transaction_manager.commit_transaction(connection)?;
+            Ok(value)
+        }
+        Err(e) => {
+            if let Err(rollback_error) = transaction_manager.rollback_transaction(connection) {
+                warn!(error = %rollback_error, "Failed to release the migration lock");
+            }
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn every_migration_directory_is_embedded() {
+        let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("migrations");
+        let mut names: Vec<String> = fs::read_dir(directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .filter(|name| !name.starts_with('.'))
+            .collect();
+        names.sort();
+
+        let embedded: Vec<&str> = MIGRATIONS.iter().map(|migration| migration.name).collect();
+        assert_eq!(names, embedded);
+        for migration in MIGRATIONS {
+            let version: String = migration.name.split('_').next().unwrap().replace('-', "");
+            assert_eq!(migration.version, version);
+        }
+    }
+
+    #[test]
+    fn parse_the_modes_and_the_commands() {
+        assert_eq!("verify".parse(), Ok(MigrationMode::Verify));
+        assert!("later".parse::<MigrationMode>().is_err());
+        assert_eq!("status".parse(), Ok(MigrateCommand::Status));
+        assert!("redo".parse::<MigrateCommand>().is_err());
+    }
+}
@@ -1,10 +1,14 @@
-use diesel::{Insertable, Queryable};
-use juniper::GraphQLObject;
+use std::fmt;
+
+use diesel::{AsChangeset, Insertable, Queryable};
+use juniper::{GraphQLInputObject, GraphQLObject};
 use paperclip::actix::Apiv2Schema;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use validator::Validate;
 
+use crate::config::REDACTED;
+use crate::model::patch::Patch;
 use crate::schema::auth_user;
 
 /// I created this model separately for security purposes. and I prefer to put this table
@@ -13,8 +17,80 @@ use crate::schema::auth_user;
 #[table_name = "auth_user"]
 pub struct AuthUser {
     pub id: Uuid,
+    #[validate(email(code = "email-format-error"))]
+    pub email: String,
+    // It holds the Argon2id PHC string, and it is never sent back to the clients.
+    #[serde(skip_serializing)]
+    #[graphql(skip)]
+    pub password: String,
+    // Global administrators can manage every team and call the remove_all endpoints.
+    pub is_admin: bool,
+}
+
+// The signup of an auth_user, the password is given in plain text and hashed by the engine.
+#[derive(Default, Serialize, Deserialize, Apiv2Schema, GraphQLInputObject, Validate)]
+pub struct NewAuthUser {
     #[validate(email(code = "email-format-error"))]
     pub email: String,
     #[validate(length(min = 9, max = 127, code = "password-length-error"))]
     pub password: String,
-}
\ No newline at end of file
+}
+
+// The fields of the auth_user to change, see Patch. The password is given in plain text and hashed by the engine,
+// a new email is also given to the user with the old one.
+#[derive(Default, Clone, Serialize, Deserialize, Apiv2Schema, GraphQLInputObject, Validate, AsChangeset)]
+#[table_name = "auth_user"]
+pub struct AuthUserPatch {
+    #[validate(email(code = "email-format-error"))]
+    pub email: Option<String>,
+    #[validate(length(min = 9, max = 127, code = "password-length-error"))]
+    pub password: Option<String>,
+    // Only the global administrators can change it.
+    pub is_admin: Option<bool>,
+}
+
+// The plain passwords are never printed, like the secrets of the config.
+impl fmt::Debug for NewAuthUser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NewAuthUser")
+            .field("email", &self.email)
+            .field("password", &REDACTED)
+            .finish()
+    }
+}
+
+impl fmt::Debug for AuthUserPatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthUserPatch")
+            .field("email", &self.email)
+            .field("password", &self.password.as_ref().map(|_| REDACTED))
+            .field("is_admin", &self.is_admin)
+            .finish()
+    }
+}
+
+impl Patch for AuthUserPatch {
+    fn fields(&self) -> Vec<(&'static str, bool)> {
+        vec![
+            ("email", self.email.is_some()),
+            ("password", self.password.is_some()),
+            ("is_admin", self.is_admin.is_some()),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_hides_the_plain_passwords() {
+        let new_auth_user = NewAuthUser { email: "a@b.com".to_string(), password: "secret-password".to_string() };
+        let printed = format!("{:?}", new_auth_user);
+        assert!(printed.contains("a@b.com") && !printed.contains("secret-password"));
+
+        let patch = AuthUserPatch { password: Some("secret-password".to_string()), ..Default::default() };
+        assert!(!format!("{:?}", patch).contains("secret-password"));
+        assert!(format!("{:?}", AuthUserPatch::default()).contains("password: None"));
+    }
+}
@@ -1,17 +1,36 @@
-use juniper::GraphQLInputObject;
+use std::fmt;
+
+use juniper::{GraphQLInputObject, GraphQLObject};
 use paperclip::actix::Apiv2Schema;
 use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::config::REDACTED;
+use crate::model::member::MemberRole;
 
-#[derive(Default, Deserialize, Apiv2Schema, GraphQLInputObject, Debug)]
+// The rows are sorted by id. `after` and `before` are the cursors of a previous response, the offset is
+// ignored when one of them is given.
+#[derive(Default, Deserialize, Apiv2Schema, GraphQLInputObject, Debug, Validate)]
 pub struct PaginationDTO {
+    #[validate(range(min = 1, max = 100, code = "page-size-range-error"))]
     pub page_size: i32,
+    #[validate(range(min = 0, code = "offset-range-error"))]
+    #[serde(default)]
     pub offset: i32,
+    pub after: Option<String>,
+    pub before: Option<String>,
+    // List the deleted rows too, only the global administrators can ask for them.
+    #[serde(default)]
+    pub include_deleted: bool,
 }
 
 #[derive(Default, Serialize, Apiv2Schema, Debug)]
 pub struct PaginatedResponseDTO<T> {
     pub paginated_list: Vec<T>,
     pub count: i64,
+    // The cursors of the next and the previous pages, they are None on the last and the first pages.
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Apiv2Schema)]
@@ -31,11 +50,61 @@ pub struct MemberInfo {
     pub name: String,
     pub email: String,
     pub identity_num: String,
-    pub role: String,
+    pub role: MemberRole,
 }
 
-#[derive(Serialize, Deserialize, Apiv2Schema)]
+#[derive(Serialize, Deserialize, Apiv2Schema, Validate)]
 pub struct MemberName {
+    #[validate(length(min = 1, max = 100, code = "name-length-error"))]
     pub name: String,
 }
 
+#[derive(Deserialize, Apiv2Schema, GraphQLInputObject, Validate)]
+pub struct LoginDTO {
+    #[validate(email(code = "email-format-error"))]
+    pub email: String,
+    pub password: String,
+}
+
+// The password is never printed, like the secrets of the config.
+impl fmt::Debug for LoginDTO {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoginDTO")
+            .field("email", &self.email)
+            .field("password", &REDACTED)
+            .finish()
+    }
+}
+
+#[derive(Deserialize, Apiv2Schema, GraphQLInputObject)]
+pub struct RefreshTokenDTO {
+    pub refresh_token: String,
+}
+
+impl fmt::Debug for RefreshTokenDTO {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RefreshTokenDTO")
+            .field("refresh_token", &REDACTED)
+            .finish()
+    }
+}
+
+#[derive(Serialize, Deserialize, Apiv2Schema, GraphQLObject)]
+pub struct TokenPair {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub token_type: String,
+    // The lifetime of the access token in seconds.
+    pub expires_in: i32,
+}
+
+impl fmt::Debug for TokenPair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenPair")
+            .field("access_token", &REDACTED)
+            .field("refresh_token", &REDACTED)
+            .field("token_type", &self.token_type)
+            .field("expires_in", &self.expires_in)
+            .finish()
+    }
+}
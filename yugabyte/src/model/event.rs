@@ -0,0 +1,72 @@
+use chrono::NaiveDateTime;
+use diesel::{Insertable, Queryable};
+use juniper::{GraphQLEnum, GraphQLObject};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::model::member::Member;
+use crate::model::team::Team;
+use crate::schema::outbox_event;
+
+/// A committed change of a member or a team, as it is delivered to the GraphQL subscriptions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum DomainEvent {
+    MemberAdded(Member),
+    MemberUpdated(Member),
+    MemberRemoved(Member),
+    TeamChanged(TeamChange),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, GraphQLEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum TeamChangeKind {
+    Created,
+    Updated,
+    Deleted,
+    Restored,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, GraphQLObject)]
+#[graphql(context = crate::context::GraphQLContext)]
+pub struct TeamChange {
+    pub kind: TeamChangeKind,
+    pub team: Team,
+}
+
+impl DomainEvent {
+    pub fn team_changed(kind: TeamChangeKind, team: &Team) -> Self {
+        DomainEvent::TeamChanged(TeamChange {
+            kind,
+            team: team.clone(),
+        })
+    }
+
+    // The team the event belongs to, the subscriptions are filtered by it.
+    pub fn team_id(&self) -> Uuid {
+        match self {
+            DomainEvent::MemberAdded(member)
+            | DomainEvent::MemberUpdated(member)
+            | DomainEvent::MemberRemoved(member) => member.team_id,
+            DomainEvent::TeamChanged(team_change) => team_change.team.id,
+        }
+    }
+}
+
+/// A row of the outbox, `payload` is the JSON encoded [`DomainEvent`].
+#[derive(Debug, Queryable, Clone)]
+pub struct OutboxEvent {
+    pub id: Uuid,
+    // It is set by the database, so the events of every service instance share one clock.
+    pub created_at: NaiveDateTime,
+    pub team_id: Uuid,
+    pub payload: String,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "outbox_event"]
+pub struct NewOutboxEvent {
+    pub id: Uuid,
+    pub team_id: Uuid,
+    pub payload: String,
+}
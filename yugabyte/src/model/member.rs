@@ -1,15 +1,83 @@
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
 use chrono::NaiveDateTime;
-use diesel::{Insertable, Queryable};
+use diesel::{AsChangeset, Insertable, Queryable};
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::serialize::{self, Output, ToSql};
 use diesel::types::VarChar;
-use juniper::GraphQLInputObject;
+use juniper::{GraphQLEnum, GraphQLInputObject};
 use juniper::GraphQLObject;
 use paperclip::actix::Apiv2Schema;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
+use validator::{Validate, validate_length, ValidationError, ValidationErrors};
+
+use error::error::{Error, ErrorCode};
 
+use crate::context::GraphQLContext;
+use crate::model::patch::{MergeField, Patch};
+use crate::model::team::Team;
+use crate::model::user::User;
 use crate::schema::member;
+use crate::util::utils::REGEX_FULL_WORD;
+
+/// The role of a member inside its team, it is stored as lowercase text in `member.role`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow, GraphQLEnum, Apiv2Schema)]
+#[serde(rename_all = "lowercase")]
+#[sql_type = "VarChar"]
+pub enum MemberRole {
+    Owner,
+    Admin,
+    #[default]
+    Viewer,
+}
+
+impl MemberRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MemberRole::Owner => "owner",
+            MemberRole::Admin => "admin",
+            MemberRole::Viewer => "viewer",
+        }
+    }
+}
+
+impl fmt::Display for MemberRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for MemberRole {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "owner" => Ok(MemberRole::Owner),
+            "admin" => Ok(MemberRole::Admin),
+            "viewer" => Ok(MemberRole::Viewer),
+            _ => Err(format!("Unknown member role: {}", value)),
+        }
+    }
+}
+
+impl ToSql<VarChar, Pg> for MemberRole {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        <str as ToSql<VarChar, Pg>>::to_sql(self.as_str(), out)
+    }
+}
+
+impl FromSql<VarChar, Pg> for MemberRole {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let value = <String as FromSql<VarChar, Pg>>::from_sql(bytes)?;
+        value.parse::<MemberRole>().map_err(|e| e.into())
+    }
+}
 
-#[derive(Debug, Serialize, Deserialize, Queryable, Insertable, Clone, GraphQLObject, Apiv2Schema)]
+#[derive(Debug, Serialize, Deserialize, Queryable, Insertable, Clone, Apiv2Schema)]
 #[table_name = "member"]
 pub struct Member {
     pub id: Uuid,
@@ -17,19 +85,89 @@ pub struct Member {
     pub user_id: Uuid,
     pub name: String,
     pub identity_num: String,
-    pub role: String,
+    pub role: MemberRole,
     pub assigned_at: NaiveDateTime,
     pub expired_at: Option<NaiveDateTime>,
     pub modification_date: Option<NaiveDateTime>,
+    // It is incremented by every update, the outbox events written before it existed have none.
+    #[serde(default)]
+    pub version: i32,
+    // Set when the member, its team or its user is deleted.
+    pub deleted_at: Option<NaiveDateTime>,
 }
 
-#[derive(Default, Debug, Serialize, Deserialize, Apiv2Schema, GraphQLInputObject)]
+#[juniper::graphql_object(Context = GraphQLContext)]
+impl Member {
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub fn team_id(&self) -> Uuid {
+        self.team_id
+    }
+
+    pub fn user_id(&self) -> Uuid {
+        self.user_id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn identity_num(&self) -> &str {
+        &self.identity_num
+    }
+
+    pub fn role(&self) -> MemberRole {
+        self.role
+    }
+
+    pub fn assigned_at(&self) -> NaiveDateTime {
+        self.assigned_at
+    }
+
+    pub fn expired_at(&self) -> Option<NaiveDateTime> {
+        self.expired_at
+    }
+
+    pub fn modification_date(&self) -> Option<NaiveDateTime> {
+        self.modification_date
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    pub fn deleted_at(&self) -> Option<NaiveDateTime> {
+        self.deleted_at
+    }
+
+    pub fn team(&self, context: &GraphQLContext) -> Result<Team, Error> {
+        context.principal()?;
+
+        context.loaders.teams
+            .load(self.team_id, context)?
+            .ok_or_else(|| Error::NotFound(format!("Team {} not found", self.team_id)))
+    }
+
+    pub fn user(&self, context: &GraphQLContext) -> Result<User, Error> {
+        context.principal()?;
+
+        context.loaders.users
+            .load(self.user_id, context)?
+            .ok_or_else(|| Error::NotFound(format!("User {} not found", self.user_id)))
+    }
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Apiv2Schema, GraphQLInputObject, Validate)]
 pub struct NewMember {
     pub team_id: Uuid,
     pub user_id: Uuid,
+    #[validate(length(min = 1, max = 100, code = "name-length-error"), regex(path = "REGEX_FULL_WORD", code = "name-format-error"))]
     pub name: String,
+    #[validate(length(min = 1, max = 50, code = "identity-num-length-error"))]
     pub identity_num: String,
-    pub role: String,
+    pub role: MemberRole,
     pub expired_at: Option<NaiveDateTime>,
 }
 
@@ -37,4 +175,140 @@ pub struct NewMember {
 pub struct Name {
     #[sql_type = "VarChar"]
     pub name: String,
-}
\ No newline at end of file
+}
+
+/// A JSON merge patch of a member: the keys that are absent keep their stored value and `expired_at: null` removes
+/// the expiry. The team and the user of a member can't be patched.
+#[derive(Default, Debug, Clone, Deserialize, Apiv2Schema)]
+#[serde(default, deny_unknown_fields)]
+pub struct MemberPatch {
+    pub name: MergeField<String>,
+    pub identity_num: MergeField<String>,
+    pub role: MergeField<MemberRole>,
+    pub expired_at: MergeField<NaiveDateTime>,
+}
+
+/// The columns a [`MemberPatch`] changes, the ones left None are not updated.
+#[derive(Debug, AsChangeset)]
+#[table_name = "member"]
+pub struct MemberChangeset {
+    pub name: Option<String>,
+    pub identity_num: Option<String>,
+    pub role: Option<MemberRole>,
+    pub expired_at: Option<Option<NaiveDateTime>>,
+    pub modification_date: Option<NaiveDateTime>,
+}
+
+/// The GraphQL input of `patchMember`. GraphQL doesn't tell a null field from a missing one, so the expiry is
+/// removed with `clearExpiredAt: true`.
+#[derive(Default, Debug, GraphQLInputObject)]
+pub struct MemberPatchInput {
+    pub name: Option<String>,
+    pub identity_num: Option<String>,
+    pub role: Option<MemberRole>,
+    pub expired_at: Option<NaiveDateTime>,
+    pub clear_expired_at: Option<bool>,
+}
+
+impl MemberPatch {
+    pub fn into_changeset(self, modification_date: NaiveDateTime) -> MemberChangeset {
+        MemberChangeset {
+            name: self.name.into_change().flatten(),
+            identity_num: self.identity_num.into_change().flatten(),
+            role: self.role.into_change().flatten(),
+            expired_at: self.expired_at.into_change(),
+            modification_date: Some(modification_date),
+        }
+    }
+}
+
+impl Patch for MemberPatch {
+    fn fields(&self) -> Vec<(&'static str, bool)> {
+        vec![
+            ("name", !self.name.is_absent()),
+            ("identity_num", !self.identity_num.is_absent()),
+            ("role", !self.role.is_absent()),
+            ("expired_at", !self.expired_at.is_absent()),
+        ]
+    }
+}
+
+// The rules of `NewMember`, and only `expired_at` may be null.
+impl Validate for MemberPatch {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        if let Some(name) = self.name.as_value() {
+            if !validate_length(name, Some(1), Some(100), None) {
+                errors.add("name", ValidationError::new("name-length-error"));
+            }
+            if !REGEX_FULL_WORD.is_match(name) {
+                errors.add("name", ValidationError::new("name-format-error"));
+            }
+        }
+        if let Some(identity_num) = self.identity_num.as_value() {
+            if !validate_length(identity_num, Some(1), Some(50), None) {
+                errors.add("identity_num", ValidationError::new("identity-num-length-error"));
+            }
+        }
+        for (field, is_null) in [
+            ("name", self.name == MergeField::Null),
+            ("identity_num", self.identity_num == MergeField::Null),
+            ("role", self.role == MergeField::Null),
+        ] {
+            if is_null {
+                errors.add(field, ValidationError::new("field-not-nullable"));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl TryFrom<MemberPatchInput> for MemberPatch {
+    type Error = Error;
+
+    fn try_from(input: MemberPatchInput) -> Result<Self, Error> {
+        let clear_expired_at = input.clear_expired_at.unwrap_or(false);
+        if clear_expired_at && input.expired_at.is_some() {
+            return Err(Error::Validation(vec![ErrorCode {
+                error_code: "expired-at-conflict".to_string(),
+                message: "expiredAt can't be set together with clearExpiredAt.".to_string(),
+                field: Some("expired_at".to_string()),
+            }]));
+        }
+        Ok(MemberPatch {
+            name: input.name.map_or(MergeField::Absent, MergeField::Value),
+            identity_num: input.identity_num.map_or(MergeField::Absent, MergeField::Value),
+            role: input.role.map_or(MergeField::Absent, MergeField::Value),
+            expired_at: match input.expired_at {
+                Some(expired_at) => MergeField::Value(expired_at),
+                None if clear_expired_at => MergeField::Null,
+                None => MergeField::Absent,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::utils::current_timestamp;
+
+    use super::*;
+
+    #[test]
+    fn member_patch_rejects_null_for_the_required_fields() {
+        let member_patch: MemberPatch = serde_json::from_str(r#"{"role": null, "expired_at": null}"#).unwrap();
+        let errors = member_patch.validate().unwrap_err();
+        assert_eq!(errors.field_errors().keys().collect::<Vec<_>>(), vec![&"role"]);
+
+        let member_patch: MemberPatch = serde_json::from_str(r#"{"role": "admin"}"#).unwrap();
+        assert!(member_patch.validate().is_ok());
+        let changeset = member_patch.into_changeset(current_timestamp());
+        assert_eq!(changeset.role, Some(MemberRole::Admin));
+        assert_eq!(changeset.expired_at, None);
+        assert!(serde_json::from_str::<MemberPatch>(r#"{"team_id": null}"#).is_err());
+    }
+}
@@ -1,5 +1,9 @@
 pub mod auth_user;
 pub mod member;
+pub mod page;
+pub mod patch;
 pub mod dto;
+pub mod event;
+pub mod refresh_token;
 pub mod team;
 pub mod user;
@@ -0,0 +1,205 @@
+use uuid::Uuid;
+
+use error::error::Error;
+
+use crate::model::auth_user::AuthUser;
+use crate::model::dto::{PaginatedResponseDTO, PaginationDTO};
+use crate::model::member::Member;
+use crate::model::team::Team;
+use crate::model::user::User;
+
+pub const DEFAULT_PAGE_SIZE: i64 = 20;
+pub const MAX_PAGE_SIZE: i64 = 100;
+
+/// The position of a row in a listing sorted by id. The clients get it as an opaque string.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cursor(pub Uuid);
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        base64::encode_config(self.0.as_bytes(), base64::URL_SAFE_NO_PAD)
+    }
+
+    pub fn decode(value: &str) -> Result<Cursor, Error> {
+        base64::decode_config(value, base64::URL_SAFE_NO_PAD)
+            .ok()
+            .and_then(|bytes| Uuid::from_slice(&bytes).ok())
+            .map(Cursor)
+            .ok_or_else(|| Error::BadRequest(format!("Invalid cursor: {}", value)))
+    }
+}
+
+/// The rows that have a cursor, they are listed in the order of their ids.
+pub trait Keyed {
+    fn key(&self) -> Uuid;
+
+    fn cursor(&self) -> Cursor {
+        Cursor(self.key())
+    }
+}
+
+impl Keyed for AuthUser {
+    fn key(&self) -> Uuid {
+        self.id
+    }
+}
+
+impl Keyed for Member {
+    fn key(&self) -> Uuid {
+        self.id
+    }
+}
+
+impl Keyed for Team {
+    fn key(&self) -> Uuid {
+        self.id
+    }
+}
+
+impl Keyed for User {
+    fn key(&self) -> Uuid {
+        self.id
+    }
+}
+
+/// A keyset page: up to `size` rows whose ids are between the cursors. A backward page takes the
+/// rows just before `before`, the others take the rows just after `after`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageRequest {
+    pub size: i64,
+    pub after: Option<Cursor>,
+    pub before: Option<Cursor>,
+    pub backward: bool,
+    // Only the REST listing without cursors skips rows, it is kept for the existing clients.
+    pub offset: i64,
+    // The soft deleted rows are left out unless it is set.
+    pub include_deleted: bool,
+}
+
+impl PageRequest {
+    pub fn from_pagination(pagination_dto: &PaginationDTO) -> Result<PageRequest, Error> {
+        let after = pagination_dto.after.as_deref().map(Cursor::decode).transpose()?;
+        let before = pagination_dto.before.as_deref().map(Cursor::decode).transpose()?;
+        let has_cursor = after.is_some() || before.is_some();
+        Ok(PageRequest {
+            size: pagination_dto.page_size as i64,
+            backward: before.is_some() && after.is_none(),
+            offset: if has_cursor { 0 } else { pagination_dto.offset as i64 },
+            after,
+            before,
+            include_deleted: pagination_dto.include_deleted,
+        })
+    }
+
+    // Read the `first`, `after`, `last` and `before` arguments of a Relay connection.
+    pub fn from_connection_args(
+        first: Option<i32>,
+        after: Option<String>,
+        last: Option<i32>,
+        before: Option<String>,
+    ) -> Result<PageRequest, Error> {
+        let size = match (first, last) {
+            (Some(_), Some(_)) => return Err(Error::BadRequest("first and last can't be used together".to_string())),
+            (Some(size), None) | (None, Some(size)) => size as i64,
+            (None, None) => DEFAULT_PAGE_SIZE,
+        };
+        if !(1..=MAX_PAGE_SIZE).contains(&size) {
+            return Err(Error::BadRequest(format!("The page size must be between 1 and {}", MAX_PAGE_SIZE)));
+        }
+        Ok(PageRequest {
+            size,
+            after: after.as_deref().map(Cursor::decode).transpose()?,
+            before: before.as_deref().map(Cursor::decode).transpose()?,
+            backward: last.is_some(),
+            offset: 0,
+            include_deleted: false,
+        })
+    }
+}
+
+/// The rows of a [`PageRequest`] in the order of their ids, and whether there are more on each side.
+#[derive(Debug, Clone)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub has_next_page: bool,
+    pub has_previous_page: bool,
+}
+
+impl<T: Keyed> Page<T> {
+    // Build the page from the rows of the query, which reads one row more than the page size
+    // to know whether the page is the last one in its direction.
+    pub fn from_rows(mut rows: Vec<T>, page_request: &PageRequest) -> Page<T> {
+        let has_more = rows.len() as i64 > page_request.size;
+        rows.truncate(page_request.size.max(0) as usize);
+        if page_request.backward {
+            rows.reverse();
+            Page {
+                items: rows,
+                has_next_page: page_request.before.is_some(),
+                has_previous_page: has_more,
+            }
+        } else {
+            Page {
+                items: rows,
+                has_next_page: has_more,
+                has_previous_page: page_request.after.is_some() || page_request.offset > 0,
+            }
+        }
+    }
+
+    pub fn next_cursor(&self) -> Option<String> {
+        self.items.last().filter(|_| self.has_next_page).map(|item| item.cursor().encode())
+    }
+
+    pub fn prev_cursor(&self) -> Option<String> {
+        self.items.first().filter(|_| self.has_previous_page).map(|item| item.cursor().encode())
+    }
+
+    pub fn into_response(self, count: i64) -> PaginatedResponseDTO<T> {
+        PaginatedResponseDTO {
+            next_cursor: self.next_cursor(),
+            prev_cursor: self.prev_cursor(),
+            paginated_list: self.items,
+            count,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn team(index: u128) -> Team {
+        Team {
+            id: Uuid::from_u128(index),
+            name: format!("team{}", index),
+            description: String::new(),
+            version: 1,
+            deleted_at: None,
+        }
+    }
+
+    #[test]
+    fn cursor_round_trip() {
+        let cursor = Cursor(Uuid::new_v4());
+
+        assert_eq!(Cursor::decode(&cursor.encode()).unwrap(), cursor);
+        assert!(Cursor::decode("not a cursor").is_err());
+    }
+
+    #[test]
+    fn page_from_rows() {
+        let forward = PageRequest::from_connection_args(Some(2), None, None, None).unwrap();
+        let page = Page::from_rows(vec![team(1), team(2), team(3)], &forward);
+        assert_eq!(page.items.iter().map(|team| team.id).collect::<Vec<_>>(), vec![Uuid::from_u128(1), Uuid::from_u128(2)]);
+        assert_eq!(page.next_cursor(), Some(Cursor(Uuid::from_u128(2)).encode()));
+        assert_eq!(page.prev_cursor(), None);
+
+        // The backward query reads the rows in the descending order of their ids.
+        let backward = PageRequest::from_connection_args(None, None, Some(2), Some(Cursor(Uuid::from_u128(4)).encode())).unwrap();
+        let page = Page::from_rows(vec![team(3), team(2)], &backward);
+        assert_eq!(page.items.iter().map(|team| team.id).collect::<Vec<_>>(), vec![Uuid::from_u128(2), Uuid::from_u128(3)]);
+        assert!(page.has_next_page);
+        assert!(!page.has_previous_page);
+    }
+}
@@ -0,0 +1,129 @@
+use paperclip::v2::models::DefaultSchemaRaw;
+use paperclip::v2::schema::Apiv2Schema;
+use serde::{Deserialize, Deserializer};
+
+use error::error::{Error, ErrorCode};
+
+/// The input of the partial updates: a field that is not set keeps its stored value.
+///
+/// The PATCH routes and the `patch*` mutations take a patch as it is. A PUT replaces the object, so it takes
+/// the same patch with every field set.
+pub trait Patch {
+    // The fields of the patch as the clients name them, with whether they are set.
+    fn fields(&self) -> Vec<(&'static str, bool)>;
+
+    fn is_empty(&self) -> bool {
+        self.fields().iter().all(|(_, is_set)| !is_set)
+    }
+
+    // Fail with a `field-required` error for every field that is not set.
+    fn require_all(&self) -> Result<(), Error> {
+        let errors: Vec<ErrorCode> = self
+            .fields()
+            .into_iter()
+            .filter(|(_, is_set)| !is_set)
+            .map(|(field, _)| ErrorCode {
+                error_code: "field-required".to_string(),
+                message: format!("The value of {} is required.", field),
+                field: Some(field.to_string()),
+            })
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Validation(errors))
+        }
+    }
+}
+
+/// A field of a JSON merge patch ([RFC 7396](https://tools.ietf.org/html/rfc7396)): a key that is absent keeps the
+/// stored value, `null` clears it and any other value replaces it. The fields need `#[serde(default)]`, so the
+/// absent keys are left `Absent`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum MergeField<T> {
+    #[default]
+    Absent,
+    Null,
+    Value(T),
+}
+
+impl<T> MergeField<T> {
+    pub fn is_absent(&self) -> bool {
+        matches!(self, MergeField::Absent)
+    }
+
+    pub fn as_value(&self) -> Option<&T> {
+        match self {
+            MergeField::Value(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    // The change of a nullable column: None keeps it, Some(None) sets it to NULL.
+    pub fn into_change(self) -> Option<Option<T>> {
+        match self {
+            MergeField::Absent => None,
+            MergeField::Null => Some(None),
+            MergeField::Value(value) => Some(Some(value)),
+        }
+    }
+}
+
+// Only a present key is deserialized, so `null` is told apart from the absent keys.
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for MergeField<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match Option::<T>::deserialize(deserializer)? {
+            Some(value) => MergeField::Value(value),
+            None => MergeField::Null,
+        })
+    }
+}
+
+// The OpenAPI spec documents the field like its value.
+impl<T: Apiv2Schema> Apiv2Schema for MergeField<T> {
+    fn raw_schema() -> DefaultSchemaRaw {
+        T::raw_schema()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::team::TeamPatch;
+
+    use super::*;
+
+    #[test]
+    fn require_all_reports_the_missing_fields() {
+        assert!(TeamPatch::default().is_empty());
+
+        let team_patch = TeamPatch {
+            name: Some("Backend".to_string()),
+            description: None,
+        };
+        assert!(!team_patch.is_empty());
+        match team_patch.require_all() {
+            Err(Error::Validation(errors)) => {
+                assert_eq!(errors.len(), 1);
+                assert_eq!(errors[0].field.as_deref(), Some("description"));
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[derive(Default, Deserialize)]
+    #[serde(default)]
+    struct Document {
+        expired_at: MergeField<String>,
+    }
+
+    #[test]
+    fn merge_field_tells_null_from_absent() {
+        let absent: Document = serde_json::from_str("{}").unwrap();
+        let null: Document = serde_json::from_str(r#"{"expired_at": null}"#).unwrap();
+        let value: Document = serde_json::from_str(r#"{"expired_at": "2022-03-01T00:00:00"}"#).unwrap();
+
+        assert_eq!(absent.expired_at.into_change(), None);
+        assert_eq!(null.expired_at.into_change(), Some(None));
+        assert_eq!(value.expired_at, MergeField::Value("2022-03-01T00:00:00".to_string()));
+    }
+}
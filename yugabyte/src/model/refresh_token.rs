@@ -0,0 +1,17 @@
+use chrono::NaiveDateTime;
+use diesel::{Insertable, Queryable};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::schema::refresh_token;
+
+/// The server side record of an issued refresh token, the token itself only carries its id.
+#[derive(Debug, Serialize, Deserialize, Queryable, Insertable, Clone)]
+#[table_name = "refresh_token"]
+pub struct RefreshToken {
+    pub id: Uuid,
+    pub auth_user_id: Uuid,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub revoked_at: Option<NaiveDateTime>,
+}
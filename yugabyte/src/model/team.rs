@@ -1,21 +1,94 @@
-use diesel::{Insertable, Queryable};
-use juniper::GraphQLObject;
+use chrono::NaiveDateTime;
+use diesel::{AsChangeset, Insertable, Queryable};
+use juniper::GraphQLInputObject;
 use paperclip::actix::Apiv2Schema;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
+use validator::Validate;
 
+use error::error::Error;
+
+use crate::auth::policy::Action;
+use crate::context::GraphQLContext;
+use crate::model::member::Member;
+use crate::model::patch::Patch;
 use crate::schema::team;
+use crate::util::utils::REGEX_FULL_WORD;
 
-#[derive(Debug, Serialize, Deserialize, Queryable, Insertable, Clone, GraphQLObject, Apiv2Schema)]
+#[derive(Debug, Serialize, Deserialize, Queryable, Insertable, Clone, Apiv2Schema)]
 #[table_name = "team"]
 pub struct Team {
     pub id: Uuid,
     pub name: String,
     pub description: String,
+    // It is incremented by every update, the outbox events written before it existed have none.
+    #[serde(default)]
+    pub version: i32,
+    // Set when the team is deleted, it is listed again once restored.
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+#[juniper::graphql_object(Context = GraphQLContext)]
+impl Team {
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    pub fn deleted_at(&self) -> Option<NaiveDateTime> {
+        self.deleted_at
+    }
+
+    // Only the members of the team can see who else is in it.
+    pub fn members(&self, context: &GraphQLContext) -> Result<Vec<Member>, Error> {
+        context.authorize(Action::ViewTeam, Some(&self.id))?;
+
+        let members = context.loaders.members_by_team.load(self.id, context)?.unwrap_or_default();
+        context.loaders.defer_members(&members);
+        Ok(members)
+    }
 }
 
-#[derive(Default, Debug, Serialize, Deserialize, Apiv2Schema)]
+#[derive(Default, Debug, Serialize, Deserialize, Apiv2Schema, GraphQLInputObject, Validate)]
 pub struct NewTeam {
+    #[validate(length(min = 1, max = 100, code = "name-length-error"), regex(path = "REGEX_FULL_WORD", code = "name-format-error"))]
     pub name: String,
+    #[validate(length(min = 1, max = 1000, code = "description-length-error"))]
     pub description: String,
 }
+
+// The fields of the team to change, see Patch.
+#[derive(Default, Debug, Clone, Serialize, Deserialize, Apiv2Schema, GraphQLInputObject, Validate, AsChangeset)]
+#[table_name = "team"]
+pub struct TeamPatch {
+    #[validate(length(min = 1, max = 100, code = "name-length-error"), regex(path = "REGEX_FULL_WORD", code = "name-format-error"))]
+    pub name: Option<String>,
+    #[validate(length(min = 1, max = 1000, code = "description-length-error"))]
+    pub description: Option<String>,
+}
+
+impl Patch for TeamPatch {
+    fn fields(&self) -> Vec<(&'static str, bool)> {
+        vec![("name", self.name.is_some()), ("description", self.description.is_some())]
+    }
+}
+
+impl From<NewTeam> for TeamPatch {
+    fn from(new_team: NewTeam) -> Self {
+        TeamPatch {
+            name: Some(new_team.name),
+            description: Some(new_team.description),
+        }
+    }
+}
@@ -1,26 +1,91 @@
-use diesel::{Insertable, Queryable};
+use chrono::NaiveDateTime;
+use diesel::{AsChangeset, Insertable, Queryable};
 use juniper::GraphQLInputObject;
-use juniper::GraphQLObject;
 use paperclip::actix::Apiv2Schema;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use validator::Validate;
 
+use error::error::Error;
+
+use crate::context::GraphQLContext;
+use crate::model::member::Member;
+use crate::model::patch::Patch;
 use crate::schema::user;
+use crate::util::utils::REGEX_FULL_WORD;
 
-#[derive(Debug, Serialize, Deserialize, Queryable, Insertable, Validate, Clone, GraphQLObject, Apiv2Schema)]
+#[derive(Debug, Serialize, Deserialize, Queryable, Insertable, Validate, Clone, Apiv2Schema)]
 #[table_name = "user"]
 pub struct User {
     pub id: Uuid,
     #[validate(email(code = "email-format-error"))]
     pub email: String,
     pub name: String,
+    // It is incremented by every update.
+    pub version: i32,
+    // Set when the user is deleted, it is listed again once restored.
+    pub deleted_at: Option<NaiveDateTime>,
+    // The auth_user that signs in as this user, its roles in the teams are the roles of that auth_user.
+    pub auth_user_id: Option<Uuid>,
+}
+
+#[juniper::graphql_object(Context = GraphQLContext)]
+impl User {
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub fn email(&self) -> &str {
+        &self.email
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    pub fn deleted_at(&self) -> Option<NaiveDateTime> {
+        self.deleted_at
+    }
+
+    pub fn auth_user_id(&self) -> Option<Uuid> {
+        self.auth_user_id
+    }
+
+    // The teams the user is a member of, with the role in each of them. Only the teams the principal can view are given.
+    pub fn memberships(&self, context: &GraphQLContext) -> Result<Vec<Member>, Error> {
+        let mut memberships = context.loaders.members_by_user.load(self.id, context)?.unwrap_or_default();
+        if let Some(visible_team_ids) = context.visible_team_ids()? {
+            memberships.retain(|membership| visible_team_ids.contains(&membership.team_id));
+        }
+        context.loaders.defer_members(&memberships);
+        Ok(memberships)
+    }
 }
 
 #[derive(Default, Debug, Serialize, Deserialize, Apiv2Schema, GraphQLInputObject, Validate)]
 pub struct NewUser {
     #[validate(email(code = "email-format-error"))]
     pub email: String,
+    #[validate(length(min = 1, max = 100, code = "name-length-error"), regex(path = "REGEX_FULL_WORD", code = "name-format-error"))]
     pub name: String,
-    pub password: String,
-}
\ No newline at end of file
+}
+
+// The fields of the user to change, see Patch. A new email is also given to the linked auth_user.
+#[derive(Default, Debug, Clone, Serialize, Deserialize, Apiv2Schema, GraphQLInputObject, Validate, AsChangeset)]
+#[table_name = "user"]
+pub struct UserPatch {
+    #[validate(email(code = "email-format-error"))]
+    pub email: Option<String>,
+    #[validate(length(min = 1, max = 100, code = "name-length-error"), regex(path = "REGEX_FULL_WORD", code = "name-format-error"))]
+    pub name: Option<String>,
+}
+
+impl Patch for UserPatch {
+    fn fields(&self) -> Vec<(&'static str, bool)> {
+        vec![("email", self.email.is_some()), ("name", self.name.is_some())]
+    }
+}
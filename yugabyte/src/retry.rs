@@ -0,0 +1,187 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use lazy_static::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+
+use error::error::Error;
+
+use crate::config::config;
+
+// YugabyteDB reports these conditions with SQLSTATE 40001, or with only the message on older versions.
+const RETRYABLE_MESSAGES: [&str; 4] = [
+    "restart read required",
+    "try again",
+    "could not serialize access",
+    "deadlock detected",
+];
+
+lazy_static! {
+    pub static ref RETRY_POLICY: RetryPolicy = config().retry;
+}
+
+static RETRIED_ATTEMPTS: AtomicU64 = AtomicU64::new(0);
+static EXHAUSTED_RETRIES: AtomicU64 = AtomicU64::new(0);
+
+/// Bounded exponential backoff for transactions that failed with a retryable error, the `retry` section
+/// of the [`Config`](crate::config::Config).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryPolicy {
+    // The total number of attempts, including the first one.
+    pub max_attempts: u32,
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+}
+
+/// The retry counters since the process started.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct RetryMetrics {
+    pub retried_attempts: u64,
+    pub exhausted_retries: u64,
+}
+
+/// Errors that tell whether running the same unit of work again may succeed.
+pub trait Retryable {
+    fn is_retryable(&self) -> bool;
+}
+
+impl Retryable for DieselError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            DieselError::DatabaseError(DatabaseErrorKind::SerializationFailure, _) => true,
+            DieselError::DatabaseError(_, info) => {
+                let message = info.message().to_lowercase();
+                RETRYABLE_MESSAGES.iter().any(|retryable_message| message.contains(retryable_message))
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Retryable for Error {
+    fn is_retryable(&self) -> bool {
+        match self {
+            Error::DBError(error) => error.is_retryable(),
+            _ => false,
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 5,
+            base_delay_ms: 20,
+            max_delay_ms: 1_000,
+        }
+    }
+}
+
+impl RetryPolicy {
+    // The upper bound of the delay before the given retry, it doubles with every retry up to max_delay_ms.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let exponential = self.base_delay_ms.saturating_mul(1u64 << retry.saturating_sub(1).min(32));
+        Duration::from_millis(exponential.min(self.max_delay_ms))
+    }
+
+    // Pick a random delay between half of the backoff and the backoff, so the competing transactions don't retry in lockstep.
+    fn jittered_backoff(&self, retry: u32) -> Duration {
+        let backoff = self.backoff(retry).as_millis() as u64;
+        Duration::from_millis(rand::thread_rng().gen_range(backoff / 2..=backoff))
+    }
+
+    // Run the unit of work until it succeeds, fails with a non retryable error, or runs out of attempts.
+    pub fn run<T, E, F>(&self, mut unit_of_work: F) -> Result<T, E>
+        where
+            F: FnMut() -> Result<T, E>,
+            E: Retryable + std::fmt::Display,
+    {
+        let mut attempt = 1;
+        loop {
+            match unit_of_work() {
+                Err(err) if err.is_retryable() => {
+                    if attempt >= self.max_attempts {
+                        EXHAUSTED_RETRIES.fetch_add(1, Ordering::Relaxed);
+                        error!(attempt, error = %err, "Giving up on the transaction after the last retry");
+                        return Err(err);
+                    }
+                    let delay = self.jittered_backoff(attempt);
+                    RETRIED_ATTEMPTS.fetch_add(1, Ordering::Relaxed);
+                    warn!(attempt, delay_ms = delay.as_millis() as u64, error = %err, "Retrying the transaction");
+                    thread::sleep(delay);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+pub fn retry_metrics() -> RetryMetrics {
+    RetryMetrics {
+        retried_attempts: RETRIED_ATTEMPTS.load(Ordering::Relaxed),
+        exhausted_retries: EXHAUSTED_RETRIES.load(Ordering::Relaxed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    struct FakeError(bool);
+
+    impl Retryable for FakeError {
+        fn is_retryable(&self) -> bool {
+            self.0
+        }
+    }
+
+    impl std::fmt::Display for FakeError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "fake error")
+        }
+    }
+
+    fn fast_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay_ms: 1,
+            max_delay_ms: 2,
+        }
+    }
+
+    #[test]
+    fn backoff_is_bounded() {
+        let policy = RetryPolicy::default();
+
+        assert_eq!(policy.backoff(1), Duration::from_millis(20));
+        assert_eq!(policy.backoff(2), Duration::from_millis(40));
+        assert_eq!(policy.backoff(40), Duration::from_millis(1_000));
+    }
+
+    #[test]
+    fn run_retries_only_retryable_errors() {
+        let attempts = Cell::new(0);
+        let result: Result<(), FakeError> = fast_policy().run(|| {
+            attempts.set(attempts.get() + 1);
+            Err(FakeError(true))
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts.get(), 3);
+
+        attempts.set(0);
+        let result: Result<(), FakeError> = fast_policy().run(|| {
+            attempts.set(attempts.get() + 1);
+            Err(FakeError(false))
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts.get(), 1);
+    }
+}
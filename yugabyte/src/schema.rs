@@ -3,6 +3,7 @@ table! {
         id -> Uuid,
         email -> Varchar,
         password -> Varchar,
+        is_admin -> Bool,
     }
 }
 
@@ -17,6 +18,27 @@ table! {
         assigned_at -> Timestamp,
         expired_at -> Nullable<Timestamp>,
         modification_date -> Nullable<Timestamp>,
+        version -> Int4,
+        deleted_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    outbox_event (id) {
+        id -> Uuid,
+        created_at -> Timestamp,
+        team_id -> Uuid,
+        payload -> Text,
+    }
+}
+
+table! {
+    refresh_token (id) {
+        id -> Uuid,
+        auth_user_id -> Uuid,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+        revoked_at -> Nullable<Timestamp>,
     }
 }
 
@@ -25,6 +47,8 @@ table! {
         id -> Uuid,
         name -> Varchar,
         description -> Varchar,
+        version -> Int4,
+        deleted_at -> Nullable<Timestamp>,
     }
 }
 
@@ -33,15 +57,22 @@ table! {
         id -> Uuid,
         email -> Varchar,
         name -> Varchar,
+        version -> Int4,
+        deleted_at -> Nullable<Timestamp>,
+        auth_user_id -> Nullable<Uuid>,
     }
 }
 
 joinable!(member -> team (team_id));
 joinable!(member -> user (user_id));
+joinable!(refresh_token -> auth_user (auth_user_id));
+joinable!(user -> auth_user (auth_user_id));
 
 allow_tables_to_appear_in_same_query!(
     auth_user,
     member,
+    outbox_event,
+    refresh_token,
     team,
     user,
 );
@@ -1 +1,2 @@
+pub mod raw_query;
 pub mod utils;
\ No newline at end of file
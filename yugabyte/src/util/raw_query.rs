@@ -0,0 +1,132 @@
+use std::marker::PhantomData;
+
+use diesel::pg::Pg;
+use diesel::{Connection, PgConnection, QueryResult};
+use diesel::deserialize::QueryableByName;
+use diesel::query_builder::{AstPass, QueryFragment, QueryId, SqlQuery};
+use diesel::serialize::ToSql;
+use diesel::sql_types::HasSqlType;
+
+use error::error::Error;
+
+/// A raw SQL query for the reports diesel's query builder can't express.
+///
+/// The SQL is a `&'static str`, so it can't be built from the input with `format!`: the values are passed with
+/// [`RawQuery::bind`] and referenced as `$1`, `$2`... in the SQL. The query fails before reaching the database when
+/// the number of bound values doesn't match the placeholders. `diesel::sql_query` is disallowed everywhere else.
+pub struct RawQuery<Q> {
+    query: Q,
+    sql: &'static str,
+    binds: usize,
+}
+
+// A value bound to the next placeholder of the query.
+pub struct Bind<Q, V, ST> {
+    query: Q,
+    value: V,
+    _sql_type: PhantomData<ST>,
+}
+
+#[allow(clippy::disallowed_methods)]
+pub fn raw_query(sql: &'static str) -> RawQuery<SqlQuery> {
+    RawQuery {
+        query: diesel::sql_query(sql),
+        sql,
+        binds: 0,
+    }
+}
+
+impl<Q> RawQuery<Q> {
+    pub fn bind<ST, V>(self, value: V) -> RawQuery<Bind<Q, V, ST>> {
+        RawQuery {
+            query: Bind {
+                query: self.query,
+                value,
+                _sql_type: PhantomData,
+            },
+            sql: self.sql,
+            binds: self.binds + 1,
+        }
+    }
+
+    pub fn load<T>(self, connection: &PgConnection) -> Result<Vec<T>, Error>
+        where
+            Q: QueryFragment<Pg> + QueryId,
+            T: QueryableByName<Pg>,
+    {
+        self.check_binds()?;
+        connection.query_by_name(&self.query).map_err(Error::from)
+    }
+
+    pub fn execute(self, connection: &PgConnection) -> Result<usize, Error>
+        where
+            Q: QueryFragment<Pg> + QueryId,
+    {
+        self.check_binds()?;
+        connection.execute_returning_count(&self.query).map_err(Error::from)
+    }
+
+    fn check_binds(&self) -> Result<(), Error> {
+        let placeholders = placeholder_count(self.sql);
+        if placeholders == self.binds {
+            Ok(())
+        } else {
+            Err(Error::InternalServerError(format!(
+                "The query has {} placeholders but {} bound values: {}",
+                placeholders, self.binds, self.sql
+            )))
+        }
+    }
+}
+
+impl<Q, V, ST> QueryFragment<Pg> for Bind<Q, V, ST>
+    where
+        Q: QueryFragment<Pg>,
+        V: ToSql<ST, Pg>,
+        Pg: HasSqlType<ST>,
+{
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        self.query.walk_ast(out.reborrow())?;
+        // The placeholder is already in the SQL, only its value is added.
+        out.push_bind_param_value_only(&self.value)
+    }
+}
+
+// The raw queries aren't cached as prepared statements, like diesel's own `sql_query`.
+impl<Q, V, ST> QueryId for Bind<Q, V, ST> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+// The highest `$n` of the SQL, the placeholders may be used more than once.
+fn placeholder_count(sql: &str) -> usize {
+    let mut count = 0;
+    let mut rest = sql;
+    while let Some(index) = rest.find('$') {
+        rest = &rest[index + 1..];
+        let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+        if let Ok(number) = digits.parse::<usize>() {
+            count = count.max(number);
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::sql_types::{Integer, Text};
+
+    use super::*;
+
+    #[test]
+    fn check_binds_matches_the_placeholders() {
+        let query = raw_query("SELECT name FROM member WHERE team_id = $1 AND role = $2 OR $2 IS NULL");
+        assert!(query.check_binds().is_err());
+
+        let query = query.bind::<Text, _>("team").bind::<Integer, _>(1);
+        assert!(query.check_binds().is_ok());
+        assert!(query.bind::<Integer, _>(2).check_binds().is_err());
+        assert_eq!(placeholder_count("SELECT count(*) FROM member"), 0);
+    }
+}
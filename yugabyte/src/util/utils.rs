@@ -19,6 +19,22 @@ pub fn expiration_date(
 }
 
 lazy_static! {
-    pub static ref REGEX_FULL_WORD: Regex = Regex::new(r"^[a-zA-Z ._-]*$").unwrap();   // examples: "abdelaziz", "Muhammad Ali", "abdelaziz-said", "abdelaziz_said", "abdelaziz.said"
+    // Letters of any script, digits, spaces, apostrophes and `._-`, with at least one letter or digit.
+    pub static ref REGEX_FULL_WORD: Regex = Regex::new(r"^[\p{L}\p{M}\p{N} '’._-]*[\p{L}\p{N}][\p{L}\p{M}\p{N} '’._-]*$").unwrap();   // examples: "abdelaziz", "Muhammad Ali", "abdelaziz-said", "José", "O'Brien", "Team 2"
     pub static ref REGEX_WORD: Regex = Regex::new(r"^[a-zA-Z]+$").unwrap();   // examples: "abdelaziz"
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_word_accepts_the_names_of_any_script() {
+        for name in ["abdelaziz", "Muhammad Ali", "abdelaziz_said", "abdelaziz.said", "José", "O'Brien", "Team 2", "عبد العزيز"] {
+            assert!(REGEX_FULL_WORD.is_match(name), "{}", name);
+        }
+        for name in ["", "   ", "._-", "Team #2", "a\nb"] {
+            assert!(!REGEX_FULL_WORD.is_match(name), "{:?}", name);
+        }
+    }
+}